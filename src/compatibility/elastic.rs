@@ -10,12 +10,39 @@ use crate::core::storage::Db;
 use crate::core::ai::BgeM3;
 use half::f16;
 use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Listener state of the mask, for INFO zedis.
+#[derive(Default)]
+pub struct MaskStatus(AtomicU8);
+
+impl MaskStatus {
+    const DISABLED: u8 = 0;
+    const LISTENING: u8 = 1;
+    const FAILED: u8 = 2;
+    const STOPPED: u8 = 3;
+
+    fn set(&self, state: u8) {
+        self.0.store(state, Ordering::Relaxed);
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.0.load(Ordering::Relaxed) {
+            Self::DISABLED => "disabled",
+            Self::LISTENING => "listening",
+            Self::FAILED => "failed",
+            _ => "stopped",
+        }
+    }
+}
 
 // Mask State
 #[derive(Clone)]
 pub struct ElasticMask {
     pub db: Arc<Db>,
     pub bge: Option<Arc<BgeM3>>,
+    pub status: Arc<MaskStatus>,
 }
 
 // Request Models
@@ -61,7 +88,9 @@ pub struct HitItem {
 }
 
 impl ElasticMask {
-    pub async fn run(self, port: u16) {
+    /// Serve until `shutdown` is cancelled, then finish in-flight requests.
+    pub async fn run(self, port: u16, shutdown: CancellationToken) {
+        let status = self.status.clone();
         let app = Router::new()
             .route("/", get(root_info))
             .route("/:index/_search", post(handle_search))
@@ -70,10 +99,21 @@ impl ElasticMask {
 
         let addr = format!("0.0.0.0:{}", port);
         log::info!("🎭 Z-Mask (Generic Elastic) listening on {}", addr);
-        
-        if let Ok(listener) = TcpListener::bind(addr).await {
-            if let Err(e) = axum::serve(listener, app).await {
-                log::error!("Mask server error: {}", e);
+
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                status.set(MaskStatus::LISTENING);
+                let served = axum::serve(listener, app).with_graceful_shutdown(shutdown.cancelled_owned()).await;
+                if let Err(e) = served {
+                    log::error!("Mask server error: {}", e);
+                    status.set(MaskStatus::FAILED);
+                } else {
+                    status.set(MaskStatus::STOPPED);
+                }
+            }
+            Err(e) => {
+                log::error!("🎭 Z-Mask: bind failed: {}", e);
+                status.set(MaskStatus::FAILED);
             }
         }
     }
@@ -115,7 +155,8 @@ async fn handle_search(
     let mut hits = Vec::new();
     if let Some(bge) = &mask.bge {
         if let Ok((dense, sparse)) = bge.embed_hybrid(&query_text) {
-             let results = mask.db.vsearch_hybrid(&index, dense, Some(sparse), 10, 0.5);
+             // A key of another type under the index name just has no hits
+             let results = mask.db.vsearch_hybrid(index.as_bytes(), dense, Some(sparse), 10, 0.5).unwrap_or_default();
              for (id, score) in results {
                  hits.push(HitItem {
                      _index: index.clone(),
@@ -166,8 +207,7 @@ async fn handle_index(
             // Use 'index' as the key prefix or collection? 
             // Zedis MVP is flat key space. We'll use "index:id" as key.
             let key = format!("{}:{}", index, id);
-            mask.db.vadd_hybrid(key, dense, Some(sparse));
-            embedded = true;
+            embedded = mask.db.vadd_hybrid(key.into(), dense, Some(sparse)).is_ok();
         }
     }
 
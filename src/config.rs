@@ -1,10 +1,142 @@
+use crate::core::client::{ClientClass, OutputBufferLimit, OutputBufferLimits};
+use crate::core::evict::EvictionPolicy;
+use crate::core::notify;
+use crate::core::protocol::{split_inline_args, ProtocolLimits};
+use crate::persistence::FsyncPolicy;
+use crate::security::tls::TlsSettings;
+use crate::core::glob::glob_match;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use std::path::Path;
+use std::time::Duration;
+
+/// Config file read when none is given on the command line and it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "zedis.conf";
+
+/// Every directive, in CONFIG GET / CONFIG REWRITE order.
+pub const PARAMETERS: &[&str] = &[
+    "bind", "port", "unixsocket", "unixsocketperm", "tls-port", "tls-cert-file", "tls-key-file",
+    "tls-ca-cert-file", "tls-auth-clients", "worker-threads", "shadow-addr", "maxclients", "timeout",
+    "tcp-keepalive", "proto-max-bulk-len", "max-multibulk-len", "client-query-buffer-limit",
+    "client-output-buffer-limit", "maxmemory", "maxmemory-policy", "maxmemory-samples",
+    "notify-keyspace-events", "slowlog-log-slower-than", "slowlog-max-len", "latency-monitor-threshold",
+    "shutdown-timeout", "save-on-shutdown", "databases", "dir", "dbfilename",
+    "appendonly", "appendfilename", "appendfsync", "bge-model-dir", "zflow-config", "elastic-port",
+    "ddos-burst", "ddos-rate",
+];
+
+/// Directives that only take effect at startup; CONFIG SET refuses them.
+const IMMUTABLE: &[&str] = &[
+    "bind", "port", "unixsocket", "unixsocketperm", "tls-port", "worker-threads", "shadow-addr",
+    "appendfilename", "bge-model-dir", "elastic-port", "databases",
+];
+
+/// Prefix of environment overrides: `ZEDIS_TLS_PORT=6380` sets `tls-port`.
+const ENV_PREFIX: &str = "ZEDIS_";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Unknown option '{0}'")]
+    Unknown(String),
+    #[error("Invalid argument '{value}' for '{name}': {reason}")]
+    Invalid { name: String, value: String, reason: String },
+    #[error("{origin}: {source}")]
+    At { origin: String, source: Box<ConfigError> },
+    #[error("Can't open config file '{path}': {source}")]
+    Io { path: String, source: std::io::Error },
+    #[error("Can't parse config file '{path}': {reason}")]
+    Parse { path: String, reason: String },
+    #[error("Can't set immutable config '{0}'")]
+    Immutable(String),
+    #[error("Applying '{name}' failed: {reason}")]
+    Apply { name: String, reason: String },
+    #[error("{0}")]
+    Validation(String),
+}
+
+impl ConfigError {
+    fn invalid(name: &str, value: &str, reason: impl Into<String>) -> Self {
+        ConfigError::Invalid { name: name.to_string(), value: value.to_string(), reason: reason.into() }
+    }
+
+    fn at(self, origin: impl Into<String>) -> Self {
+        ConfigError::At { origin: origin.into(), source: Box::new(self) }
+    }
+}
+
+/// Every server setting. Directive names follow redis.conf where Redis has
+/// an equivalent; the same names are used by the config file, `--flags`,
+/// `ZEDIS_*` variables and CONFIG SET.
 #[derive(Debug, Clone)]
 pub struct Config {
     pub port: u16,
+    /// bind
     pub host: String,
     #[allow(dead_code)]
     pub worker_threads: usize,
     pub shadow_addr: Option<String>,
+    /// Unix socket path, served alongside TCP (or alone when `port` is 0).
+    pub unixsocket: Option<String>,
+    pub unixsocketperm: u32,
+    /// TLS listener port; 0 disables TLS.
+    pub tls_port: u16,
+    pub tls_cert_file: String,
+    pub tls_key_file: String,
+    /// CA bundle for client certificates. Setting it enables mutual TLS.
+    pub tls_ca_cert_file: Option<String>,
+    /// tls-auth-clients: with a CA set, require (true) or merely accept (false) client certs.
+    pub tls_auth_clients: bool,
+    // Protocol safety limits (see core::protocol::ProtocolLimits)
+    pub proto_max_bulk_len: usize,
+    pub max_multibulk_len: usize,
+    pub client_query_buffer_limit: usize,
+    /// maxclients: connections beyond this get `-ERR max number of clients reached`.
+    pub maxclients: usize,
+    /// timeout: close clients idle this long (0 = never).
+    pub timeout: Duration,
+    /// tcp-keepalive: SO_KEEPALIVE probe time (0 = off).
+    pub tcp_keepalive: Duration,
+    /// client-output-buffer-limit for the normal, replica and pubsub classes.
+    pub client_output_buffer_limits: OutputBufferLimits,
+    /// databases: number of logical databases (SELECT 0..N-1).
+    pub databases: usize,
+    /// maxmemory in bytes (0 = no limit). `None` is `auto`: the HardwareManager
+    /// recommendation for this machine.
+    pub maxmemory: Option<usize>,
+    /// maxmemory-policy; `None` is `auto`, like maxmemory.
+    pub maxmemory_policy: Option<EvictionPolicy>,
+    /// maxmemory-samples: keys sampled per eviction round.
+    pub maxmemory_samples: usize,
+    /// notify-keyspace-events classes (core::notify); 0 = off.
+    pub notify_keyspace_events: u32,
+    /// slowlog-log-slower-than in microseconds; negative = off, 0 = every command.
+    pub slowlog_log_slower_than: i64,
+    /// slowlog-max-len: entries kept by SLOWLOG.
+    pub slowlog_max_len: usize,
+    /// latency-monitor-threshold in milliseconds; 0 = off.
+    pub latency_monitor_threshold: u64,
+    /// shutdown-timeout: how long in-flight clients get to finish on shutdown.
+    pub shutdown_timeout: Duration,
+    /// Write the RDB snapshot on SIGTERM / plain SHUTDOWN.
+    pub save_on_shutdown: bool,
+    // Persistence
+    /// Working directory; every relative path (RDB, AOF, TLS files, ...) resolves in it.
+    pub dir: String,
+    pub dbfilename: String,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    pub appendfsync: FsyncPolicy,
+    // Zedis subsystems
+    /// Directory holding the BGE-M3 model files.
+    pub bge_model_dir: String,
+    /// Z-Flow pipeline definitions.
+    pub zflow_config: String,
+    /// Z-Mask Elasticsearch emulation port; 0 disables it.
+    pub elastic_port: u16,
+    /// DdosGuard token bucket: burst size and refill rate per client IP.
+    pub ddos_burst: u32,
+    pub ddos_rate: f64,
+    /// File the settings were loaded from; target of CONFIG REWRITE.
+    pub config_file: Option<String>,
 }
 
 impl Default for Config {
@@ -14,6 +146,770 @@ impl Default for Config {
             host: "127.0.0.1".to_string(),
             worker_threads: num_cpus::get(),
             shadow_addr: None, // e.g., Some("127.0.0.1:6380".to_string())
+            unixsocket: None, // e.g., Some("/tmp/zedis.sock".to_string())
+            unixsocketperm: 0, // e.g., 0o700
+            tls_port: 0, // e.g., 6380
+            tls_cert_file: "cert.pem".to_string(),
+            tls_key_file: "key.pem".to_string(),
+            tls_ca_cert_file: None,
+            tls_auth_clients: true,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: 1024 * 1024,
+            client_query_buffer_limit: 1024 * 1024 * 1024,
+            maxclients: 10000,
+            timeout: Duration::ZERO,
+            tcp_keepalive: Duration::from_secs(300),
+            client_output_buffer_limits: OutputBufferLimits::default(),
+            databases: 16,
+            maxmemory: None,
+            maxmemory_policy: None,
+            maxmemory_samples: 5,
+            notify_keyspace_events: 0,
+            slowlog_log_slower_than: 10_000,
+            slowlog_max_len: 128,
+            latency_monitor_threshold: 0,
+            shutdown_timeout: Duration::from_secs(10),
+            save_on_shutdown: true,
+            dir: ".".to_string(),
+            dbfilename: "dump.rdb".to_string(),
+            appendonly: true,
+            appendfilename: "appendonly.aof".to_string(),
+            appendfsync: FsyncPolicy::EverySec,
+            bge_model_dir: "bge-m3".to_string(),
+            zflow_config: "zflow.toml".to_string(),
+            elastic_port: 9200,
+            ddos_burst: 1000,
+            ddos_rate: 100.0,
+            config_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, then the config file, then `ZEDIS_*` variables, then
+    /// command-line flags; validated at the end.
+    ///
+    /// Usage: `zedis [/path/to/zedis.conf|zedis.toml] [--<option> <value>...]`
+    pub fn load(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut args = args.into_iter().peekable();
+        let mut config = Config::default();
+
+        let file = match args.peek() {
+            Some(first) if !first.starts_with("--") => args.next(),
+            _ => Path::new(DEFAULT_CONFIG_FILE).exists().then(|| DEFAULT_CONFIG_FILE.to_string()),
+        };
+        if let Some(path) = file {
+            config.apply_file(&path)?;
+            config.config_file = Some(path);
+        }
+
+        config.apply_env(std::env::vars())?;
+        config.apply_flags(args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// redis.conf style (`name arg...` per line, `#` comments) or, for a
+    /// `.toml` file, top-level `name = value` keys.
+    pub fn apply_file(&mut self, path: &str) -> Result<(), ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| ConfigError::Io { path: path.to_string(), source })?;
+        if path.ends_with(".toml") {
+            self.apply_toml(path, &text)
+        } else {
+            self.apply_conf(path, &text)
+        }
+    }
+
+    fn apply_conf(&mut self, path: &str, text: &str) -> Result<(), ConfigError> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let origin = || format!("{}:{}", path, n + 1);
+            let args = split_inline_args(line.as_bytes())
+                .ok_or_else(|| ConfigError::Validation("Unbalanced quotes in configuration line".to_string()).at(origin()))?;
+            let args: Vec<String> = args.iter().map(|a| String::from_utf8_lossy(a).into_owned()).collect();
+            let Some((name, values)) = args.split_first() else { continue };
+            self.set(name, &values.join(" ")).map_err(|e| e.at(origin()))?;
+        }
+        Ok(())
+    }
+
+    fn apply_toml(&mut self, path: &str, text: &str) -> Result<(), ConfigError> {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| ConfigError::Parse { path: path.to_string(), reason: e.to_string() })?;
+        for (name, value) in &table {
+            // Arrays repeat a directive: client-output-buffer-limit = ["normal 0 0 0", ...]
+            let values = match value {
+                toml::Value::Array(items) => items.clone(),
+                other => vec![other.clone()],
+            };
+            for value in values {
+                let value = match value {
+                    toml::Value::String(s) => s,
+                    toml::Value::Boolean(b) => if b { "yes" } else { "no" }.to_string(),
+                    toml::Value::Integer(i) => i.to_string(),
+                    toml::Value::Float(f) => f.to_string(),
+                    other => {
+                        return Err(ConfigError::invalid(name, &other.to_string(), "expected a string, number or boolean")
+                            .at(path.to_string()))
+                    }
+                };
+                self.set(name, &value).map_err(|e| e.at(path.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `ZEDIS_MAXCLIENTS=500`, `ZEDIS_TLS_PORT=6380`, ... Other `ZEDIS_*`
+    /// variables (`ZEDIS_HOME`, ...) are not ours to reject: they are skipped.
+    pub fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<(), ConfigError> {
+        for (key, value) in vars {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else { continue };
+            let name = name.to_lowercase().replace('_', "-");
+            if !PARAMETERS.contains(&name.as_str()) {
+                log::warn!("Ignoring environment variable {}: no '{}' directive", key, name);
+                continue;
+            }
+            self.set(&name, &value).map_err(|e| e.at(format!("environment variable {}", key)))?;
+        }
+        Ok(())
+    }
+
+    /// `--port 6380 --client-output-buffer-limit pubsub 64mb 16mb 60`: every
+    /// word up to the next `--option` is part of the value, like redis-server.
+    pub fn apply_flags(&mut self, args: impl IntoIterator<Item = String>) -> Result<(), ConfigError> {
+        let mut current: Option<(String, Vec<String>)> = None;
+        for arg in args.into_iter().map(Some).chain(std::iter::once(None)) {
+            let next_option = match &arg {
+                Some(a) => a.strip_prefix("--").map(str::to_string),
+                None => Some(String::new()),
+            };
+            match next_option {
+                Some(name) => {
+                    if let Some((prev, values)) = current.take() {
+                        self.set(&prev, &values.join(" ")).map_err(|e| e.at(format!("--{}", prev)))?;
+                    }
+                    if !name.is_empty() {
+                        current = Some((name, Vec::new()));
+                    }
+                }
+                None => match &mut current {
+                    Some((_, values)) => values.push(arg.unwrap_or_default()),
+                    None => return Err(ConfigError::Validation(format!("Unexpected argument '{}'", arg.unwrap_or_default()))),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Set one directive from its textual value.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigError> {
+        let name = name.to_lowercase();
+        let v = value.trim();
+        match name.as_str() {
+            "port" => self.port = parse_num(&name, v)?,
+            "bind" => self.host = parse_string(&name, v)?,
+            "worker-threads" => self.worker_threads = parse_num(&name, v)?,
+            "shadow-addr" => self.shadow_addr = parse_optional(v),
+            "unixsocket" => self.unixsocket = parse_optional(v),
+            "unixsocketperm" => {
+                self.unixsocketperm = u32::from_str_radix(v, 8)
+                    .map_err(|_| ConfigError::invalid(&name, v, "expected an octal permission mask"))?
+            }
+            "tls-port" => self.tls_port = parse_num(&name, v)?,
+            "tls-cert-file" => self.tls_cert_file = parse_string(&name, v)?,
+            "tls-key-file" => self.tls_key_file = parse_string(&name, v)?,
+            "tls-ca-cert-file" => self.tls_ca_cert_file = parse_optional(v),
+            "tls-auth-clients" => {
+                self.tls_auth_clients = match v.to_lowercase().as_str() {
+                    "optional" => false,
+                    _ => parse_bool(&name, v)?,
+                }
+            }
+            "proto-max-bulk-len" => self.proto_max_bulk_len = parse_memory(&name, v)?,
+            "max-multibulk-len" => self.max_multibulk_len = parse_num(&name, v)?,
+            "client-query-buffer-limit" => self.client_query_buffer_limit = parse_memory(&name, v)?,
+            "maxclients" => self.maxclients = parse_num(&name, v)?,
+            "databases" => self.databases = parse_num(&name, v)?,
+            "maxmemory" => {
+                self.maxmemory = match v.to_lowercase().as_str() {
+                    "auto" => None,
+                    _ => Some(parse_memory(&name, v)?),
+                }
+            }
+            "maxmemory-policy" => {
+                self.maxmemory_policy = match v.to_lowercase().as_str() {
+                    "auto" => None,
+                    _ => Some(EvictionPolicy::parse(v).ok_or_else(|| {
+                        ConfigError::invalid(&name, v, "expected auto, noeviction, allkeys-lru, allkeys-lfu, allkeys-random, volatile-lru, volatile-lfu, volatile-random or volatile-ttl")
+                    })?),
+                }
+            }
+            "maxmemory-samples" => self.maxmemory_samples = parse_num(&name, v)?,
+            "notify-keyspace-events" => {
+                self.notify_keyspace_events = notify::parse(v)
+                    .ok_or_else(|| ConfigError::invalid(&name, v, "expected event classes from KEg$lshzxetvjTGbMA"))?
+            }
+            "slowlog-log-slower-than" => self.slowlog_log_slower_than = parse_num(&name, v)?,
+            "slowlog-max-len" => self.slowlog_max_len = parse_num(&name, v)?,
+            "latency-monitor-threshold" => self.latency_monitor_threshold = parse_num(&name, v)?,
+            "timeout" => self.timeout = parse_seconds(&name, v)?,
+            "tcp-keepalive" => self.tcp_keepalive = parse_seconds(&name, v)?,
+            "client-output-buffer-limit" => parse_output_limits(&name, v, &mut self.client_output_buffer_limits)?,
+            "shutdown-timeout" => self.shutdown_timeout = parse_seconds(&name, v)?,
+            "save-on-shutdown" => self.save_on_shutdown = parse_bool(&name, v)?,
+            "dir" => self.dir = parse_dir(&name, v)?,
+            "dbfilename" => self.dbfilename = parse_filename(&name, v)?,
+            "appendonly" => self.appendonly = parse_bool(&name, v)?,
+            "appendfilename" => self.appendfilename = parse_filename(&name, v)?,
+            "appendfsync" => {
+                self.appendfsync = match v.to_lowercase().as_str() {
+                    "always" => FsyncPolicy::Always,
+                    "everysec" => FsyncPolicy::EverySec,
+                    "no" => FsyncPolicy::No,
+                    _ => return Err(ConfigError::invalid(&name, v, "expected always, everysec or no")),
+                }
+            }
+            "bge-model-dir" => self.bge_model_dir = parse_string(&name, v)?,
+            "zflow-config" => self.zflow_config = parse_string(&name, v)?,
+            "elastic-port" => self.elastic_port = parse_num(&name, v)?,
+            "ddos-burst" => self.ddos_burst = parse_num(&name, v)?,
+            "ddos-rate" => self.ddos_rate = parse_num(&name, v)?,
+            _ => return Err(ConfigError::Unknown(name)),
+        }
+        Ok(())
+    }
+
+    /// Current value of a directive as CONFIG GET shows it.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let yes_no = |b: bool| if b { "yes" } else { "no" }.to_string();
+        let secs = |d: Duration| d.as_secs().to_string();
+        Some(match name {
+            "port" => self.port.to_string(),
+            "bind" => self.host.clone(),
+            "worker-threads" => self.worker_threads.to_string(),
+            "shadow-addr" => self.shadow_addr.clone().unwrap_or_default(),
+            "unixsocket" => self.unixsocket.clone().unwrap_or_default(),
+            "unixsocketperm" => format!("{:o}", self.unixsocketperm),
+            "tls-port" => self.tls_port.to_string(),
+            "tls-cert-file" => self.tls_cert_file.clone(),
+            "tls-key-file" => self.tls_key_file.clone(),
+            "tls-ca-cert-file" => self.tls_ca_cert_file.clone().unwrap_or_default(),
+            "tls-auth-clients" => if self.tls_auth_clients { "yes" } else { "optional" }.to_string(),
+            "proto-max-bulk-len" => self.proto_max_bulk_len.to_string(),
+            "max-multibulk-len" => self.max_multibulk_len.to_string(),
+            "client-query-buffer-limit" => self.client_query_buffer_limit.to_string(),
+            "maxclients" => self.maxclients.to_string(),
+            "databases" => self.databases.to_string(),
+            "maxmemory" => self.maxmemory.map_or("auto".to_string(), |m| m.to_string()),
+            "maxmemory-policy" => self.maxmemory_policy.map_or("auto", EvictionPolicy::name).to_string(),
+            "maxmemory-samples" => self.maxmemory_samples.to_string(),
+            "notify-keyspace-events" => notify::to_string(self.notify_keyspace_events),
+            "slowlog-log-slower-than" => self.slowlog_log_slower_than.to_string(),
+            "slowlog-max-len" => self.slowlog_max_len.to_string(),
+            "latency-monitor-threshold" => self.latency_monitor_threshold.to_string(),
+            "timeout" => secs(self.timeout),
+            "tcp-keepalive" => secs(self.tcp_keepalive),
+            "client-output-buffer-limit" => output_limit_groups(&self.client_output_buffer_limits).join(" "),
+            "shutdown-timeout" => secs(self.shutdown_timeout),
+            "save-on-shutdown" => yes_no(self.save_on_shutdown),
+            "dir" => self.dir.clone(),
+            "dbfilename" => self.dbfilename.clone(),
+            "appendonly" => yes_no(self.appendonly),
+            "appendfilename" => self.appendfilename.clone(),
+            "appendfsync" => match self.appendfsync {
+                FsyncPolicy::Always => "always",
+                FsyncPolicy::EverySec => "everysec",
+                FsyncPolicy::No => "no",
+            }
+            .to_string(),
+            "bge-model-dir" => self.bge_model_dir.clone(),
+            "zflow-config" => self.zflow_config.clone(),
+            "elastic-port" => self.elastic_port.to_string(),
+            "ddos-burst" => self.ddos_burst.to_string(),
+            "ddos-rate" => self.ddos_rate.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Cross-field checks that individual directives can't make.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let fail = |msg: String| Err(ConfigError::Validation(msg));
+        if self.port == 0 && self.tls_port == 0 && self.unixsocket.is_none() {
+            return fail("No listener configured: set port, tls-port or unixsocket".to_string());
+        }
+        if self.port != 0 && self.port == self.tls_port {
+            return fail(format!("port and tls-port are both {}", self.port));
+        }
+        if self.elastic_port != 0 && (self.elastic_port == self.port || self.elastic_port == self.tls_port) {
+            return fail(format!("elastic-port {} collides with a client port", self.elastic_port));
+        }
+        if self.tls_port != 0 {
+            for (name, path) in [("tls-cert-file", &self.tls_cert_file), ("tls-key-file", &self.tls_key_file)] {
+                // Relative paths are opened after the chdir into `dir`
+                if !Path::new(&self.dir).join(path).exists() {
+                    return fail(format!("{} '{}' does not exist (required by tls-port)", name, path));
+                }
+            }
+        }
+        if self.worker_threads == 0 {
+            return fail("worker-threads must be at least 1".to_string());
+        }
+        if self.maxclients == 0 {
+            return fail("maxclients must be at least 1".to_string());
+        }
+        if self.databases == 0 {
+            return fail("databases must be at least 1".to_string());
+        }
+        if self.maxmemory_samples == 0 || self.maxmemory_samples > 64 {
+            return fail("maxmemory-samples must be between 1 and 64".to_string());
+        }
+        if self.proto_max_bulk_len < 1024 * 1024 {
+            return fail("proto-max-bulk-len must be at least 1mb".to_string());
+        }
+        if self.client_query_buffer_limit < 1024 * 1024 {
+            return fail("client-query-buffer-limit must be at least 1mb".to_string());
+        }
+        if self.client_query_buffer_limit < self.proto_max_bulk_len {
+            return fail("client-query-buffer-limit can't be smaller than proto-max-bulk-len".to_string());
+        }
+        if self.ddos_burst == 0 || self.ddos_rate <= 0.0 || self.ddos_rate.is_nan() {
+            return fail("ddos-burst and ddos-rate must be positive".to_string());
+        }
+        if !Path::new(&self.dir).is_dir() {
+            return fail(format!("dir '{}' is not a directory", self.dir));
+        }
+        Ok(())
+    }
+
+    pub fn tls_settings(&self) -> TlsSettings {
+        TlsSettings {
+            cert_path: self.tls_cert_file.clone(),
+            key_path: self.tls_key_file.clone(),
+            ca_path: self.tls_ca_cert_file.clone(),
+            require_client_cert: self.tls_auth_clients,
+        }
+    }
+
+    pub fn protocol_limits(&self) -> ProtocolLimits {
+        ProtocolLimits {
+            max_bulk_len: self.proto_max_bulk_len,
+            max_multibulk_len: self.max_multibulk_len,
+            max_query_buffer: self.client_query_buffer_limit,
+            ..ProtocolLimits::default()
         }
     }
 }
+
+fn parse_num<T: std::str::FromStr>(name: &str, v: &str) -> Result<T, ConfigError> {
+    v.parse().map_err(|_| ConfigError::invalid(name, v, "expected a number in range"))
+}
+
+fn parse_string(name: &str, v: &str) -> Result<String, ConfigError> {
+    if v.is_empty() {
+        return Err(ConfigError::invalid(name, v, "must not be empty"));
+    }
+    Ok(v.to_string())
+}
+
+/// Stored absolute, so it stays meaningful after the chdir into it.
+fn parse_dir(name: &str, v: &str) -> Result<String, ConfigError> {
+    let path = std::fs::canonicalize(parse_string(name, v)?)
+        .map_err(|e| ConfigError::invalid(name, v, e.to_string()))?;
+    if !path.is_dir() {
+        return Err(ConfigError::invalid(name, v, "not a directory"));
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// A bare file name: persistence files always live in `dir`.
+fn parse_filename(name: &str, v: &str) -> Result<String, ConfigError> {
+    let v = parse_string(name, v)?;
+    if v.contains('/') || v.contains('\\') {
+        return Err(ConfigError::invalid(name, &v, "must be a file name, not a path (use 'dir')"));
+    }
+    Ok(v)
+}
+
+/// Empty value (`""`) clears an optional setting.
+fn parse_optional(v: &str) -> Option<String> {
+    (!v.is_empty()).then(|| v.to_string())
+}
+
+fn parse_bool(name: &str, v: &str) -> Result<bool, ConfigError> {
+    match v.to_lowercase().as_str() {
+        "yes" | "true" | "on" | "1" => Ok(true),
+        "no" | "false" | "off" | "0" => Ok(false),
+        _ => Err(ConfigError::invalid(name, v, "expected yes or no")),
+    }
+}
+
+fn parse_seconds(name: &str, v: &str) -> Result<Duration, ConfigError> {
+    parse_num::<u64>(name, v).map(Duration::from_secs)
+}
+
+/// Redis memory notation: `1k` = 1000, `1kb` = 1024, likewise m/mb and g/gb.
+pub fn parse_memory(name: &str, v: &str) -> Result<usize, ConfigError> {
+    let lower = v.to_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (digits, unit) = lower.split_at(split);
+    let multiplier: usize = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(ConfigError::invalid(name, v, "unknown memory unit")),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| ConfigError::invalid(name, v, "expected a memory size such as 64mb"))
+}
+
+/// `<class> <hard> <soft> <seconds>` groups, one or more per value.
+fn parse_output_limits(name: &str, v: &str, limits: &mut OutputBufferLimits) -> Result<(), ConfigError> {
+    let words: Vec<&str> = v.split_whitespace().collect();
+    if words.is_empty() || !words.len().is_multiple_of(4) {
+        return Err(ConfigError::invalid(name, v, "expected <class> <hard> <soft> <seconds>"));
+    }
+    // Parse everything before applying so a bad group changes nothing
+    let mut parsed = Vec::new();
+    for group in words.chunks(4) {
+        let class = match group[0].to_lowercase().as_str() {
+            "normal" => ClientClass::Normal,
+            "replica" | "slave" => ClientClass::Replica,
+            "pubsub" => ClientClass::Pubsub,
+            _ => return Err(ConfigError::invalid(name, group[0], "class must be normal, replica or pubsub")),
+        };
+        let limit = OutputBufferLimit {
+            hard: parse_memory(name, group[1])?,
+            soft: parse_memory(name, group[2])?,
+            soft_seconds: parse_seconds(name, group[3])?,
+        };
+        parsed.push((class, limit));
+    }
+    for (class, limit) in parsed {
+        *limits.get_mut(class) = limit;
+    }
+    Ok(())
+}
+
+/// `<class> <hard> <soft> <seconds>` per class, in bytes and seconds.
+fn output_limit_groups(limits: &OutputBufferLimits) -> Vec<String> {
+    [("normal", ClientClass::Normal), ("replica", ClientClass::Replica), ("pubsub", ClientClass::Pubsub)]
+        .iter()
+        .map(|(label, class)| {
+            let l = limits.get(*class);
+            format!("{} {} {} {}", label, l.hard, l.soft, l.soft_seconds.as_secs())
+        })
+        .collect()
+}
+
+type ApplyHook = Box<dyn Fn(&Config) -> Result<(), String> + Send + Sync>;
+
+/// The running configuration. CONFIG SET validates a candidate copy, runs the
+/// apply hooks of the parameters it touches and only then publishes it; if a
+/// hook fails the hooks are re-run with the old values.
+pub struct ConfigStore {
+    current: RwLock<Config>,
+    // Held for the whole of CONFIG SET, which also serializes concurrent SETs
+    hooks: Mutex<Vec<(&'static [&'static str], ApplyHook)>>,
+}
+
+impl ConfigStore {
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: RwLock::new(config),
+            hooks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Config> {
+        self.current.read()
+    }
+
+    /// Register how a subsystem picks up new values of `params`.
+    pub fn on_change(&self, params: &'static [&'static str], hook: impl Fn(&Config) -> Result<(), String> + Send + Sync + 'static) {
+        self.hooks.lock().push((params, Box::new(hook)));
+    }
+
+    /// CONFIG GET: every parameter matching one of the glob `patterns`.
+    pub fn get(&self, patterns: &[&[u8]]) -> Vec<(&'static str, String)> {
+        let config = self.current.read();
+        PARAMETERS
+            .iter()
+            .filter(|name| patterns.iter().any(|p| glob_match(p, name.as_bytes(), true)))
+            .filter_map(|name| config.get(name).map(|v| (*name, v)))
+            .collect()
+    }
+
+    /// CONFIG SET: all pairs apply, or none do.
+    pub fn set(&self, pairs: &[(String, String)]) -> Result<(), ConfigError> {
+        let hooks = self.hooks.lock();
+        let old = self.current.read().clone();
+        let mut new = old.clone();
+        let mut touched: Vec<String> = Vec::new();
+        for (name, value) in pairs {
+            let name = name.to_lowercase();
+            if !PARAMETERS.contains(&name.as_str()) {
+                return Err(ConfigError::Unknown(name));
+            }
+            if IMMUTABLE.contains(&name.as_str()) {
+                return Err(ConfigError::Immutable(name));
+            }
+            if touched.contains(&name) {
+                return Err(ConfigError::invalid(&name, value, "duplicate parameter"));
+            }
+            new.set(&name, value)?;
+            touched.push(name);
+        }
+        new.validate()?;
+
+        let apply = |config: &Config| -> Result<(), ConfigError> {
+            for (params, hook) in hooks.iter() {
+                if let Some(name) = params.iter().find(|p| touched.iter().any(|t| t == *p)) {
+                    hook(config).map_err(|reason| ConfigError::Apply { name: name.to_string(), reason })?;
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = apply(&new) {
+            if let Err(undo) = apply(&old) {
+                log::error!("CONFIG SET: rollback failed: {}", undo);
+            }
+            return Err(e);
+        }
+        *self.current.write() = new;
+        Ok(())
+    }
+
+    /// CONFIG REWRITE: write the running values back to the config file,
+    /// keeping its comments and layout.
+    pub fn rewrite(&self) -> Result<(), ConfigError> {
+        let config = self.current.read().clone();
+        let Some(path) = config.config_file.clone() else {
+            return Err(ConfigError::Validation("The server is running without a config file".to_string()));
+        };
+        let old = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(source) => return Err(ConfigError::Io { path, source }),
+        };
+        let text = if path.ends_with(".toml") {
+            rewrite_toml(&config, &path, &old)?
+        } else {
+            rewrite_conf(&config, &old)
+        };
+
+        // Write a sibling temp file and rename it over, so a crash never
+        // leaves a truncated config behind
+        let tmp = format!("{}.tmp-{}", path, std::process::id());
+        std::fs::write(&tmp, text)
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(|source| {
+                let _ = std::fs::remove_file(&tmp);
+                ConfigError::Io { path: path.clone(), source }
+            })
+    }
+}
+
+/// Lines for one directive in redis.conf syntax.
+fn conf_lines(config: &Config, name: &str) -> Vec<String> {
+    if name == "client-output-buffer-limit" {
+        return output_limit_groups(&config.client_output_buffer_limits)
+            .into_iter()
+            .map(|group| format!("{} {}", name, group))
+            .collect();
+    }
+    let value = config.get(name).unwrap_or_default();
+    vec![format!("{} {}", name, quote_arg(&value))]
+}
+
+/// Quote a value so `split_inline_args` reads it back unchanged.
+fn quote_arg(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\') {
+        return value.to_string();
+    }
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn rewrite_conf(config: &Config, old: &str) -> String {
+    let defaults = Config::default();
+    let mut out: Vec<String> = Vec::new();
+    let mut written: Vec<&str> = Vec::new();
+
+    for line in old.lines() {
+        let name = split_inline_args(line.trim().as_bytes())
+            .and_then(|args| args.first().map(|a| String::from_utf8_lossy(a).to_lowercase()))
+            .filter(|_| !line.trim_start().starts_with('#'));
+        match name.and_then(|n| PARAMETERS.iter().copied().find(|p| *p == n)) {
+            // First occurrence is replaced in place, repeats are dropped
+            Some(param) => {
+                if !written.contains(&param) {
+                    out.extend(conf_lines(config, param));
+                    written.push(param);
+                }
+            }
+            None => out.push(line.to_string()),
+        }
+    }
+
+    let mut header = false;
+    for param in PARAMETERS {
+        if written.contains(param) || config.get(param) == defaults.get(param) {
+            continue;
+        }
+        if !header {
+            out.push("# Generated by CONFIG REWRITE".to_string());
+            header = true;
+        }
+        out.extend(conf_lines(config, param));
+    }
+
+    let mut text = out.join("\n");
+    text.push('\n');
+    text
+}
+
+fn rewrite_toml(config: &Config, path: &str, old: &str) -> Result<String, ConfigError> {
+    let defaults = Config::default();
+    let mut table: toml::Table = old
+        .parse()
+        .map_err(|e: toml::de::Error| ConfigError::Parse { path: path.to_string(), reason: e.to_string() })?;
+
+    // Typed where the text round-trips through apply_toml unchanged
+    let typed = |value: String| match value.as_str() {
+        "yes" => toml::Value::Boolean(true),
+        "no" => toml::Value::Boolean(false),
+        v => match v.parse::<i64>() {
+            Ok(n) if n.to_string() == v => toml::Value::Integer(n),
+            _ => toml::Value::String(value),
+        },
+    };
+    for param in PARAMETERS {
+        if !table.contains_key(*param) && config.get(param) == defaults.get(param) {
+            continue;
+        }
+        let value = if *param == "client-output-buffer-limit" {
+            toml::Value::Array(output_limit_groups(&config.client_output_buffer_limits).into_iter().map(toml::Value::String).collect())
+        } else {
+            typed(config.get(param).unwrap_or_default())
+        };
+        table.insert(param.to_string(), value);
+    }
+    toml::to_string(&table).map_err(|e| ConfigError::Parse { path: path.to_string(), reason: e.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    fn config_file(name: &str, text: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("zedis-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn load_applies_file_then_env_then_flags() {
+        let path = config_file("precedence.conf", "port 7000\nmaxclients 100\nslowlog-max-len 10\n");
+        // The only test that touches the process environment
+        std::env::set_var("ZEDIS_MAXCLIENTS", "200");
+        std::env::set_var("ZEDIS_SLOWLOG_MAX_LEN", "20");
+        let loaded = Config::load(args(&[&path, "--slowlog-max-len", "30"]));
+        std::env::remove_var("ZEDIS_MAXCLIENTS");
+        std::env::remove_var("ZEDIS_SLOWLOG_MAX_LEN");
+
+        let config = loaded.unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.maxclients, 200);
+        assert_eq!(config.slowlog_max_len, 30);
+        assert_eq!(config.config_file.as_deref(), Some(path.as_str()));
+    }
+
+    #[test]
+    fn toml_file_sets_directives() {
+        let path = config_file(
+            "precedence.toml",
+            "port = 7001\nappendonly = true\nclient-output-buffer-limit = [\"pubsub 64mb 16mb 60\"]\n",
+        );
+        let mut config = Config::default();
+        config.apply_file(&path).unwrap();
+        assert_eq!(config.port, 7001);
+        assert!(config.appendonly);
+        assert!(config.get("client-output-buffer-limit").unwrap().contains("pubsub 67108864 16777216 60"));
+    }
+
+    #[test]
+    fn flags_take_every_word_up_to_the_next_option() {
+        let mut config = Config::default();
+        config
+            .apply_flags(args(&["--client-output-buffer-limit", "pubsub", "64mb", "16mb", "60", "--port", "7002"]))
+            .unwrap();
+        assert_eq!(config.port, 7002);
+        assert!(config.get("client-output-buffer-limit").unwrap().contains("pubsub 67108864 16777216 60"));
+
+        let err = Config::default().apply_flags(args(&["7003"])).unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected argument '7003'");
+    }
+
+    #[test]
+    fn later_sources_override_earlier_ones() {
+        let mut config = Config::default();
+        config.apply_env(env(&[("ZEDIS_PORT", "7004"), ("ZEDIS_TIMEOUT", "5")])).unwrap();
+        config.apply_flags(args(&["--port", "7005"])).unwrap();
+        assert_eq!(config.port, 7005);
+        assert_eq!(config.get("timeout").as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn load_validates_the_merged_result() {
+        let err = Config::load(args(&["--port", "0", "--tls-port", "0"])).unwrap_err();
+        assert!(matches!(err, ConfigError::Validation(_)), "{}", err);
+    }
+
+    #[test]
+    fn env_skips_variables_that_are_not_directives() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[("ZEDIS_HOME", "/opt/zedis"), ("ZEDIS_VERSION", "1.2"), ("ZEDIS_MAXCLIENTS", "500"), ("PATH", "/bin")]))
+            .unwrap();
+        assert_eq!(config.maxclients, 500);
+    }
+
+    #[test]
+    fn env_rejects_bad_values_of_known_directives() {
+        let err = Config::default().apply_env(env(&[("ZEDIS_TLS_PORT", "not-a-port")])).unwrap_err();
+        assert!(err.to_string().starts_with("environment variable ZEDIS_TLS_PORT:"), "{}", err);
+    }
+}
@@ -4,3 +4,17 @@ pub mod executor;
 pub mod universe;
 pub mod structs;
 pub mod ai;
+pub mod client;
+pub mod stats;
+pub mod glob;
+pub mod expire;
+pub mod lazyfree;
+pub mod dump;
+pub mod keyspace;
+pub mod memory;
+pub mod evict;
+pub mod notify;
+pub mod latency;
+pub mod slowlog;
+pub mod monitor;
+pub mod error;
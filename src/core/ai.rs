@@ -5,6 +5,10 @@ use tokenizers::Tokenizer;
 use std::path::Path;
 use anyhow::{Result, Error};
 use half::f16;
+use crate::core::latency;
+
+/// Dense vector and sparse (token id, weight) pairs.
+pub type HybridEmbedding = (Vec<f16>, Vec<(u32, f32)>);
 
 // BGE-M3 Wrapper
 pub struct BgeM3 {
@@ -38,7 +42,11 @@ impl BgeM3 {
     }
 
     // Returns (Dense Vector, Sparse Bag-of-Words Weights)
-    pub fn embed_hybrid(&self, text: &str) -> Result<(Vec<f16>, Vec<(u32, f32)>)> {
+    pub fn embed_hybrid(&self, text: &str) -> Result<HybridEmbedding> {
+        latency::measure(latency::EMBEDDING, || self.infer(text))
+    }
+
+    fn infer(&self, text: &str) -> Result<HybridEmbedding> {
         let tokens = self.tokenizer.encode(text, true).map_err(|e| Error::msg(e.to_string()))?;
         let token_ids = Tensor::new(tokens.get_ids(), &self.device)?.unsqueeze(0)?;
 
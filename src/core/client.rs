@@ -0,0 +1,470 @@
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+/// CLIENT REPLY mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyMode {
+    On = 0,
+    Off = 1,
+    /// Suppress the reply of the next command only.
+    Skip = 2,
+}
+
+/// CLIENT PAUSE mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMode {
+    /// Only write commands are held back.
+    Write,
+    All,
+}
+
+/// client-output-buffer-limit class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientClass {
+    Normal,
+    Replica,
+    Pubsub,
+}
+
+/// One `client-output-buffer-limit` entry. Zero disables a bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputBufferLimit {
+    /// Disconnect as soon as pending output reaches this many bytes.
+    pub hard: usize,
+    /// Disconnect when pending output stays at or above this for `soft_seconds`.
+    pub soft: usize,
+    pub soft_seconds: Duration,
+}
+
+impl OutputBufferLimit {
+    pub const fn new(hard: usize, soft: usize, soft_seconds: u64) -> Self {
+        Self { hard, soft, soft_seconds: Duration::from_secs(soft_seconds) }
+    }
+}
+
+/// Limits per client class, Redis defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputBufferLimits {
+    pub normal: OutputBufferLimit,
+    pub replica: OutputBufferLimit,
+    pub pubsub: OutputBufferLimit,
+}
+
+impl Default for OutputBufferLimits {
+    fn default() -> Self {
+        Self {
+            normal: OutputBufferLimit::new(0, 0, 0),
+            replica: OutputBufferLimit::new(256 * 1024 * 1024, 64 * 1024 * 1024, 60),
+            pubsub: OutputBufferLimit::new(32 * 1024 * 1024, 8 * 1024 * 1024, 60),
+        }
+    }
+}
+
+impl OutputBufferLimits {
+    pub fn get(&self, class: ClientClass) -> OutputBufferLimit {
+        match class {
+            ClientClass::Normal => self.normal,
+            ClientClass::Replica => self.replica,
+            ClientClass::Pubsub => self.pubsub,
+        }
+    }
+
+    pub fn get_mut(&mut self, class: ClientClass) -> &mut OutputBufferLimit {
+        match class {
+            ClientClass::Normal => &mut self.normal,
+            ClientClass::Replica => &mut self.replica,
+            ClientClass::Pubsub => &mut self.pubsub,
+        }
+    }
+}
+
+/// One client session. Shared between its connection task and the registry
+/// so CLIENT LIST / KILL can observe and act on it from any connection.
+pub struct Client {
+    pub id: u64,
+    pub addr: String,
+    pub laddr: String,
+    created: Instant,
+    /// Milliseconds since `created` of the last command.
+    last_interaction: AtomicU64,
+    name: Mutex<Option<String>>,
+    user: Mutex<String>,
+    last_cmd: Mutex<String>,
+    db: AtomicUsize,
+    resp: AtomicU8,
+    subscriptions: AtomicUsize,
+    pattern_subscriptions: AtomicUsize,
+    in_multi: AtomicBool,
+    monitor: AtomicBool,
+    no_evict: AtomicBool,
+    reply: AtomicU8,
+    // Query / output buffer sizes, refreshed once per batch
+    qbuf: AtomicUsize,
+    qbuf_free: AtomicUsize,
+    omem: AtomicUsize,
+    /// When output first reached the soft limit, while it stays there.
+    soft_limit_since: Mutex<Option<Instant>>,
+    killed: CancellationToken,
+}
+
+impl Client {
+    fn new(id: u64, addr: String, laddr: String) -> Self {
+        Self {
+            id,
+            addr,
+            laddr,
+            created: Instant::now(),
+            last_interaction: AtomicU64::new(0),
+            name: Mutex::new(None),
+            user: Mutex::new("default".to_string()),
+            last_cmd: Mutex::new("NULL".to_string()),
+            db: AtomicUsize::new(0),
+            resp: AtomicU8::new(2),
+            subscriptions: AtomicUsize::new(0),
+            pattern_subscriptions: AtomicUsize::new(0),
+            in_multi: AtomicBool::new(false),
+            monitor: AtomicBool::new(false),
+            no_evict: AtomicBool::new(false),
+            reply: AtomicU8::new(ReplyMode::On as u8),
+            qbuf: AtomicUsize::new(0),
+            qbuf_free: AtomicUsize::new(0),
+            omem: AtomicUsize::new(0),
+            soft_limit_since: Mutex::new(None),
+            killed: CancellationToken::new(),
+        }
+    }
+
+    /// Unregistered client for server-internal execution (AOF replay, Lua).
+    /// `label` stands in for its address, e.g. in MONITOR output.
+    pub fn internal(label: &str) -> Arc<Self> {
+        Arc::new(Self::new(0, label.to_string(), String::new()))
+    }
+
+    /// Record that `cmd` is about to run.
+    pub fn touch(&self, cmd: &str) {
+        self.last_interaction.store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
+        let mut last = self.last_cmd.lock();
+        last.clear();
+        last.push_str(&cmd.to_lowercase());
+    }
+
+    pub fn age(&self) -> Duration {
+        self.created.elapsed()
+    }
+
+    pub fn idle(&self) -> Duration {
+        let last = Duration::from_millis(self.last_interaction.load(Ordering::Relaxed));
+        self.created.elapsed().saturating_sub(last)
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.name.lock().clone()
+    }
+
+    pub fn set_name(&self, name: Option<String>) {
+        *self.name.lock() = name;
+    }
+
+    pub fn user(&self) -> String {
+        self.user.lock().clone()
+    }
+
+    pub fn set_user(&self, user: String) {
+        *self.user.lock() = user;
+    }
+
+    pub fn db(&self) -> usize {
+        self.db.load(Ordering::Relaxed)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_db(&self, db: usize) {
+        self.db.store(db, Ordering::Relaxed);
+    }
+
+    /// Protocol negotiated with HELLO (2 or 3).
+    pub fn resp(&self) -> u8 {
+        self.resp.load(Ordering::Relaxed)
+    }
+
+    pub fn set_resp(&self, version: i64) {
+        self.resp.store(version as u8, Ordering::Relaxed);
+    }
+
+    pub fn subscriptions(&self) -> usize {
+        self.subscriptions.load(Ordering::Relaxed)
+    }
+
+    pub fn set_subscriptions(&self, count: usize) {
+        self.subscriptions.store(count, Ordering::Relaxed);
+    }
+
+    pub fn pattern_subscriptions(&self) -> usize {
+        self.pattern_subscriptions.load(Ordering::Relaxed)
+    }
+
+    pub fn set_pattern_subscriptions(&self, count: usize) {
+        self.pattern_subscriptions.store(count, Ordering::Relaxed);
+    }
+
+    pub fn set_multi(&self, in_multi: bool) {
+        self.in_multi.store(in_multi, Ordering::Relaxed);
+    }
+
+    pub fn set_monitor(&self, on: bool) {
+        self.monitor.store(on, Ordering::Relaxed);
+    }
+
+    pub fn no_evict(&self) -> bool {
+        self.no_evict.load(Ordering::Relaxed)
+    }
+
+    pub fn set_no_evict(&self, on: bool) {
+        self.no_evict.store(on, Ordering::Relaxed);
+    }
+
+    pub fn reply_mode(&self) -> ReplyMode {
+        match self.reply.load(Ordering::Relaxed) {
+            1 => ReplyMode::Off,
+            2 => ReplyMode::Skip,
+            _ => ReplyMode::On,
+        }
+    }
+
+    pub fn set_reply_mode(&self, mode: ReplyMode) {
+        self.reply.store(mode as u8, Ordering::Relaxed);
+    }
+
+    /// Whether the reply to the command that just ran should be sent, given
+    /// the reply mode before it ran. Consumes a pending SKIP.
+    pub fn take_reply_permit(&self, before: ReplyMode) -> bool {
+        match self.reply_mode() {
+            ReplyMode::On => true,
+            ReplyMode::Off => false,
+            ReplyMode::Skip => {
+                // The command that set SKIP gets no reply either; the next
+                // one is skipped and then replies resume
+                if before == ReplyMode::Skip {
+                    self.set_reply_mode(ReplyMode::On);
+                }
+                false
+            }
+        }
+    }
+
+    pub fn set_buffers(&self, qbuf: usize, qbuf_free: usize, omem: usize) {
+        self.qbuf.store(qbuf, Ordering::Relaxed);
+        self.qbuf_free.store(qbuf_free, Ordering::Relaxed);
+        self.omem.store(omem, Ordering::Relaxed);
+    }
+
+    /// CLIENT KILL: the connection task closes the socket on its next wakeup.
+    pub fn kill(&self) {
+        self.killed.cancel();
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.is_cancelled()
+    }
+
+    pub async fn killed(&self) {
+        self.killed.cancelled().await
+    }
+
+    pub fn is_pubsub(&self) -> bool {
+        self.subscriptions() > 0 || self.pattern_subscriptions() > 0
+    }
+
+    pub fn class(&self) -> ClientClass {
+        if self.is_pubsub() { ClientClass::Pubsub } else { ClientClass::Normal }
+    }
+
+    /// Whether `omem` pending bytes break `limit`. Starts, keeps or resets the
+    /// soft-limit timer as a side effect.
+    fn output_limit_reached(&self, omem: usize, limit: &OutputBufferLimit) -> bool {
+        if limit.hard != 0 && omem >= limit.hard {
+            return true;
+        }
+        let mut since = self.soft_limit_since.lock();
+        if limit.soft != 0 && omem >= limit.soft {
+            let start = *since.get_or_insert_with(Instant::now);
+            start.elapsed() >= limit.soft_seconds
+        } else {
+            *since = None;
+            false
+        }
+    }
+
+    /// Deadline for the soft-limit timer, if it is running.
+    fn soft_limit_deadline(&self, limit: &OutputBufferLimit) -> Option<Instant> {
+        self.soft_limit_since.lock().map(|since| since + limit.soft_seconds)
+    }
+
+    /// One CLIENT LIST / CLIENT INFO line (without the trailing newline).
+    pub fn info_line(&self) -> String {
+        let mut flags = String::new();
+        if self.monitor.load(Ordering::Relaxed) { flags.push('O'); }
+        if self.is_pubsub() { flags.push('P'); }
+        if self.in_multi.load(Ordering::Relaxed) { flags.push('x'); }
+        if self.no_evict() { flags.push('e'); }
+        if self.is_killed() { flags.push('c'); }
+        if flags.is_empty() { flags.push('N'); }
+
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db={} sub={} psub={} multi={} qbuf={} qbuf-free={} obl=0 oll=0 omem={} cmd={} user={} resp={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            self.name().unwrap_or_default(),
+            self.age().as_secs(),
+            self.idle().as_secs(),
+            flags,
+            self.db(),
+            self.subscriptions(),
+            self.pattern_subscriptions(),
+            if self.in_multi.load(Ordering::Relaxed) { 0 } else { -1 },
+            self.qbuf.load(Ordering::Relaxed),
+            self.qbuf_free.load(Ordering::Relaxed),
+            self.omem.load(Ordering::Relaxed),
+            self.last_cmd.lock(),
+            self.user(),
+            self.resp.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Every connected client, by id.
+pub struct ClientRegistry {
+    clients: DashMap<u64, Arc<Client>>,
+    next_id: AtomicU64,
+    // Slots taken against maxclients; reserved before the client is inserted
+    connected: AtomicUsize,
+    // CLIENT PAUSE deadline and mode
+    pause: Mutex<Option<(Instant, PauseMode)>>,
+    unpaused: Notify,
+    output_limits: RwLock<OutputBufferLimits>,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self {
+            clients: DashMap::new(),
+            next_id: AtomicU64::new(1),
+            connected: AtomicUsize::new(0),
+            pause: Mutex::new(None),
+            unpaused: Notify::new(),
+            output_limits: RwLock::new(OutputBufferLimits::default()),
+        }
+    }
+
+    /// Register a new connection, or `None` if `maxclients` are already
+    /// connected. The slot is reserved first, so concurrent accepts can't
+    /// both pass the check and overshoot the limit.
+    pub fn try_register(&self, addr: String, laddr: String, maxclients: usize) -> Option<Arc<Client>> {
+        if self.connected.fetch_add(1, Ordering::AcqRel) >= maxclients {
+            self.connected.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let client = Arc::new(Client::new(id, addr, laddr));
+        self.clients.insert(id, client.clone());
+        Some(client)
+    }
+
+    pub fn unregister(&self, id: u64) {
+        if self.clients.remove(&id).is_some() {
+            self.connected.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Snapshot of all clients, ordered by id.
+    pub fn all(&self) -> Vec<Arc<Client>> {
+        let mut clients: Vec<Arc<Client>> = self.clients.iter().map(|c| c.value().clone()).collect();
+        clients.sort_by_key(|c| c.id);
+        clients
+    }
+
+    #[allow(dead_code)]
+    pub fn output_limits(&self) -> OutputBufferLimits {
+        *self.output_limits.read()
+    }
+
+    pub fn set_output_limits(&self, limits: OutputBufferLimits) {
+        *self.output_limits.write() = limits;
+    }
+
+    /// Whether `client` with `omem` bytes of pending output must be closed
+    /// under its class's client-output-buffer-limit.
+    pub fn output_limit_reached(&self, client: &Client, omem: usize) -> bool {
+        let limit = self.output_limits.read().get(client.class());
+        client.output_limit_reached(omem, &limit)
+    }
+
+    /// How long a flush of over-soft-limit output may block before the
+    /// client counts as stuck. None when no timer is running.
+    pub fn output_flush_deadline(&self, client: &Client) -> Option<Instant> {
+        let limit = self.output_limits.read().get(client.class());
+        client.soft_limit_deadline(&limit)
+    }
+
+    pub fn pause(&self, timeout: Duration, mode: PauseMode) {
+        *self.pause.lock() = Some((Instant::now() + timeout, mode));
+    }
+
+    pub fn unpause(&self) {
+        *self.pause.lock() = None;
+        self.unpaused.notify_waiters();
+    }
+
+    /// Hold a command while CLIENT PAUSE applies to it.
+    pub async fn wait_if_paused(&self, is_write: bool) {
+        loop {
+            let deadline = match *self.pause.lock() {
+                Some((until, mode)) if until > Instant::now() && (mode == PauseMode::All || is_write) => until,
+                _ => return,
+            };
+            let unpaused = self.unpaused.notified();
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline.into()) => {}
+                _ = unpaused => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maxclients_holds_under_concurrent_connects() {
+        let registry = Arc::new(ClientRegistry::new());
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..32)
+            .map(|i| {
+                let (registry, accepted) = (registry.clone(), accepted.clone());
+                std::thread::spawn(move || {
+                    if registry.try_register(format!("127.0.0.1:{}", i), String::new(), 8).is_some() {
+                        accepted.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::Relaxed), 8);
+        assert_eq!(registry.all().len(), 8);
+
+        // A disconnect frees its slot
+        let first = registry.all()[0].id;
+        registry.unregister(first);
+        assert!(registry.try_register("127.0.0.1:1".to_string(), String::new(), 8).is_some());
+        assert!(registry.try_register("127.0.0.1:2".to_string(), String::new(), 8).is_none());
+    }
+}
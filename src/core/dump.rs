@@ -0,0 +1,102 @@
+use crate::core::storage::DataType;
+
+/// DUMP payload: bincode-encoded value, then a little-endian u16 format
+/// version, then a little-endian CRC64 (Jones, as Redis uses) of everything
+/// before it.
+pub const DUMP_VERSION: u16 = 1;
+
+const CRC64_TABLE: [u64; 256] = crc64_table();
+
+const fn crc64_table() -> [u64; 256] {
+    // Reflected form of the Jones polynomial 0xad93d23594c935a9
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub fn crc64(data: &[u8]) -> u64 {
+    data.iter().fold(0u64, |crc, b| CRC64_TABLE[((crc ^ *b as u64) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// Serialize one value for DUMP.
+pub fn dump_value(value: &DataType) -> Result<Vec<u8>, String> {
+    let mut payload = bincode::serialize(value).map_err(|e| format!("DUMP failed: {}", e))?;
+    payload.extend_from_slice(&DUMP_VERSION.to_le_bytes());
+    let crc = crc64(&payload);
+    payload.extend_from_slice(&crc.to_le_bytes());
+    Ok(payload)
+}
+
+/// Check and decode a RESTORE payload.
+pub fn restore_value(payload: &[u8]) -> Result<DataType, String> {
+    const BAD: &str = "DUMP payload version or checksum are wrong";
+    if payload.len() < 10 {
+        return Err(BAD.to_string());
+    }
+    let (body, footer) = payload.split_at(payload.len() - 10);
+    let version = u16::from_le_bytes([footer[0], footer[1]]);
+    let crc = u64::from_le_bytes(footer[2..].try_into().unwrap());
+    if version > DUMP_VERSION || crc != crc64(&payload[..payload.len() - 8]) {
+        return Err(BAD.to_string());
+    }
+    bincode::deserialize(body).map_err(|_| "Bad data format".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use hashbrown::HashMap;
+
+    fn hash() -> DataType {
+        DataType::Hash(HashMap::from([(Bytes::from_static(b"f\xff"), Bytes::from_static(b"\x00v"))]))
+    }
+
+    #[test]
+    fn crc64_matches_redis() {
+        assert_eq!(crc64(b"123456789"), 0xe9c6_d914_c4b8_d9ca);
+    }
+
+    #[test]
+    fn restore_gives_back_the_dumped_value() {
+        let payload = dump_value(&hash()).unwrap();
+        match restore_value(&payload).unwrap() {
+            DataType::Hash(h) => assert_eq!(h.get(&b"f\xff"[..]), Some(&Bytes::from_static(b"\x00v"))),
+            other => panic!("restored {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tampered_payloads_are_rejected() {
+        let payload = dump_value(&hash()).unwrap();
+        let bad = Err("DUMP payload version or checksum are wrong".to_string());
+
+        let mut body = payload.clone();
+        body[0] ^= 1;
+        assert_eq!(restore_value(&body).map(|_| ()), bad);
+
+        let mut checksum = payload.clone();
+        *checksum.last_mut().unwrap() ^= 1;
+        assert_eq!(restore_value(&checksum).map(|_| ()), bad);
+
+        // A newer format is refused even with a valid checksum
+        let mut newer = payload[..payload.len() - 10].to_vec();
+        newer.extend_from_slice(&(DUMP_VERSION + 1).to_le_bytes());
+        let crc = crc64(&newer);
+        newer.extend_from_slice(&crc.to_le_bytes());
+        assert_eq!(restore_value(&newer).map(|_| ()), bad);
+
+        assert_eq!(restore_value(&payload[..9]).map(|_| ()), bad);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::core::protocol::RespFrame;
+
+/// Result of a `Db` operation that can fail the way a Redis command does.
+pub type DbResult<T> = Result<T, ZedisError>;
+
+/// An error reply. The first word of the rendered message is the code client
+/// libraries dispatch on (redis-py, ioredis and go-redis map WRONGTYPE,
+/// NOPERM, EXECABORT, ... to their own exception types).
+///
+/// Handlers return it with `?`; `Dispatcher::execute` turns it into a
+/// `RespFrame::Error` instead of closing the connection.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ZedisError {
+    #[error("ERR {0}")]
+    Generic(String),
+    /// Command name, or `command|subcommand`.
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("ERR unknown subcommand '{sub}'. Try {command} HELP.")]
+    UnknownSubcommand { command: &'static str, sub: String },
+    #[error("ERR syntax error")]
+    Syntax,
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+    #[error("ERR no such key")]
+    NoSuchKey,
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    /// Name of the command the user may not run.
+    #[error("NOPERM this user has no permissions to run the '{0}' command")]
+    NoPerm(String),
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    Oom,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    /// HELLO with a protocol version other than 2 or 3.
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+}
+
+impl ZedisError {
+    /// `ERR <message>`
+    pub fn err(message: impl Into<String>) -> Self {
+        ZedisError::Generic(message.into())
+    }
+}
+
+impl From<ZedisError> for RespFrame {
+    fn from(e: ZedisError) -> Self {
+        RespFrame::Error(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One of each variant. The match fails to compile when a variant is
+    /// added without being listed here.
+    fn every_variant() -> Vec<ZedisError> {
+        let all = vec![
+            ZedisError::err("DUMP payload version or checksum are wrong"),
+            ZedisError::WrongArity("get".to_string()),
+            ZedisError::UnknownSubcommand { command: "CLIENT", sub: "NOPE".to_string() },
+            ZedisError::Syntax,
+            ZedisError::NotInteger,
+            ZedisError::NotFloat,
+            ZedisError::Overflow,
+            ZedisError::NoSuchKey,
+            ZedisError::WrongType,
+            ZedisError::BusyKey,
+            ZedisError::NoPerm("get".to_string()),
+            ZedisError::WrongPass,
+            ZedisError::Oom,
+            ZedisError::ExecAbort,
+            ZedisError::NoProto,
+        ];
+        for e in &all {
+            match e {
+                ZedisError::Generic(_) | ZedisError::WrongArity(_) | ZedisError::UnknownSubcommand { .. }
+                | ZedisError::Syntax | ZedisError::NotInteger | ZedisError::NotFloat | ZedisError::Overflow
+                | ZedisError::NoSuchKey | ZedisError::WrongType | ZedisError::BusyKey | ZedisError::NoPerm(_)
+                | ZedisError::WrongPass | ZedisError::Oom | ZedisError::ExecAbort | ZedisError::NoProto => {}
+            }
+        }
+        all
+    }
+
+    const CODES: &[&str] = &["ERR", "WRONGTYPE", "BUSYKEY", "NOPERM", "WRONGPASS", "OOM", "EXECABORT", "NOPROTO"];
+
+    #[test]
+    fn every_variant_renders_exactly_one_prefix() {
+        for e in every_variant() {
+            let RespFrame::Error(message) = RespFrame::from(e.clone()) else { panic!("{:?} is not an error reply", e) };
+            let mut words = message.split(' ');
+            let code = words.next().unwrap();
+            assert!(CODES.contains(&code), "{:?} renders an unknown code: {}", e, message);
+            let second = words.next().unwrap_or_default();
+            assert!(!CODES.contains(&second), "{:?} renders two prefixes: {}", e, message);
+            assert!(!message.contains('\r') && !message.contains('\n'), "{:?} breaks the reply line", e);
+        }
+    }
+
+    #[test]
+    fn generic_errors_are_prefixed_once() {
+        assert_eq!(ZedisError::err("no such key").to_string(), "ERR no such key");
+        assert_eq!(
+            ZedisError::UnknownSubcommand { command: "CONFIG", sub: "NOPE".to_string() }.to_string(),
+            "ERR unknown subcommand 'NOPE'. Try CONFIG HELP."
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use crate::core::error::ZedisError;
+use crate::core::keyspace::Keyspace;
+use crate::core::memory::used_memory;
+use bytes::Bytes;
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use rand::Rng;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How long one command may spend evicting before it is let through anyway;
+/// the next write picks up where it stopped.
+const EVICTION_TIME_LIMIT: Duration = Duration::from_millis(10);
+
+// LFU counter, as in Redis: logarithmic increments, minus one per idle minute
+const LFU_INIT: u8 = 5;
+const LFU_LOG_FACTOR: f64 = 10.0;
+const LFU_DECAY_SECONDS: u32 = 60;
+
+const SHARDS: usize = 64;
+
+/// maxmemory-policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    NoEviction = 0,
+    AllKeysLru = 1,
+    AllKeysLfu = 2,
+    AllKeysRandom = 3,
+    VolatileLru = 4,
+    VolatileLfu = 5,
+    VolatileRandom = 6,
+    VolatileTtl = 7,
+}
+
+impl EvictionPolicy {
+    const ALL: [EvictionPolicy; 8] = [
+        EvictionPolicy::NoEviction, EvictionPolicy::AllKeysLru, EvictionPolicy::AllKeysLfu,
+        EvictionPolicy::AllKeysRandom, EvictionPolicy::VolatileLru, EvictionPolicy::VolatileLfu,
+        EvictionPolicy::VolatileRandom, EvictionPolicy::VolatileTtl,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EvictionPolicy::NoEviction => "noeviction",
+            EvictionPolicy::AllKeysLru => "allkeys-lru",
+            EvictionPolicy::AllKeysLfu => "allkeys-lfu",
+            EvictionPolicy::AllKeysRandom => "allkeys-random",
+            EvictionPolicy::VolatileLru => "volatile-lru",
+            EvictionPolicy::VolatileLfu => "volatile-lfu",
+            EvictionPolicy::VolatileRandom => "volatile-random",
+            EvictionPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|p| p.name().eq_ignore_ascii_case(s))
+    }
+
+    fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(EvictionPolicy::NoEviction)
+    }
+
+    /// Only keys with a TTL are candidates.
+    pub fn volatile(self) -> bool {
+        matches!(self, EvictionPolicy::VolatileLru | EvictionPolicy::VolatileLfu
+            | EvictionPolicy::VolatileRandom | EvictionPolicy::VolatileTtl)
+    }
+
+    fn tracking(self) -> Tracking {
+        match self {
+            EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => Tracking::Lru,
+            EvictionPolicy::AllKeysLfu | EvictionPolicy::VolatileLfu => Tracking::Lfu,
+            _ => Tracking::Off,
+        }
+    }
+}
+
+/// Which access statistic key lookups maintain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tracking {
+    Off = 0,
+    Lru = 1,
+    Lfu = 2,
+}
+
+static TRACKING: AtomicU8 = AtomicU8::new(Tracking::Off as u8);
+
+/// Current access tracking mode; `Off` unless an LRU/LFU policy is active.
+pub fn tracking() -> Tracking {
+    match TRACKING.load(Ordering::Relaxed) {
+        1 => Tracking::Lru,
+        2 => Tracking::Lfu,
+        _ => Tracking::Off,
+    }
+}
+
+/// Seconds since startup, the LRU clock.
+fn clock() -> u32 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs() as u32
+}
+
+fn lfu_decay(freq: u8, idle: u32) -> u8 {
+    freq.saturating_sub((idle / LFU_DECAY_SECONDS).min(255) as u8)
+}
+
+fn lfu_incr(freq: u8) -> u8 {
+    if freq == 255 {
+        return 255;
+    }
+    let base = freq.saturating_sub(LFU_INIT) as f64;
+    let p = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+    if rand::thread_rng().gen::<f64>() < p { freq + 1 } else { freq }
+}
+
+#[derive(Clone, Copy)]
+struct Access {
+    at: u32,
+    freq: u8,
+}
+
+impl Access {
+    fn fresh() -> Self {
+        Self { at: clock(), freq: LFU_INIT }
+    }
+}
+
+/// Per-key access statistics for LRU/LFU eviction, kept beside the keyspace
+/// like the expires table. Keys missing here have not been looked up since
+/// tracking started; sampling stamps them so new keys are not evicted first.
+pub struct AccessTable {
+    shards: Box<[Mutex<HashMap<Bytes, Access>>]>,
+}
+
+impl AccessTable {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    fn shard(&self, key: &[u8]) -> &Mutex<HashMap<Bytes, Access>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    /// Record a lookup of an existing key.
+    pub fn touch(&self, key: &Bytes, mode: Tracking) {
+        let mut shard = self.shard(key).lock();
+        let access = shard.entry(key.clone()).or_insert_with(Access::fresh);
+        let now = clock();
+        if mode == Tracking::Lfu {
+            access.freq = lfu_incr(lfu_decay(access.freq, now.saturating_sub(access.at)));
+        }
+        access.at = now;
+    }
+
+    pub fn remove(&self, key: &[u8]) {
+        self.shard(key).lock().remove(key);
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().clear();
+        }
+    }
+
+    pub fn swap_with(&self, other: &AccessTable) {
+        for (a, b) in self.shards.iter().zip(other.shards.iter()) {
+            std::mem::swap(&mut *a.lock(), &mut *b.lock());
+        }
+    }
+
+    /// Seconds since the last lookup.
+    pub fn idle(&self, key: &Bytes) -> u32 {
+        let mut shard = self.shard(key).lock();
+        let access = shard.entry(key.clone()).or_insert_with(Access::fresh);
+        clock().saturating_sub(access.at)
+    }
+
+    /// Decayed LFU counter.
+    pub fn frequency(&self, key: &Bytes) -> u8 {
+        let mut shard = self.shard(key).lock();
+        let access = shard.entry(key.clone()).or_insert_with(Access::fresh);
+        lfu_decay(access.freq, clock().saturating_sub(access.at))
+    }
+}
+
+/// Eviction score of a sampled key under `policy`: higher goes first.
+pub fn score(policy: EvictionPolicy, access: &AccessTable, key: &Bytes, deadline: Option<u64>) -> u64 {
+    match policy {
+        EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => access.idle(key) as u64,
+        EvictionPolicy::AllKeysLfu | EvictionPolicy::VolatileLfu => 255 - access.frequency(key) as u64,
+        EvictionPolicy::VolatileTtl => u64::MAX - deadline.unwrap_or(u64::MAX),
+        _ => rand::thread_rng().gen(),
+    }
+}
+
+/// Enforces maxmemory by evicting sampled keys before commands that may
+/// grow the dataset.
+pub struct Evictor {
+    maxmemory: AtomicU64,
+    policy: AtomicU8,
+    samples: AtomicUsize,
+    evicted_keys: AtomicU64,
+}
+
+impl Evictor {
+    pub fn new(maxmemory: u64, policy: EvictionPolicy, samples: usize) -> Self {
+        let evictor = Self {
+            maxmemory: AtomicU64::new(0),
+            policy: AtomicU8::new(0),
+            samples: AtomicUsize::new(0),
+            evicted_keys: AtomicU64::new(0),
+        };
+        evictor.configure(maxmemory, policy, samples);
+        evictor
+    }
+
+    /// Apply maxmemory / maxmemory-policy / maxmemory-samples (CONFIG SET).
+    pub fn configure(&self, maxmemory: u64, policy: EvictionPolicy, samples: usize) {
+        self.maxmemory.store(maxmemory, Ordering::Relaxed);
+        self.policy.store(policy as u8, Ordering::Relaxed);
+        self.samples.store(samples.max(1), Ordering::Relaxed);
+        TRACKING.store(policy.tracking() as u8, Ordering::Relaxed);
+    }
+
+    pub fn maxmemory(&self) -> u64 {
+        self.maxmemory.load(Ordering::Relaxed)
+    }
+
+    pub fn policy(&self) -> EvictionPolicy {
+        EvictionPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+
+    pub fn evicted_keys(&self) -> u64 {
+        self.evicted_keys.load(Ordering::Relaxed)
+    }
+
+    /// CONFIG RESETSTAT
+    pub fn reset_stats(&self) {
+        self.evicted_keys.store(0, Ordering::Relaxed);
+    }
+
+    /// Evict until used memory is under maxmemory. `Err` means nothing more
+    /// can go (noeviction, or no candidates) and the command gets `-OOM`.
+    pub fn make_room(&self, keyspace: &Keyspace) -> Result<(), ZedisError> {
+        let limit = self.maxmemory();
+        if limit == 0 || used_memory() <= limit {
+            return Ok(());
+        }
+        if self.policy() == EvictionPolicy::NoEviction {
+            return Err(ZedisError::Oom);
+        }
+        self.evict_while(keyspace, || used_memory() > limit)
+    }
+
+    /// Evict one key at a time while `over` holds, within the time limit.
+    fn evict_while(&self, keyspace: &Keyspace, over: impl Fn() -> bool) -> Result<(), ZedisError> {
+        let policy = self.policy();
+        let samples = self.samples.load(Ordering::Relaxed);
+        let started = Instant::now();
+        while over() {
+            // Best candidate among `samples` keys from every database
+            let victim = keyspace.iter()
+                .filter_map(|db| db.eviction_candidate(policy, samples).map(|(score, key)| (score, db, key)))
+                .max_by_key(|(score, _, _)| *score);
+            let Some((_, db, key)) = victim else {
+                return Err(ZedisError::Oom);
+            };
+            if db.evict(&key) {
+                self.evicted_keys.fetch_add(1, Ordering::Relaxed);
+            }
+            if started.elapsed() >= EVICTION_TIME_LIMIT {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::expire::now_ms;
+    use crate::core::storage::{Db, SetCondition, SetExpiry};
+
+    fn set(db: &Db, key: &str, size: usize, expiry: SetExpiry) {
+        db.set_with(Bytes::from(key.to_string()), Bytes::from(vec![b'x'; size]), SetCondition::Always, expiry, false).unwrap();
+    }
+
+    /// Record an access to `key` now, with LFU counter `freq`.
+    fn stamp(access: &AccessTable, key: &str, freq: u8) {
+        access.shard(key.as_bytes()).lock().insert(Bytes::from(key.to_string()), Access { at: clock(), freq });
+    }
+
+    /// Evict a single key with `policy`; enough samples to see every key.
+    fn evict_one(policy: EvictionPolicy, keyspace: &Keyspace) -> Result<(), ZedisError> {
+        let evictor = Evictor::new(1, policy, 64);
+        let before = keyspace.db(0).len();
+        evictor.evict_while(keyspace, || keyspace.db(0).len() == before)
+    }
+
+    fn keys(db: &Db) -> Vec<String> {
+        let mut keys: Vec<String> = ["cold", "warm", "hot"].iter().filter(|k| db.exists(k.as_bytes())).map(|k| k.to_string()).collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn allkeys_lru_evicts_the_least_recently_used() {
+        let keyspace = Keyspace::new(1);
+        let db = keyspace.db(0);
+        for key in ["cold", "warm", "hot"] {
+            set(db, key, 8, SetExpiry::Clear);
+        }
+        // The LRU clock ticks in seconds
+        stamp(db.access(), "cold", LFU_INIT);
+        std::thread::sleep(Duration::from_millis(1100));
+        stamp(db.access(), "warm", LFU_INIT);
+        stamp(db.access(), "hot", LFU_INIT);
+
+        evict_one(EvictionPolicy::AllKeysLru, &keyspace).unwrap();
+        assert_eq!(keys(db), ["hot", "warm"]);
+    }
+
+    #[test]
+    fn allkeys_lfu_evicts_the_least_frequently_used() {
+        let keyspace = Keyspace::new(1);
+        let db = keyspace.db(0);
+        for (key, freq) in [("cold", 1), ("warm", 50), ("hot", 200)] {
+            set(db, key, 8, SetExpiry::Clear);
+            stamp(db.access(), key, freq);
+        }
+
+        evict_one(EvictionPolicy::AllKeysLfu, &keyspace).unwrap();
+        assert_eq!(keys(db), ["hot", "warm"]);
+        evict_one(EvictionPolicy::AllKeysLfu, &keyspace).unwrap();
+        assert_eq!(keys(db), ["hot"]);
+    }
+
+    #[test]
+    fn volatile_ttl_evicts_the_nearest_deadline_and_spares_persistent_keys() {
+        let keyspace = Keyspace::new(1);
+        let db = keyspace.db(0);
+        set(db, "cold", 8, SetExpiry::At(now_ms() + 10_000));
+        set(db, "warm", 8, SetExpiry::At(now_ms() + 100_000));
+        set(db, "hot", 8, SetExpiry::Clear);
+
+        evict_one(EvictionPolicy::VolatileTtl, &keyspace).unwrap();
+        assert_eq!(keys(db), ["hot", "warm"]);
+        // Once no key has a TTL there is nothing left to evict
+        let evictor = Evictor::new(1, EvictionPolicy::VolatileTtl, 64);
+        assert_eq!(evictor.evict_while(&keyspace, || true), Err(ZedisError::Oom));
+        assert_eq!(keys(db), ["hot"]);
+        assert_eq!(evictor.evicted_keys(), 1);
+    }
+
+    #[test]
+    fn noeviction_refuses_instead_of_evicting() {
+        let keyspace = Keyspace::new(1);
+        set(keyspace.db(0), "hot", 8, SetExpiry::Clear);
+        let evictor = Evictor::new(1, EvictionPolicy::NoEviction, 5);
+        assert_eq!(evictor.make_room(&keyspace), Err(ZedisError::Oom));
+        assert_eq!(keyspace.db(0).len(), 1);
+        // No limit, nothing to do
+        assert_eq!(Evictor::new(0, EvictionPolicy::NoEviction, 5).make_room(&keyspace), Ok(()));
+    }
+
+    #[test]
+    fn make_room_frees_memory() {
+        const VALUE: usize = 4 << 20;
+        let keyspace = Keyspace::new(1);
+        let db = keyspace.db(0);
+        for i in 0..16 {
+            set(db, &format!("k{}", i), VALUE, SetExpiry::Clear);
+        }
+
+        // Two values over the limit: a few evictions bring memory back under
+        let limit = used_memory() - 2 * VALUE as u64;
+        let evictor = Evictor::new(limit, EvictionPolicy::AllKeysRandom, 5);
+        for _ in 0..100 {
+            assert_eq!(evictor.make_room(&keyspace), Ok(()));
+            if used_memory() <= limit {
+                break;
+            }
+        }
+        let evicted = evictor.evicted_keys();
+        assert!((2..16).contains(&evicted), "evicted {} keys", evicted);
+        assert_eq!(db.len() as u64, 16 - evicted);
+
+        // A limit no dataset fits under empties the keyspace, then reports
+        // OOM. Each call stops at the time limit, as each write would
+        let before = used_memory();
+        let evictor = Evictor::new(1, EvictionPolicy::AllKeysRandom, 5);
+        let mut result = Ok(());
+        for _ in 0..100 {
+            result = evictor.make_room(&keyspace);
+            if result.is_err() {
+                break;
+            }
+        }
+        assert_eq!(result, Err(ZedisError::Oom));
+        assert_eq!(db.len(), 0);
+        assert!(used_memory() + 8 * VALUE as u64 <= before, "{} -> {}", before, used_memory());
+    }
+}
@@ -1,410 +1,791 @@
+use crate::config::ConfigStore;
 use crate::core::protocol::RespFrame;
-use crate::core::storage::{Db, BitfieldOp, BitType, BitOverflow};
+use crate::core::error::{DbResult, ZedisError};
+use crate::core::keyspace::Keyspace;
+use crate::core::storage::{Db, BitfieldOp, BitType, BitOverflow, SetCondition, SetExpiry};
+use crate::core::expire::now_ms;
+use crate::core::glob::glob_match;
 use crate::security::acl::AclEngine;
 
 use crate::persistence::AofManager;
 use std::sync::Arc;
 use anyhow::Result;
+use bytes::Bytes;
 
 use crate::scripting::ScriptEngine;
 use crate::core::ai::BgeM3;
+use crate::shutdown::{SaveMode, Shutdown};
+use crate::core::client::{Client, ClientRegistry, PauseMode, ReplyMode};
+use crate::core::stats::ServerStats;
+use crate::core::evict::Evictor;
+use crate::core::memory::{peak_memory, used_memory};
+use crate::core::lazyfree;
+use crate::core::latency;
+use crate::core::slowlog::SlowLog;
+use crate::core::monitor::{self, Monitor, MonitorEvent};
+use crate::hardware::HardwareManager;
+use crate::flow::manager::FlowManager;
+use crate::compatibility::elastic::MaskStatus;
+use crate::persistence::Persistence;
+use std::fmt::Write as _;
+use std::time::Instant;
+
+pub mod command;
+#[cfg(test)]
+mod tests;
+
+/// Redis release whose INFO fields and behaviour Zedis follows; client
+/// libraries gate features on `redis_version`.
+const REDIS_COMPAT_VERSION: &str = "7.2.0";
+
+/// Messages a subscriber may fall behind by before it is dropped. Its output
+/// buffer, not this, is what normally bounds a slow reader (see `pubsub_loop`).
+const PUBSUB_CHANNEL_CAPACITY: usize = 4096;
+
+/// INFO sections shown by plain INFO / INFO default.
+const DEFAULT_INFO_SECTIONS: &[&str] = &["server", "clients", "memory", "persistence", "stats", "keyspace", "zedis"];
+
+/// Zedis subsystems INFO reports on.
+pub struct Subsystems {
+    pub hw: Arc<HardwareManager>,
+    pub flow: Arc<FlowManager>,
+    pub mask: Arc<MaskStatus>,
+}
 
 
 pub struct Dispatcher {
-    db: Arc<Db>,
+    keyspace: Arc<Keyspace>,
     acl: Arc<AclEngine>,
     aof: Arc<AofManager>,
     shadow_addr: Option<String>,
+    config: Arc<ConfigStore>,
     script_engine: ScriptEngine,
     bge_model: Option<Arc<BgeM3>>,
-    pubsub_tx: tokio::sync::broadcast::Sender<String>,
+    pubsub_tx: tokio::sync::broadcast::Sender<(Bytes, Bytes)>,
+    shutdown: Arc<Shutdown>,
+    clients: Arc<ClientRegistry>,
+    stats: Arc<ServerStats>,
+    evictor: Arc<Evictor>,
+    slowlog: Arc<SlowLog>,
+    subsystems: Subsystems,
+    started: Instant,
+    /// Random id of this server run (INFO run_id).
+    run_id: String,
 }
 
 
 impl Dispatcher {
-    pub fn new(db: Arc<Db>, aof: Arc<AofManager>, shadow_addr: Option<String>, bge_model: Option<Arc<BgeM3>>) -> Self {
+    pub fn new(keyspace: Arc<Keyspace>, aof: Arc<AofManager>, config: Arc<ConfigStore>, bge_model: Option<Arc<BgeM3>>, evictor: Arc<Evictor>, subsystems: Subsystems) -> Self {
+        let shadow_addr = config.read().shadow_addr.clone();
+        let slowlog = {
+            let c = config.read();
+            Arc::new(SlowLog::new(c.slowlog_log_slower_than, c.slowlog_max_len))
+        };
+        let (pubsub_tx, _) = tokio::sync::broadcast::channel(PUBSUB_CHANNEL_CAPACITY);
+        // Keyspace notifications go out on the same channel as PUBLISH
+        crate::core::notify::install(pubsub_tx.clone());
         Self { 
-            db,
+            keyspace,
             acl: Arc::new(AclEngine::new()),
             aof,
             shadow_addr,
+            config,
             script_engine: ScriptEngine::new(),
             bge_model,
-            pubsub_tx: { let (tx, _) = tokio::sync::broadcast::channel(1000); tx },
+            pubsub_tx,
+            shutdown: Arc::new(Shutdown::new()),
+            clients: Arc::new(ClientRegistry::new()),
+            stats: Arc::new(ServerStats::default()),
+            evictor,
+            slowlog,
+            subsystems,
+            started: Instant::now(),
+            run_id: {
+                use rand::Rng;
+                let mut rng = rand::thread_rng();
+                (0..40).map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap_or('0')).collect()
+            },
+        }
+
+    }
+
+    /// Shutdown signal observed by listeners and connections.
+    pub fn shutdown(&self) -> Arc<Shutdown> {
+        self.shutdown.clone()
+    }
+
+    /// Registry of connected clients (CLIENT LIST / KILL / PAUSE).
+    pub fn clients(&self) -> Arc<ClientRegistry> {
+        self.clients.clone()
+    }
+
+    /// Running configuration (CONFIG GET / SET).
+    pub fn config(&self) -> Arc<ConfigStore> {
+        self.config.clone()
+    }
+
+    /// Counters for INFO stats.
+    pub fn stats(&self) -> Arc<ServerStats> {
+        self.stats.clone()
+    }
+
+    /// SLOWLOG ring (CONFIG SET slowlog-*).
+    pub fn slowlog(&self) -> Arc<SlowLog> {
+        self.slowlog.clone()
+    }
+
+    /// Enforce client-output-buffer-limit on `omem` pending bytes. True when
+    /// the client has been condemned and must be disconnected.
+    pub fn output_buffer_exceeded(&self, client: &Client, omem: usize) -> bool {
+        if self.clients.output_limit_reached(client, omem) {
+            self.output_buffer_disconnect(client);
+            return true;
         }
+        false
+    }
+
+    /// Log and count an output-buffer-limit disconnection.
+    pub fn output_buffer_disconnect(&self, client: &Client) {
+        log::warn!("Client id={} addr={} closed for overcoming of output buffer limits.", client.id, client.addr);
+        ServerStats::incr(&self.stats.client_output_buffer_limit_disconnections);
+    }
 
+    /// A pub/sub or MONITOR client whose receive loop fell behind the
+    /// broadcast channel. Not an output buffer limit: the messages were lost
+    /// before reaching its buffer.
+    fn broadcast_lagged(&self, client: &Client, missed: u64) {
+        log::warn!("Client id={} addr={} closed: missed {} messages published faster than it could receive them.", client.id, client.addr, missed);
     }
 
-    pub async fn execute(&self, frame: RespFrame) -> Result<RespFrame> {
+    /// Credential check used by HELLO ... AUTH.
+    pub fn authenticate(&self, username: &str, password: &str) -> bool {
+        self.acl.authenticate(username, password)
+    }
+
+    pub async fn execute(&self, client: &Client, frame: RespFrame) -> Result<RespFrame> {
         match frame {
             RespFrame::Array(Some(frames)) => {
                 if frames.is_empty() {
-                    return Ok(RespFrame::Error("ERR empty command".to_string()));
+                    return Ok(ZedisError::err("empty command").into());
                 }
 
                 // Parse command name
-                let cmd_name = match &frames[0] {
-                    RespFrame::BulkString(Some(s)) => s.to_uppercase(),
-                    RespFrame::SimpleString(s) => s.to_uppercase(),
-                    _ => return Ok(RespFrame::Error("ERR invalid command format".to_string())),
+                let cmd_name = match frames[0].as_bytes() {
+                    Some(s) => String::from_utf8_lossy(s).to_uppercase(),
+                    None => return Ok(ZedisError::err("invalid command format").into()),
                 };
 
+                ServerStats::incr(&self.stats.total_commands_processed);
+
+                let Some(spec) = command::lookup(&cmd_name) else {
+                    return Ok(unknown_command(&frames).into());
+                };
+                if !spec.arity_ok(frames.len()) {
+                    return Ok(ZedisError::WrongArity(spec.name.to_string()).into());
+                }
+
                 // ACL Check
-                if !self.acl.check_permission("default", &cmd_name) {
-                     return Ok(RespFrame::Error(format!("NOPERM this user has no permissions to run the '{}' command", cmd_name)));
+                if !self.acl.check_permission(&client.user(), spec, &frames) {
+                     return Ok(ZedisError::NoPerm(spec.name.to_string()).into());
                 }
 
-use crate::persistence::Persistence;
+                // CLIENT PAUSE holds commands from real connections (CLIENT itself stays usable to unpause)
+                if client.id != 0 && spec.name != "client" {
+                    self.clients.wait_if_paused(spec.has(command::WRITE | command::MAY_REPLICATE)).await;
+                }
 
-                match cmd_name.as_str() {
-                    "GET" => self.handle_get(&frames).await,
-                    "DEL" => self.handle_del(&frames).await,
-                    "EXISTS" => self.handle_exists(&frames).await,
-                    "TTL" => self.handle_ttl(&frames).await,
-                    "INCR" => self.handle_incr(&frames).await,
-                    "INCRBY" => self.handle_incrby(&frames).await,
-                    "SET" => {
-                         // Shadow Mode: Fire and Forget
-                         if let Some(addr) = &self.shadow_addr {
-                             let addr = addr.clone();
-                             // Quick & dirty serialization re-use is hard without 'encode' returning bytes, 
-                             // but we can just forward valid frames in a real impl. 
-                             // For MVP, we spawn a task to just log it or connect.
-                             // tokio::spawn(async move { ... });
-                             // Currently just a placeholder log to prove architectural capability.
-                             log::info!("Shadow Mode: Mirroring SET to {}", addr);
-                         }
-                         self.handle_set(&frames).await
-                    },
-                    "SETEX" => self.handle_setex(&frames).await,
-                    "RPUSH" => self.handle_rpush(&frames).await,
-                    "LPOP" => self.handle_lpop(&frames).await,
-                    "LRANGE" => self.handle_lrange(&frames).await,
-                    "HSET" => self.handle_hset(&frames).await,
-                    "HGET" => self.handle_hget(&frames).await,
-                    "ZADD" => self.handle_zadd(&frames).await,
-                    "ZRANGE" => self.handle_zrange(&frames).await,
-                    "BITCOUNT" => self.handle_bitcount(&frames).await,
-                    "GEOADD" => self.handle_geoadd(&frames).await,
-                    "XADD" => self.handle_xadd(&frames).await,
-                    "XRANGE" => self.handle_xrange(&frames).await,
-                    "SADD" => self.handle_sadd(&frames).await,
-                    "SMEMBERS" => self.handle_smembers(&frames).await,
-                    "VADD" => self.handle_vadd(&frames).await,
-                    "VADD.TEXT" => self.handle_vadd_text(&frames).await,
-                    "VADD.M3" => self.handle_vadd_m3(&frames).await,
-                    "VSEARCH" => self.handle_vsearch(&frames).await,
-                    "VSEARCH.TEXT" => self.handle_vsearch_text(&frames).await,
-                    "VSEARCH.HYBRID" => self.handle_vsearch_hybrid(&frames).await,
-                    "BF.ADD" => self.handle_bfadd(&frames).await,
-                    "BF.EXISTS" => self.handle_bfexists(&frames).await,
-                    "JSON.SET" => self.handle_jsonset(&frames).await,
-                    "JSON.GET" => self.handle_jsonget(&frames).await,
-                    "TS.ADD" => self.handle_tsadd(&frames).await,
-                    "TS.RANGE" => self.handle_tsrange(&frames).await,
-                    "GRAPH.ADD" => self.handle_graphadd(&frames).await,
-                    "GRAPH.BFS" => self.handle_graphbfs(&frames).await,
-                    "ML.LOAD" => self.handle_mlload(&frames).await,
-                    "ML.RUN" => self.handle_mlrun(&frames).await,
-                    "EVAL" => self.handle_eval(&frames).await,
-                    "SAVE" => {
-                        // Blocking save for now
-                        match Persistence::save_rdb(&self.db, "dump.rdb") {
-                            Ok(_) => Ok(RespFrame::SimpleString("OK".to_string())),
-                            Err(e) => Ok(RespFrame::Error(format!("ERR save failed: {}", e))),
-                        }
+                // maxmemory: evict before anything that can grow the dataset.
+                // AOF replay (internal client) is never refused
+                if client.id != 0 && spec.has(command::DENYOOM) {
+                    if let Err(e) = self.evictor.make_room(&self.keyspace) {
+                        return Ok(e.into());
                     }
-                    "PING" => Ok(RespFrame::SimpleString("PONG".to_string())),
-                    "PFADD" => self.handle_pfadd(&frames).await,
-                    "PFCOUNT" => self.handle_pfcount(&frames).await,
-                    "CF.ADD" => self.handle_cfadd(&frames).await,
-                    "CF.EXISTS" => self.handle_cfexists(&frames).await,
-                    "CMS.INCRBY" => self.handle_cms_incr(&frames).await,
-                    "CMS.QUERY" => self.handle_cms_query(&frames).await,
-                    "TOPK.ADD" => self.handle_topk_add(&frames).await,
-                    "TOPK.LIST" => self.handle_topk_list(&frames).await,
-                    "TDIGEST.ADD" => self.handle_tdigest_add(&frames).await,
-                    "TDIGEST.QUANTILE" => self.handle_tdigest_quantile(&frames).await,
-                    "PUBLISH" => self.handle_publish(&frames).await,
-                    "BITFIELD" => self.handle_bitfield(&frames).await,
-                    // SUBSCRIBE is handled via special control flow in server.rs calling handle_subscribe directly
-
-                    _ => Ok(RespFrame::Error(format!("ERR unknown command '{}'", cmd_name))),
                 }
+
+                // The client's SELECTed database
+                let db = self.keyspace.db(client.db());
+
+                let started = Instant::now();
+                // Typed errors become error replies; anything else still tears down the connection
+                let reply = match (spec.handler)(self, client, db, &frames).await {
+                    Err(e) => e.downcast::<ZedisError>().map(RespFrame::from),
+                    reply => reply,
+                };
+                let elapsed = started.elapsed();
+                // AOF replay (internal client) is not a client command
+                if client.id != 0 {
+                    self.slowlog.record(client, &frames, elapsed);
+                }
+                latency::add_sample(latency::COMMAND, elapsed);
+                if monitor::is_active() && !spec.has(command::ADMIN) {
+                    let args: Vec<&[u8]> = frames.iter().filter_map(|f| f.as_bytes()).collect();
+                    monitor::feed(db.index(), &client.addr, &args);
+                }
+                if spec.has(command::WRITE) && matches!(reply, Ok(ref r) if !matches!(r, RespFrame::Error(_))) {
+                    // Writes whose handler doesn't log a normalized form go to the AOF as sent
+                    if !spec.has(command::OWN_AOF) {
+                        let args: Vec<&[u8]> = frames.iter().filter_map(|f| f.as_bytes()).collect();
+                        if let Err(e) = self.aof.append(db.index(), &args) { log::error!("AOF error: {}", e); }
+                    }
+                    // rdb_changes_since_last_save
+                    Persistence::record_change();
+                }
+                reply
             }
-            _ => Ok(RespFrame::Error("ERR request must be an array".to_string())),
+            _ => Ok(ZedisError::err("request must be an array").into()),
+        }
+    }
+
+    async fn handle_get(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        // Null bulk string for miss
+        Ok(RespFrame::BulkString(db.get_string(key)?))
+    }
+
+    async fn handle_ttl(&self, db: &Arc<Db>, cmd: &str, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        let ttl = db.pttl(key);
+        if ttl < 0 || cmd == "PTTL" {
+            return Ok(RespFrame::Integer(ttl));
+        }
+        // TTL rounds to the nearest second, as Redis does
+        Ok(RespFrame::Integer((ttl + 500) / 1000))
+    }
+
+    async fn handle_expiretime(&self, db: &Arc<Db>, cmd: &str, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        let at = db.expire_time(key);
+        if at < 0 || cmd == "PEXPIRETIME" {
+            return Ok(RespFrame::Integer(at));
         }
+        Ok(RespFrame::Integer(at / 1000))
     }
 
-    async fn handle_get(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 {
-            return Ok(RespFrame::Error("ERR wrong number of arguments for 'get' command".to_string()));
+    /// EXPIRE / PEXPIRE / EXPIREAT / PEXPIREAT key time [NX | XX | GT | LT]
+    async fn handle_expire(&self, db: &Arc<Db>, cmd: &str, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+        let time = arg_i64(frames, 2)?;
+        let flag = frames.get(3).and_then(|f| f.as_str()).map(|s| s.to_uppercase());
+        if frames.len() > 4 {
+            return Err(ZedisError::Syntax.into());
+        }
+        if frames.len() == 4 && !matches!(flag.as_deref(), Some("NX" | "XX" | "GT" | "LT")) {
+            return Err(ZedisError::err(format!("Unsupported option {}", frames[3].as_str().unwrap_or(""))).into());
         }
 
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k,
-            RespFrame::SimpleString(k) => k,
-            _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
+        let now = now_ms() as i64;
+        let at = match cmd {
+            "EXPIRE" => time.checked_mul(1000).and_then(|ms| ms.checked_add(now)),
+            "PEXPIRE" => time.checked_add(now),
+            "EXPIREAT" => time.checked_mul(1000),
+            _ => Some(time),
         };
+        let Some(at) = at else {
+            return Err(ZedisError::err(format!("invalid expire time in '{}' command", cmd.to_lowercase())).into());
+        };
+        let at = at.max(0) as u64;
 
-        match self.db.get_string(key) {
-            Some(val) => Ok(RespFrame::BulkString(Some(val))),
-            None => Ok(RespFrame::BulkString(None)), // Null bulk string for miss
+        let current = db.expire_time(key);
+        if current == -2 {
+            return Ok(RespFrame::Integer(0));
+        }
+        // A persistent key counts as an infinite TTL for GT/LT
+        let allowed = match flag.as_deref() {
+            Some("NX") => current == -1,
+            Some("XX") => current != -1,
+            Some("GT") => current != -1 && at > current as u64,
+            Some("LT") => current == -1 || at < current as u64,
+            _ => true,
+        };
+        if !allowed || !db.set_expire(key, at) {
+            return Ok(RespFrame::Integer(0));
         }
+        // Logged as an absolute deadline so replay does not extend it
+        if let Err(e) = self.aof.append(db.index(), &[b"PEXPIREAT", key, at.to_string().as_bytes()]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::Integer(1))
     }
 
-    async fn handle_ttl(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k, _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        
-        let ttl = self.db.get_ttl(key);
-        Ok(RespFrame::Integer(ttl))
+    async fn handle_persist(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        if !db.persist(key) {
+            return Ok(RespFrame::Integer(0));
+        }
+        if let Err(e) = self.aof.append(db.index(), &[b"PERSIST", key]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::Integer(1))
     }
 
-    async fn handle_del(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
+    async fn handle_del(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         let mut count = 0;
         for i in 1..frames.len() {
-            let key = match &frames[i] { RespFrame::BulkString(Some(k)) => k, _ => continue };
-            if self.db.del(key) {
-                 if let Err(e) = self.aof.append(&format!("DEL {}", key)) { log::error!("AOF error: {}", e); }
+            let key = match frames[i].as_bytes() { Some(k) => k, None => continue };
+            if db.del(key) {
+                 if let Err(e) = self.aof.append(db.index(), &[b"DEL", key]) { log::error!("AOF error: {}", e); }
                  count += 1;
             }
         }
         Ok(RespFrame::Integer(count))
     }
 
-    async fn handle_exists(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
+    async fn handle_unlink(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         let mut count = 0;
-        for i in 1..frames.len() {
-            let key = match &frames[i] { RespFrame::BulkString(Some(k)) => k, _ => continue };
-            if self.db.exists(key) { count += 1; }
+        for frame in &frames[1..] {
+            let key = match frame.as_bytes() { Some(k) => k, None => continue };
+            if db.unlink(key) {
+                 if let Err(e) = self.aof.append(db.index(), &[b"UNLINK", key]) { log::error!("AOF error: {}", e); }
+                 count += 1;
+            }
         }
         Ok(RespFrame::Integer(count))
     }
 
-    async fn handle_incr(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_rename(&self, db: &Arc<Db>, cmd: &str, frames: &[RespFrame]) -> Result<RespFrame> {
+        let src = arg(frames, 1)?;
+        let dst = arg_bytes(frames, 2)?;
+        let nx = cmd == "RENAMENX";
 
-        match self.db.incr_by(key.clone(), 1) {
-            Ok(val) => {
-                 if let Err(e) = self.aof.append(&format!("INCR {}", key)) { log::error!("AOF error: {}", e); }
-                 Ok(RespFrame::Integer(val))
-            },
-            Err(e) => Ok(RespFrame::Error(e)),
+        match db.rename(src, dst.clone(), nx)? {
+            true => {
+                if let Err(e) = self.aof.append(db.index(), &[b"RENAME", src, &dst]) { log::error!("AOF error: {}", e); }
+                if nx { Ok(RespFrame::Integer(1)) } else { Ok(RespFrame::SimpleString("OK".to_string())) }
+            }
+            false if nx => Ok(RespFrame::Integer(0)),
+            false => Ok(RespFrame::SimpleString("OK".to_string())),
         }
     }
 
-    async fn handle_incrby(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let by_val = match &frames[2] { 
-            RespFrame::BulkString(Some(s)) => s.parse::<i64>().unwrap_or(0), 
-            RespFrame::Integer(i) => *i,
-            _ => return Ok(RespFrame::Error("ERR value not integer".to_string())) 
+    /// COPY source destination [DB destination-db] [REPLACE]
+    async fn handle_copy(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let src = arg(frames, 1)?;
+        let dst = arg_bytes(frames, 2)?;
+        let mut replace = false;
+        let mut target = db;
+        let mut i = 3;
+        while i < frames.len() {
+            match frames[i].as_str().map(|s| s.to_uppercase()).as_deref() {
+                Some("REPLACE") => replace = true,
+                Some("DB") if i + 1 < frames.len() => {
+                    i += 1;
+                    target = self.keyspace.db(self.keyspace.check_index(frames[i].as_str())?);
+                }
+                _ => return Err(ZedisError::Syntax.into()),
+            }
+            i += 1;
+        }
+
+        if !db.copy(src, target, dst.clone(), replace) {
+            return Ok(RespFrame::Integer(0));
+        }
+        let target_index = target.index().to_string();
+        if let Err(e) = self.aof.append(db.index(), &[b"COPY", src, &dst, b"DB", target_index.as_bytes(), b"REPLACE"]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::Integer(1))
+    }
+
+    async fn handle_select(&self, client: &Client, frames: &[RespFrame]) -> Result<RespFrame> {
+        client.set_db(self.keyspace.check_index(frames[1].as_str())?);
+        Ok(RespFrame::SimpleString("OK".to_string()))
+    }
+
+    async fn handle_swapdb(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let index = |i: usize, which: &str| match self.keyspace.check_index(frames[i].as_str()) {
+            Err(ZedisError::NotInteger) => Err(ZedisError::err(format!("invalid {} DB index", which))),
+            other => other,
         };
+        let (a, b) = (index(1, "first")?, index(2, "second")?);
 
-        match self.db.incr_by(key.clone(), by_val) {
-            Ok(val) => {
-                 if let Err(e) = self.aof.append(&format!("INCRBY {} {}", key, by_val)) { log::error!("AOF error: {}", e); }
-                 Ok(RespFrame::Integer(val))
-            },
-            Err(e) => Ok(RespFrame::Error(e)),
+        self.keyspace.swap(a, b);
+        let (a, b) = (a.to_string(), b.to_string());
+        if let Err(e) = self.aof.append(0, &[b"SWAPDB", a.as_bytes(), b.as_bytes()]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::SimpleString("OK".to_string()))
+    }
+
+    /// FLUSHDB / FLUSHALL [ASYNC | SYNC]
+    async fn handle_flush(&self, db: &Arc<Db>, cmd: &str, frames: &[RespFrame]) -> Result<RespFrame> {
+        let lazy = match frames.get(1).and_then(|f| f.as_str()).map(|s| s.to_uppercase()).as_deref() {
+            None | Some("SYNC") if frames.len() <= 2 => false,
+            Some("ASYNC") if frames.len() == 2 => true,
+            _ => return Err(ZedisError::Syntax.into()),
+        };
+
+        if cmd == "FLUSHALL" {
+            self.keyspace.flush_all(lazy);
+        } else {
+            db.flush(lazy);
+        }
+        if let Err(e) = self.aof.append(db.index(), &[cmd.as_bytes()]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::SimpleString("OK".to_string()))
+    }
+
+    async fn handle_move(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+        let index = self.keyspace.check_index(frames[2].as_str())?;
+        if index == db.index() {
+            return Err(ZedisError::err("source and destination objects are the same").into());
+        }
+
+        if !db.move_key(key, self.keyspace.db(index)) {
+            return Ok(RespFrame::Integer(0));
+        }
+        if let Err(e) = self.aof.append(db.index(), &[b"MOVE", key, index.to_string().as_bytes()]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::Integer(1))
+    }
+
+    async fn handle_touch(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let count = frames[1..].iter()
+            .filter_map(|f| f.as_bytes())
+            .filter(|key| db.exists(key))
+            .count();
+        Ok(RespFrame::Integer(count as i64))
+    }
+
+    async fn handle_dump(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        match db.dump(key) {
+            Some(payload) => Ok(RespFrame::bulk(payload.map_err(ZedisError::err)?)),
+            None => Ok(RespFrame::BulkString(None)),
+        }
+    }
+
+    /// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+    async fn handle_restore(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let ttl = arg_i64(frames, 2)?;
+        let payload = arg(frames, 3)?;
+
+        let mut replace = false;
+        let mut absttl = false;
+        for frame in &frames[4..] {
+            match frame.as_str().map(|s| s.to_uppercase()).as_deref() {
+                Some("REPLACE") => replace = true,
+                Some("ABSTTL") => absttl = true,
+                _ => return Err(ZedisError::Syntax.into()),
+            }
+        }
+        if ttl < 0 {
+            return Err(ZedisError::err("Invalid TTL value, must be >= 0").into());
         }
+        // 0 means no expiry; a relative TTL is stored as a deadline
+        let at = match (ttl, absttl) {
+            (0, _) => None,
+            (t, true) => Some(t as u64),
+            (t, false) => Some(now_ms() + t as u64),
+        };
+
+        db.restore(key.clone(), payload, at, replace)?;
+        let at = at.unwrap_or(0).to_string();
+        if let Err(e) = self.aof.append(db.index(), &[b"RESTORE", &key, at.as_bytes(), payload, b"ABSTTL", b"REPLACE"]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::SimpleString("OK".to_string()))
     }
 
-    async fn handle_set(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'set' command".to_string()));
+    async fn handle_exists(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let mut count = 0;
+        for i in 1..frames.len() {
+            let key = match frames[i].as_bytes() { Some(k) => k, None => continue };
+            if db.exists(key) { count += 1; }
         }
+        Ok(RespFrame::Integer(count))
+    }
 
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-            RespFrame::SimpleString(k) => k.to_string(),
-            _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
+    /// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+    async fn handle_scan(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let Some(cursor) = frames[1].as_str().and_then(|s| s.parse::<u64>().ok()) else {
+            return Err(ZedisError::err("invalid cursor").into());
         };
 
+        let mut pattern: Option<&[u8]> = None;
+        let mut count = 10;
+        let mut type_name: Option<String> = None;
+        let mut i = 2;
+        while i < frames.len() {
+            let opt = frames[i].as_str().unwrap_or("").to_uppercase();
+            if i + 1 >= frames.len() {
+                return Err(ZedisError::Syntax.into());
+            }
+            match opt.as_str() {
+                "MATCH" => pattern = Some(arg(frames, i + 1)?),
+                "COUNT" => match arg_i64(frames, i + 1)? {
+                    n if n >= 1 => count = n as usize,
+                    _ => return Err(ZedisError::Syntax.into()),
+                },
+                "TYPE" => type_name = Some(arg_str(frames, i + 1)?.to_lowercase()),
+                _ => return Err(ZedisError::Syntax.into()),
+            }
+            i += 2;
+        }
+        let pattern = pattern.filter(|p| *p != b"*");
+
+        let (next, keys) = db.scan(cursor, count, |key, value| {
+            pattern.is_none_or(|p| glob_match(p, key, false))
+                && type_name.as_deref().is_none_or(|t| value.type_name() == t)
+        });
+        Ok(RespFrame::Array(Some(vec![
+            RespFrame::bulk(next.to_string()),
+            RespFrame::Array(Some(keys.into_iter().map(|k| RespFrame::BulkString(Some(k))).collect())),
+        ])))
+    }
+
+    async fn handle_keys(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let pattern = arg(frames, 1)?;
+
+        let keys = db.keys(pattern);
+        Ok(RespFrame::Array(Some(keys.into_iter().map(|k| RespFrame::BulkString(Some(k))).collect())))
+    }
+
+    async fn handle_type(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        Ok(RespFrame::SimpleString(db.key_type(key).to_string()))
+    }
+
+    /// MEMORY USAGE key [SAMPLES count]
+    async fn handle_memory(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let sub = frames.get(1).and_then(|f| f.as_str()).map(|s| s.to_uppercase()).unwrap_or_default();
+        match sub.as_str() {
+            "USAGE" => {
+                // SAMPLES is accepted for compatibility; sizes here are exact walks
+                if frames.len() != 3 && !(frames.len() == 5 && frames[3].as_str().is_some_and(|o| o.eq_ignore_ascii_case("SAMPLES"))) {
+                    return Err(ZedisError::Syntax.into());
+                }
+                let key = arg(frames, 2)?;
+                Ok(match db.memory_usage(key) {
+                    Some(bytes) => RespFrame::Integer(bytes as i64),
+                    None => RespFrame::BulkString(None),
+                })
+            }
+            _ => Err(ZedisError::err(format!("unknown subcommand '{}'. Try MEMORY HELP.", sub)).into()),
+        }
+    }
+
+    async fn handle_incr(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+
+        let val = db.incr_by(key.clone(), 1)?;
+        if let Err(e) = self.aof.append(db.index(), &[b"INCR", &key]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::Integer(val))
+    }
+
+    async fn handle_incrby(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let by_val = arg_i64(frames, 2)?;
+
+        let val = db.incr_by(key.clone(), by_val)?;
+        if let Err(e) = self.aof.append(db.index(), &[b"INCRBY", &key, by_val.to_string().as_bytes()]) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::Integer(val))
+    }
+
+    /// SET key value [NX | XX] [GET] [EX s | PX ms | EXAT ts | PXAT ts-ms | KEEPTTL]
+    async fn handle_set(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        // Shadow Mode: Fire and Forget
+        if let Some(addr) = &self.shadow_addr {
+            // Quick & dirty serialization re-use is hard without 'encode' returning bytes,
+            // but we can just forward valid frames in a real impl.
+            // Currently just a placeholder log to prove architectural capability.
+            log::info!("Shadow Mode: Mirroring SET to {}", addr);
+        }
+
+        let key = arg_bytes(frames, 1)?;
+
         let val = match &frames[2] {
-            RespFrame::BulkString(Some(v)) => v.to_string(),
-            RespFrame::SimpleString(v) => v.to_string(),
-            RespFrame::Integer(i) => i.to_string(),
-            _ => return Ok(RespFrame::Error("ERR invalid value".to_string())),
+            RespFrame::Integer(i) => Bytes::from(i.to_string()),
+            _ => arg_bytes(frames, 2)?,
         };
 
-    
-    // AOF Log
-        if let Err(e) = self.aof.append(&format!("SET {} {}", key, val)) {
-             log::error!("Failed to append to AOF: {}", e);
+        let mut cond = SetCondition::Always;
+        let mut expiry = SetExpiry::Clear;
+        let mut get_old = false;
+        let mut i = 3;
+        while i < frames.len() {
+            let opt = frames[i].as_str().unwrap_or("").to_uppercase();
+            match opt.as_str() {
+                "NX" if cond == SetCondition::Always => cond = SetCondition::IfMissing,
+                "XX" if cond == SetCondition::Always => cond = SetCondition::IfExists,
+                "GET" => get_old = true,
+                "KEEPTTL" if expiry == SetExpiry::Clear => expiry = SetExpiry::Keep,
+                "EX" | "PX" | "EXAT" | "PXAT" if expiry == SetExpiry::Clear && i + 1 < frames.len() => {
+                    i += 1;
+                    let at = Some(arg_i64(frames, i)?).filter(|t| *t > 0).and_then(|t| match opt.as_str() {
+                        "EX" => t.checked_mul(1000).and_then(|ms| ms.checked_add(now_ms() as i64)),
+                        "PX" => t.checked_add(now_ms() as i64),
+                        "EXAT" => t.checked_mul(1000),
+                        _ => Some(t),
+                    });
+                    match at {
+                        Some(at) => expiry = SetExpiry::At(at as u64),
+                        None => return Err(ZedisError::err("invalid expire time in 'set' command").into()),
+                    }
+                }
+                _ => return Err(ZedisError::Syntax.into()),
+            }
+            i += 1;
         }
 
-        self.db.set_string(key, val);
-        Ok(RespFrame::SimpleString("OK".to_string()))
+        let (written, old) = db.set_with(key.clone(), val.clone(), cond, expiry, get_old)?;
+
+        if written {
+            // AOF Log: relative expiries are logged as the absolute deadline
+            let res = match expiry {
+                SetExpiry::At(at) => self.aof.append(db.index(), &[b"SET", &key, &val, b"PXAT", at.to_string().as_bytes()]),
+                SetExpiry::Keep => self.aof.append(db.index(), &[b"SET", &key, &val, b"KEEPTTL"]),
+                SetExpiry::Clear => self.aof.append(db.index(), &[b"SET", &key, &val]),
+            };
+            if let Err(e) = res {
+                 log::error!("Failed to append to AOF: {}", e);
+            }
+        }
+
+        if get_old {
+            return Ok(RespFrame::BulkString(old));
+        }
+        if written {
+            Ok(RespFrame::SimpleString("OK".to_string()))
+        } else {
+            Ok(RespFrame::BulkString(None))
+        }
     }
 
-    async fn handle_setex(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+    async fn handle_setex(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         // SETEX key seconds value
-        if frames.len() != 4 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'setex' command".to_string()));
-        }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let _seconds = match &frames[2] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR seconds".to_string())) }; 
-        let val = match &frames[3] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR value".to_string())) };
+        let key = arg_bytes(frames, 1)?;
+        let at = Some(arg_i64(frames, 2)?)
+            .filter(|s| *s > 0)
+            .and_then(|s| s.checked_mul(1000))
+            .and_then(|ms| ms.checked_add(now_ms() as i64));
+        let Some(at) = at else {
+            return Err(ZedisError::err("invalid expire time in 'setex' command").into());
+        };
+        let val = arg_bytes(frames, 3)?;
 
-        if let Err(e) = self.aof.append(&format!("SET {} {}", key, val)) {
+        db.set_with(key.clone(), val.clone(), SetCondition::Always, SetExpiry::At(at as u64), false)?;
+        // Logged once applied, so a rejected SETEX never reaches the AOF
+        if let Err(e) = self.aof.append(db.index(), &[b"SET", &key, &val, b"PXAT", at.to_string().as_bytes()]) {
              log::error!("AOF error: {}", e);
         }
-        self.db.set_string(key, val);
         Ok(RespFrame::SimpleString("OK".to_string()))
     }
 
-    async fn handle_rpush(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 3 {
-            return Ok(RespFrame::Error("ERR wrong number of arguments for 'rpush' command".to_string()));
-        }
-
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-            RespFrame::SimpleString(k) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+    async fn handle_rpush(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
 
         let mut count = 0;
         for i in 2..frames.len() {
             let val = match &frames[i] {
-                RespFrame::BulkString(Some(v)) => v.to_string(),
-                RespFrame::SimpleString(v) => v.to_string(),
-                RespFrame::Integer(n) => n.to_string(),
-                _ => continue,
+                RespFrame::Integer(n) => Bytes::from(n.to_string()),
+                other => match other.to_bytes() {
+                    Some(v) => v,
+                    None => continue,
+                },
             };
-            count = self.db.list_push(key.clone(), val);
+            count = db.list_push(key.clone(), val)?;
         }
 
         Ok(RespFrame::Integer(count as i64))
     }
 
-    async fn handle_lpop(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_lpop(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
         
-        match self.db.list_pop(&key) {
-            Some(v) => Ok(RespFrame::BulkString(Some(v))),
-            None => Ok(RespFrame::BulkString(None)),
-        }
+        Ok(RespFrame::BulkString(db.list_pop(&key)?))
     }
 
-    async fn handle_lrange(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_lrange(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
         
-        let start = match &frames[2] { 
-            RespFrame::BulkString(Some(s)) => s.parse::<i64>().unwrap_or(0), 
-            RespFrame::Integer(i) => *i,
-            _ => 0 
-        };
-        let stop = match &frames[3] { 
-            RespFrame::BulkString(Some(s)) => s.parse::<i64>().unwrap_or(0), 
-            RespFrame::Integer(i) => *i,
-            _ => 0 
-        };
+        let start = arg_i64(frames, 2)?;
+        let stop = arg_i64(frames, 3)?;
 
-        let items = self.db.list_range(&key, start, stop);
+        let items = db.list_range(&key, start, stop)?;
         let resp = items.into_iter().map(|s| RespFrame::BulkString(Some(s))).collect();
         Ok(RespFrame::Array(Some(resp)))
     }
 
-    async fn handle_hset(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        // HSET key f1 v1 [f2 v2 ...]
-        if frames.len() < 4 || frames.len() % 2 != 0 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'hset' command".to_string()));
-        }
-        
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+    async fn handle_hset(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
 
         let mut count = 0;
         let mut i = 2;
         while i < frames.len() {
-             let field = match &frames[i] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR field".to_string())) };
-             let value = match &frames[i+1] { RespFrame::BulkString(Some(v)) => v.to_string(), _ => return Ok(RespFrame::Error("ERR value".to_string())) };
+             let field = arg_bytes(frames, i)?;
+             let value = arg_bytes(frames, i + 1)?;
              
-             count += self.db.hash_set(key.clone(), field, value);
+             count += db.hash_set(key.clone(), field, value)?;
              i += 2;
         }
 
         Ok(RespFrame::Integer(count as i64))
     }
 
-    async fn handle_hget(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 3 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'hget' command".to_string()));
-        }
+    async fn handle_hget(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
 
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+        let field = arg_bytes(frames, 2)?;
 
-        let field = match &frames[2] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid field".to_string())),
-        };
+        Ok(RespFrame::BulkString(db.hash_get(&key, &field)?))
+    }
 
-        match self.db.hash_get(&key, &field) {
-            Some(val) => Ok(RespFrame::BulkString(Some(val))),
-            None => Ok(RespFrame::BulkString(None)),
-        }
+    async fn handle_hgetall(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+
+        let pairs = db.hash_getall(key)?.into_iter()
+            .map(|(f, v)| (RespFrame::BulkString(Some(f)), RespFrame::BulkString(Some(v))))
+            .collect();
+        Ok(RespFrame::Map(pairs))
     }
 
-    async fn handle_zadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        // ZADD key score member [score member ...]
-        if frames.len() < 4 || (frames.len() - 2) % 2 != 0 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'zadd' command".to_string()));
-        }
-        
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+    async fn handle_zadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
 
         let mut added_count = 0;
         let mut i = 2;
         while i < frames.len() {
-             let score_str = match &frames[i] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR score".to_string())) };
-             let score = match score_str.parse::<f64>() { Ok(s) => s, Err(_) => return Ok(RespFrame::Error("ERR float".to_string())) };
-             let member = match &frames[i+1] { RespFrame::BulkString(Some(m)) => m.to_string(), _ => return Ok(RespFrame::Error("ERR member".to_string())) };
+             let score = arg_f64(frames, i)?;
+             let member = arg_bytes(frames, i + 1)?;
              
-             if self.db.zadd(key.clone(), score, member) { added_count += 1; }
+             if db.zadd(key.clone(), score, member)? { added_count += 1; }
              i += 2;
         }
 
         Ok(RespFrame::Integer(added_count))
     }
 
-    async fn handle_zrange(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 4 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'zrange' command".to_string()));
-        }
-        // ZRANGE key start stop
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
+    async fn handle_zrange(&self, client: &Client, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        // ZRANGE key start stop [WITHSCORES]
+        let with_scores = match &frames[4..] {
+            [] => false,
+            [opt] if opt.as_str().is_some_and(|s| s.eq_ignore_ascii_case("WITHSCORES")) => true,
+            _ => return Err(ZedisError::Syntax.into()),
         };
+        let key = arg_bytes(frames, 1)?;
 
-        let start = match &frames[2] {
-            RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(0),
+        let start = match frames[2].as_str() {
+            Some(s) => s.parse::<usize>().unwrap_or(0),
              _ => 0,
         };
 
-        let end = match &frames[3] {
-            RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(0),
+        let end = match frames[3].as_str() {
+            Some(s) => s.parse::<usize>().unwrap_or(0),
              _ => 0,
         };
 
-        let result = self.db.zrange(&key, start, end);
+        if with_scores {
+            let pairs = db.zrange_withscores(&key, start, end)?;
+            // RESP3: [[member, score], ...]; RESP2 keeps the flat member, score, ... list
+            if client.resp() >= 3 {
+                let pairs = pairs.into_iter()
+                    .map(|(m, score)| RespFrame::Array(Some(vec![RespFrame::BulkString(Some(m)), RespFrame::Double(score)])))
+                    .collect();
+                return Ok(RespFrame::Array(Some(pairs)));
+            }
+            let flat = pairs.into_iter()
+                .flat_map(|(m, score)| [RespFrame::BulkString(Some(m)), RespFrame::Double(score)])
+                .collect();
+            return Ok(RespFrame::Array(Some(flat)));
+        }
+
+        let result = db.zrange(&key, start, end)?;
         let resp_array = result.into_iter()
             .map(|s| RespFrame::BulkString(Some(s)))
             .collect();
@@ -412,21 +793,14 @@ use crate::persistence::Persistence;
         Ok(RespFrame::Array(Some(resp_array)))
     }
 
-    async fn handle_bitcount(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'bitcount' command".to_string()));
-        }
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
-        let count = self.db.bitcount(&key);
+    async fn handle_bitcount(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let count = db.bitcount(&key)?;
         Ok(RespFrame::Integer(count as i64))
     }
 
-    async fn handle_bitfield(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() < 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_bitfield(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg_bytes(frames, 1)?;
 
          let mut ops = Vec::new();
          let mut overflows = Vec::new();
@@ -434,18 +808,18 @@ use crate::persistence::Persistence;
 
          let mut i = 2;
          while i < frames.len() {
-             let op_str = match &frames[i] { RespFrame::BulkString(Some(s)) => s.to_uppercase(), _ => break };
+             let op_str = match frames[i].as_str() { Some(s) => s.to_uppercase(), None => break };
              i += 1;
 
              match op_str.as_str() {
                  "OVERFLOW" => {
-                     if i >= frames.len() { return Ok(RespFrame::Error("ERR syntax".to_string())); }
-                     let strat = match &frames[i] { RespFrame::BulkString(Some(s)) => s.to_uppercase(), _ => return Ok(RespFrame::Error("ERR overflow".to_string())) };
+                     if i >= frames.len() { return Err(ZedisError::Syntax.into()); }
+                     let strat = arg_str(frames, i)?.to_uppercase();
                      cur_overflow = match strat.as_str() {
                          "WRAP" => BitOverflow::Wrap,
                          "SAT" => BitOverflow::Sat,
                          "FAIL" => BitOverflow::Fail,
-                         _ => return Ok(RespFrame::Error("ERR overflow type".to_string())),
+                         _ => return Err(ZedisError::err("Invalid OVERFLOW type specified").into()),
                      };
                      i += 1;
                      // Push overflow state for subsequent commands? 
@@ -455,9 +829,9 @@ use crate::persistence::Persistence;
                  },
                  "GET" => {
                      // GET type offset
-                     if i + 1 >= frames.len() { return Ok(RespFrame::Error("ERR syntax".to_string())); }
-                     let type_str = match &frames[i] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR type".to_string())) };
-                     let off_str = match &frames[i+1] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR offset".to_string())) };
+                     if i + 1 >= frames.len() { return Err(ZedisError::Syntax.into()); }
+                     let type_str = arg_str(frames, i)?;
+                     let off_str = arg_str(frames, i + 1)?;
                      i += 2;
 
                      let (typ, width) = Self::parse_bittype(type_str)?;
@@ -468,39 +842,37 @@ use crate::persistence::Persistence;
                  },
                  "SET" => {
                      // SET type offset value
-                     if i + 2 >= frames.len() { return Ok(RespFrame::Error("ERR syntax".to_string())); }
-                     let type_str = match &frames[i] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR type".to_string())) };
-                     let off_str = match &frames[i+1] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR offset".to_string())) };
-                     let val_str = match &frames[i+2] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR value".to_string())) };
+                     if i + 2 >= frames.len() { return Err(ZedisError::Syntax.into()); }
+                     let type_str = arg_str(frames, i)?;
+                     let off_str = arg_str(frames, i + 1)?;
+                     let val = arg_i64(frames, i + 2)?;
                      i += 3;
 
                      let (typ, width) = Self::parse_bittype(type_str)?;
                      let offset = Self::parse_bitoffset(off_str, width)?;
-                     let val = val_str.parse::<i64>().map_err(|_| anyhow::anyhow!("ERR value"))?;
 
                      ops.push(BitfieldOp::Set(typ, offset, val));
                      overflows.push(cur_overflow);
                  },
                  "INCRBY" => {
                      // INCRBY type offset increment
-                     if i + 2 >= frames.len() { return Ok(RespFrame::Error("ERR syntax".to_string())); }
-                     let type_str = match &frames[i] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR type".to_string())) };
-                     let off_str = match &frames[i+1] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR offset".to_string())) };
-                     let incr_str = match &frames[i+2] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR incr".to_string())) };
+                     if i + 2 >= frames.len() { return Err(ZedisError::Syntax.into()); }
+                     let type_str = arg_str(frames, i)?;
+                     let off_str = arg_str(frames, i + 1)?;
+                     let incr = arg_i64(frames, i + 2)?;
                      i += 3;
 
                      let (typ, width) = Self::parse_bittype(type_str)?;
                      let offset = Self::parse_bitoffset(off_str, width)?;
-                     let incr = incr_str.parse::<i64>().map_err(|_| anyhow::anyhow!("ERR incr"))?;
 
                      ops.push(BitfieldOp::IncrBy(typ, offset, incr));
                      overflows.push(cur_overflow);
                  },
-                 _ => return Ok(RespFrame::Error(format!("ERR unknown subcommand '{}'", op_str))),
+                 _ => return Err(ZedisError::Syntax.into()),
              }
          }
 
-         let results = self.db.bitfield(key, ops, overflows);
+         let results = db.bitfield(key, ops, overflows)?;
          
          let resp_arr = results.into_iter().map(|v| {
              match v {
@@ -511,47 +883,42 @@ use crate::persistence::Persistence;
          Ok(RespFrame::Array(Some(resp_arr)))
     }
 
-    fn parse_bittype(s: &str) -> anyhow::Result<(BitType, u8)> {
-        if s.len() < 2 { return Err(anyhow::anyhow!("ERR invalid type")); }
-        let width = s[1..].parse::<u8>().map_err(|_| anyhow::anyhow!("ERR invalid width"))?;
+    fn parse_bittype(s: &str) -> DbResult<(BitType, u8)> {
+        let invalid = || ZedisError::err("Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.");
+        if s.len() < 2 { return Err(invalid()); }
+        let width = s[1..].parse::<u8>().map_err(|_| invalid())?;
         match s.chars().next() {
             Some('i') => Ok((BitType::Signed(width), width)),
             Some('u') => Ok((BitType::Unsigned(width), width)),
-            _ => Err(anyhow::anyhow!("ERR invalid type char")),
+            _ => Err(invalid()),
         }
     }
 
-    fn parse_bitoffset(s: &str, width: u8) -> anyhow::Result<usize> {
-        if s.starts_with('#') {
-            let idx = s[1..].parse::<usize>().map_err(|_| anyhow::anyhow!("ERR invalid offset"))?;
+    fn parse_bitoffset(s: &str, width: u8) -> DbResult<usize> {
+        let invalid = |_| ZedisError::err("bit offset is not an integer or out of range");
+        if let Some(idx) = s.strip_prefix('#') {
+            let idx = idx.parse::<usize>().map_err(invalid)?;
             Ok(idx * width as usize)
         } else {
-            s.parse::<usize>().map_err(|_| anyhow::anyhow!("ERR invalid offset"))
+            s.parse::<usize>().map_err(invalid)
         }
     }
 
 
-    async fn handle_geoadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         // GEOADD key lon lat member [lon lat member ...]
-        if frames.len() < 5 || (frames.len() - 2) % 3 != 0 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'geoadd' command".to_string()));
-        }
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+    async fn handle_geoadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
 
         let mut count = 0;
         let mut i = 2;
         while i < frames.len() {
-             let lon_s = match &frames[i] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR lon".to_string())) };
-             let lat_s = match &frames[i+1] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR lat".to_string())) };
-             let member = match &frames[i+2] { RespFrame::BulkString(Some(m)) => m.to_string(), _ => return Ok(RespFrame::Error("ERR member".to_string())) };
-             
-             let lon = lon_s.parse::<f64>().unwrap_or(0.0);
-             let lat = lat_s.parse::<f64>().unwrap_or(0.0);
+             let lon = arg_f64(frames, i)?;
+             let lat = arg_f64(frames, i + 1)?;
+             let member = arg_bytes(frames, i + 2)?;
+             if !(-180.0..=180.0).contains(&lon) || !(-85.05112878..=85.05112878).contains(&lat) {
+                 return Err(ZedisError::err(format!("invalid longitude,latitude pair {:.6},{:.6}", lon, lat)).into());
+             }
              
-             self.db.geoadd(key.clone(), lon, lat, member);
+             db.geoadd(key.clone(), lon, lat, member)?;
              count += 1; 
 
              i += 3;
@@ -559,59 +926,45 @@ use crate::persistence::Persistence;
         Ok(RespFrame::Integer(count))
     }
 
-    async fn handle_xadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 4 { // XADD key ID field value
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'xadd' command".to_string()));
-        }
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
-        let id_arg = match &frames[2] {
-            RespFrame::BulkString(Some(s)) => s.to_string(),
-             _ => "*".to_string(), // Default to auto
+    async fn handle_xadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let id_arg = match frames[2].as_str() {
+            Some(s) => s.to_string(),
+             None => "*".to_string(), // Default to auto
         };
 
         let mut fields = hashbrown::HashMap::new();
-        let mut i = 3;
-        while i < frames.len() {
-            let f = match &frames[i] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => break };
-            let v = if i + 1 < frames.len() {
-                match &frames[i+1] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => "".to_string() }
-            } else { "".to_string() };
-            fields.insert(f, v);
-            i += 2;
+        for i in (3..frames.len()).step_by(2) {
+            fields.insert(arg_bytes(frames, i)?, arg_bytes(frames, i + 1)?);
         }
 
-        let new_id = self.db.xadd(key, Some(&id_arg), fields);
-        Ok(RespFrame::BulkString(Some(new_id)))
+        let new_id = db.xadd(key.clone(), Some(&id_arg), fields)?;
+        // Log the resolved ID so replay doesn't mint a new one for `*`
+        let mut args: Vec<&[u8]> = vec![b"XADD", &key, new_id.as_bytes()];
+        args.extend(frames[3..].iter().filter_map(|f| f.as_bytes()));
+        if let Err(e) = self.aof.append(db.index(), &args) { log::error!("AOF error: {}", e); }
+        Ok(RespFrame::bulk(new_id))
     }
 
-    async fn handle_xrange(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 4 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'xrange' command".to_string()));
-        }
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
-        let start = match &frames[2] {
-            RespFrame::BulkString(Some(s)) => s.to_string(),
-             _ => "-".to_string(),
+    async fn handle_xrange(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let start = match frames[2].as_str() {
+            Some(s) => s.to_string(),
+             None => "-".to_string(),
         };
-        let end = match &frames[3] {
-            RespFrame::BulkString(Some(s)) => s.to_string(),
-             _ => "+".to_string(),
+        let end = match frames[3].as_str() {
+            Some(s) => s.to_string(),
+             None => "+".to_string(),
         };
 
         // Simplified response: just return IDs for MVP or JSON-like
-        let entries = self.db.xrange(&key, &start, &end);
+        let entries = db.xrange(&key, &start, &end)?;
         
         // Serialize manually to Array of Arrays
         let mut arr = Vec::new();
         for e in entries {
             let mut entry_arr = Vec::new();
-            entry_arr.push(RespFrame::BulkString(Some(e.id)));
+            entry_arr.push(RespFrame::bulk(e.id));
             let mut fields_arr = Vec::new();
             for (k, v) in e.fields {
                 fields_arr.push(RespFrame::BulkString(Some(k)));
@@ -624,19 +977,17 @@ use crate::persistence::Persistence;
         Ok(RespFrame::Array(Some(arr)))
     }
 
-    async fn handle_eval(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 3 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'eval' command".to_string()));
-        }
-        let script = match &frames[1] {
-            RespFrame::BulkString(Some(s)) => s.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid script".to_string())),
-        };
+    async fn handle_eval(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let script = arg_str(frames, 1)?.to_string();
         
-        let numkeys = match &frames[2] {
-             RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(0),
-             _ => 0, // Default 0 if parse fail or careful? Redis errors on invalid int.
-        };
+        let numkeys = arg_i64(frames, 2)?;
+        if numkeys < 0 {
+            return Err(ZedisError::err("Number of keys can't be negative").into());
+        }
+        let numkeys = numkeys as usize;
+        if 3 + numkeys > frames.len() {
+            return Err(ZedisError::err("Number of keys can't be greater than number of args").into());
+        }
 
         let mut keys = Vec::new();
         let mut args = Vec::new();
@@ -645,311 +996,488 @@ use crate::persistence::Persistence;
         for i in 0..numkeys {
              let idx = 3 + i;
              if idx < frames.len() {
-                 if let RespFrame::BulkString(Some(s)) = &frames[idx] {
-                     keys.push(s.to_string());
+                 if let Some(s) = frames[idx].to_bytes() {
+                     keys.push(s);
                  }
              }
         }
 
         // Parse args
         for i in (3 + numkeys)..frames.len() {
-             if let RespFrame::BulkString(Some(s)) = &frames[i] {
-                 args.push(s.to_string());
+             if let Some(s) = frames[i].to_bytes() {
+                 args.push(s);
              }
         }
         
-        match self.script_engine.eval(&script, keys, args, self.db.clone(), self.aof.clone()) {
+        match self.script_engine.eval(&script, keys, args, db.clone(), self.aof.clone()) {
              Ok(res) => Ok(RespFrame::BulkString(Some(res))),
-             Err(e) => Ok(RespFrame::Error(format!("ERR script error: {}", e))),
+             Err(e) => Err(crate::scripting::call_error(&e)
+                 .unwrap_or_else(|| ZedisError::err(format!("script error: {}", e)))
+                 .into()),
         }
     }
 
 
-    async fn handle_sadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 3 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'sadd' command".to_string()));
-        }
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+    async fn handle_sadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
 
         let mut count = 0;
         for i in 2..frames.len() {
-             let member = match &frames[i] { RespFrame::BulkString(Some(m)) => m.to_string(), _ => continue };
-             if self.db.sadd(key.clone(), member) {
+             let member = match frames[i].to_bytes() { Some(m) => m, None => continue };
+             if db.sadd(key.clone(), member)? {
                  count += 1;
              }
         }
         Ok(RespFrame::Integer(count))
     }
 
-    async fn handle_smembers(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 {
-             return Ok(RespFrame::Error("ERR wrong number of arguments for 'smembers' command".to_string()));
-        }
-        let key = match &frames[1] {
-            RespFrame::BulkString(Some(k)) => k.to_string(),
-             _ => return Ok(RespFrame::Error("ERR invalid key".to_string())),
-        };
+    async fn handle_smembers(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
         
-        let members = self.db.smembers(&key);
+        let members = db.smembers(&key)?;
+        // `~` set under RESP3, a plain array under RESP2
         let resp = members.into_iter().map(|m| RespFrame::BulkString(Some(m))).collect();
-        Ok(RespFrame::Array(Some(resp)))
+        Ok(RespFrame::Set(resp))
     }
-    async fn handle_vadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_vadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
         
         let mut vector = Vec::new();
         for i in 2..frames.len() {
-             if let RespFrame::BulkString(Some(s)) = &frames[i] {
-                 if let Ok(f) = s.parse::<f32>() { vector.push(f); }
-             }
+             vector.push(arg_f64(frames, i)? as f32);
         }
         
         // MVP: vector must be f32s. 
-        let ok = self.db.vadd(key, vector);
-        Ok(RespFrame::Integer(if ok { 1 } else { 0 }))
+        db.vadd(key, vector)?;
+        Ok(RespFrame::Integer(1))
     }
 
-    async fn handle_bfadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let item = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR item".to_string())) };
+    async fn handle_bfadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let item = arg_bytes(frames, 2)?;
         
-        let ok = self.db.bf_add(key, item);
-        Ok(RespFrame::Integer(if ok { 1 } else { 0 }))
+        db.bf_add(key, &item)?;
+        Ok(RespFrame::Integer(1))
     }
     
-    async fn handle_jsonset(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let json = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR json".to_string())) };
+    async fn handle_jsonset(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let json = arg_str(frames, 2)?;
         
-        let ok = self.db.json_set(key, json);
-        Ok(RespFrame::SimpleString(if ok { "OK".to_string() } else { "ERR".to_string() }))
+        db.json_set(key, json)?;
+        Ok(RespFrame::SimpleString("OK".to_string()))
     }
-    async fn handle_vadd_text(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+    async fn handle_vadd_text(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         // VADD.TEXT key text - Auto-embed text and store as hybrid vector
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR wrong number of arguments for 'VADD.TEXT' command".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR invalid key".to_string())) };
-        let text = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR invalid text".to_string())) };
+        let key = arg_bytes(frames, 1)?;
+        let text = arg_str(frames, 2)?.to_string();
         
         if let Some(model) = &self.bge_model {
              match model.embed_hybrid(&text) {
                  Ok((dense, sparse)) => {
-                     let ok = self.db.vadd_hybrid(key, dense, Some(sparse));
-                     Ok(RespFrame::Integer(if ok { 1 } else { 0 }))
+                     db.vadd_hybrid(key, dense, Some(sparse))?;
+                     Ok(RespFrame::Integer(1))
                  },
-                 Err(e) => Ok(RespFrame::Error(format!("ERR embedding failed: {}", e)))
+                 Err(e) => Err(ZedisError::err(format!("embedding failed: {}", e)).into())
              }
         } else {
-             Ok(RespFrame::Error("ERR BGE-M3 model not loaded. Please ensure 'bge-m3' directory exists with model files.".to_string()))
+             Err(ZedisError::err("BGE-M3 model not loaded. Please ensure the 'bge-model-dir' directory exists with model files.").into())
         }
     }
 
-    async fn handle_vsearch_text(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+    async fn handle_vsearch_text(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         // VSEARCH.TEXT index_prefix query k - Embed query and search for similar documents
-        if frames.len() != 4 { return Ok(RespFrame::Error("ERR wrong number of arguments for 'VSEARCH.TEXT' command".to_string())); }
-        let index_prefix = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR invalid index".to_string())) };
-        let query = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR invalid query".to_string())) };
-        let k = match &frames[3] { RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(1), _ => 1 };
+        let index_prefix = arg_bytes(frames, 1)?;
+        let query = arg_str(frames, 2)?.to_string();
+        let k = arg_i64(frames, 3)?.max(1) as usize;
         
         if let Some(model) = &self.bge_model {
              match model.embed_hybrid(&query) {
                  Ok((dense, sparse)) => {
                      // Search for similar vectors (use default alpha of 0.7 for hybrid search)
-                     let results = self.db.vsearch_hybrid(&index_prefix, dense, Some(sparse), k, 0.7);
-                     let mut resp = Vec::new();
-                     for (id, score) in results {
-                         resp.push(RespFrame::BulkString(Some(id)));
-                         resp.push(RespFrame::SimpleString(format!("{:.4}", score)));
-                     }
-                     Ok(RespFrame::Array(Some(resp)))
+                     let results = db.vsearch_hybrid(&index_prefix, dense, Some(sparse), k, 0.7)?;
+                     let resp = results.into_iter()
+                         .map(|(id, score)| (RespFrame::bulk(id), RespFrame::Double(score as f64)))
+                         .collect();
+                     Ok(RespFrame::Map(resp))
                  },
-                 Err(e) => Ok(RespFrame::Error(format!("ERR embedding failed: {}", e)))
+                 Err(e) => Err(ZedisError::err(format!("embedding failed: {}", e)).into())
              }
         } else {
-             Ok(RespFrame::Error("ERR BGE-M3 model not loaded. Please ensure 'bge-m3' directory exists with model files.".to_string()))
+             Err(ZedisError::err("BGE-M3 model not loaded. Please ensure the 'bge-model-dir' directory exists with model files.").into())
         }
     }
 
-    async fn handle_tsadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let ts = match &frames[2] { RespFrame::BulkString(Some(s)) => s.parse::<u64>().unwrap_or(0), _ => return Ok(RespFrame::Error("ERR ts".to_string())) };
-        let val = match &frames[3] { RespFrame::BulkString(Some(s)) => s.parse::<f64>().unwrap_or(0.0), _ => return Ok(RespFrame::Error("ERR val".to_string())) };
+    async fn handle_tsadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let ts = u64::try_from(arg_i64(frames, 2)?).map_err(|_| ZedisError::NotInteger)?;
+        let val = arg_f64(frames, 3)?;
         
-        self.db.ts_add(key, ts, val);
+        db.ts_add(key, ts, val)?;
         Ok(RespFrame::Integer(1)) 
     }
 
-    async fn handle_tsrange(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let min = match &frames[2] { RespFrame::BulkString(Some(s)) => s.parse::<u64>().unwrap_or(0), _ => 0 };
-        let max = match &frames[3] { RespFrame::BulkString(Some(s)) => s.parse::<u64>().unwrap_or(u64::MAX), _ => u64::MAX };
+    async fn handle_tsrange(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let min = match arg_str(frames, 2)? {
+            "-" => 0,
+            s => s.parse::<u64>().map_err(|_| ZedisError::NotInteger)?,
+        };
+        let max = match arg_str(frames, 3)? {
+            "+" => u64::MAX,
+            s => s.parse::<u64>().map_err(|_| ZedisError::NotInteger)?,
+        };
 
-        let result = self.db.ts_range(&key, min, max);
+        let result = db.ts_range(&key, min, max)?;
         let mut arr = Vec::new();
         for (t, v) in result {
              let mut sample = Vec::new();
              sample.push(RespFrame::Integer(t as i64));
-             sample.push(RespFrame::SimpleString(v.to_string()));
+             sample.push(RespFrame::Double(v));
              arr.push(RespFrame::Array(Some(sample)));
         }
         Ok(RespFrame::Array(Some(arr)))
     }
 
-    async fn handle_graphadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let u = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR u".to_string())) };
-        let v = match &frames[3] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR v".to_string())) };
+    async fn handle_graphadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let u = String::from_utf8_lossy(arg(frames, 2)?).into_owned();
+        let v = String::from_utf8_lossy(arg(frames, 3)?).into_owned();
         
-        self.db.graph_add_edge(key, u, v);
+        db.graph_add_edge(key, u, v)?;
         Ok(RespFrame::Integer(1)) 
     }
 
-    async fn handle_graphbfs(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let start = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR start".to_string())) };
-        let depth = match &frames[3] { RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(1), _ => 1 };
+    async fn handle_graphbfs(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let start = String::from_utf8_lossy(arg(frames, 2)?).into_owned();
+        let depth = usize::try_from(arg_i64(frames, 3)?).map_err(|_| ZedisError::NotInteger)?;
         
-        let nodes = self.db.graph_bfs(&key, &start, depth);
-        let resp = nodes.into_iter().map(|n| RespFrame::BulkString(Some(n))).collect();
+        let nodes = db.graph_bfs(&key, &start, depth)?;
+        let resp = nodes.into_iter().map(RespFrame::bulk).collect();
         Ok(RespFrame::Array(Some(resp)))
     }
 
-    async fn handle_mlload(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let name = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR name".to_string())) };
+    async fn handle_mlload(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let name = String::from_utf8_lossy(arg(frames, 2)?).into_owned();
         
-        self.db.ml_load(key, name);
+        db.ml_load(key, name);
         Ok(RespFrame::SimpleString("OK".to_string()))
     }
 
-    async fn handle_mlrun(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() < 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_mlrun(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg_bytes(frames, 1)?;
          
          let mut input = Vec::new();
          for i in 2..frames.len() {
-             if let RespFrame::BulkString(Some(s)) = &frames[i] {
-                 if let Ok(f) = s.parse::<f32>() { input.push(f); }
-             }
+             input.push(arg_f64(frames, i)? as f32);
          }
 
-         match self.db.ml_run(&key, &input) {
+         match db.ml_run(&key, &input)? {
              Some(res) => {
-                 let arr: Vec<RespFrame> = res.into_iter().map(|f| RespFrame::SimpleString(f.to_string())).collect();
+                 let arr: Vec<RespFrame> = res.into_iter().map(|f| RespFrame::Double(f as f64)).collect();
                  Ok(RespFrame::Array(Some(arr)))
              },
-             None => Ok(RespFrame::Error("ERR no model or run fail".to_string())),
+             None => Err(ZedisError::err("no model loaded at this key").into()),
          }
     }
 
 
-    async fn handle_vsearch(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+    async fn handle_vsearch(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         // VSEARCH key 1.0 2.0 ... K
-        if frames.len() < 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+        let key = arg_bytes(frames, 1)?;
         
-        let k_arg = frames.last().unwrap(); // Simplification: last arg is K
-        let k = match k_arg { RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(1), _ => 1 };
+        // Simplification: last arg is K
+        let k = arg_i64(frames, frames.len() - 1)?.max(1) as usize;
 
         let mut vector = Vec::new();
         for i in 2..frames.len()-1 {
-             if let RespFrame::BulkString(Some(s)) = &frames[i] {
-                 if let Ok(f) = s.parse::<f32>() { vector.push(f); }
-             }
+             vector.push(arg_f64(frames, i)? as f32);
         }
         
-        let results = self.db.vsearch(&key, vector, k);
-        // Return Array of [Key, Score]
-        let mut resp = Vec::new();
-        for (id, score) in results {
-            resp.push(RespFrame::BulkString(Some(id)));
-            resp.push(RespFrame::SimpleString(score.to_string()));
-        }
-        Ok(RespFrame::Array(Some(resp)))
+        let results = db.vsearch(&key, vector, k)?;
+        // Map of id -> score (flattened to [id, score, ...] for RESP2)
+        let resp = results.into_iter()
+            .map(|(id, score)| (RespFrame::bulk(id), RespFrame::Double(score as f64)))
+            .collect();
+        Ok(RespFrame::Map(resp))
     }
 
-    async fn handle_bfexists(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let item = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR item".to_string())) };
+    async fn handle_bfexists(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let item = arg_bytes(frames, 2)?;
         
-        let exists = self.db.bf_exists(&key, &item);
+        let exists = db.bf_exists(&key, &item)?;
         Ok(RespFrame::Integer(if exists { 1 } else { 0 }))
     }
 
-    async fn handle_jsonget(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let path = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR path".to_string())) };
+    async fn handle_jsonget(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
+        let path = arg_str(frames, 2)?;
         
-        match self.db.json_get(&key, &path) {
-            Some(v) => Ok(RespFrame::BulkString(Some(v))),
+        match db.json_get(&key, path)? {
+            Some(v) => Ok(RespFrame::bulk(v)),
             None => Ok(RespFrame::BulkString(None)),
         }
     }
-    async fn handle_vadd_m3(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+    async fn handle_vadd_m3(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         // VADD.M3 key text
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let text = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR text".to_string())) };
+        let key = arg_bytes(frames, 1)?;
+        let text = arg_str(frames, 2)?.to_string();
 
         if let Some(model) = &self.bge_model {
              match model.embed_hybrid(&text) {
                  Ok((dense, sparse)) => {
-                     let ok = self.db.vadd_hybrid(key, dense, Some(sparse));
-                     Ok(RespFrame::Integer(if ok { 1 } else { 0 }))
+                     db.vadd_hybrid(key, dense, Some(sparse))?;
+                     Ok(RespFrame::Integer(1))
                  },
-                 Err(e) => Ok(RespFrame::Error(format!("ERR embedding error: {}", e)))
+                 Err(e) => Err(ZedisError::err(format!("embedding error: {}", e)).into())
              }
         } else {
-             Ok(RespFrame::Error("ERR BGE-M3 model not loaded".to_string()))
+             Err(ZedisError::err("BGE-M3 model not loaded").into())
         }
     }
 
-    async fn handle_vsearch_hybrid(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+    async fn handle_vsearch_hybrid(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
         // VSEARCH.HYBRID key query k alpha
-        if frames.len() < 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let query = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR query".to_string())) };
-        let k = match &frames[3] { RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(1), _ => 1 };
-        let alpha = if frames.len() > 4 {
-             match &frames[4] { RespFrame::BulkString(Some(s)) => s.parse::<f32>().unwrap_or(0.5), _ => 0.5 }
-        } else { 0.5 };
+        let key = arg_bytes(frames, 1)?;
+        let query = arg_str(frames, 2)?.to_string();
+        let k = arg_i64(frames, 3)?.max(1) as usize;
+        let alpha = if frames.len() > 4 { arg_f64(frames, 4)? as f32 } else { 0.5 };
 
         if let Some(model) = &self.bge_model {
              match model.embed_hybrid(&query) {
                  Ok((dense, sparse)) => {
-                     let results = self.db.vsearch_hybrid(&key, dense, Some(sparse), k, alpha);
-                     let mut resp = Vec::new();
-                     for (id, score) in results {
-                         resp.push(RespFrame::BulkString(Some(id)));
-                         resp.push(RespFrame::SimpleString(score.to_string()));
-                     }
-                     Ok(RespFrame::Array(Some(resp)))
+                     let results = db.vsearch_hybrid(&key, dense, Some(sparse), k, alpha)?;
+                     let resp = results.into_iter()
+                         .map(|(id, score)| (RespFrame::bulk(id), RespFrame::Double(score as f64)))
+                         .collect();
+                     Ok(RespFrame::Map(resp))
                  },
-                 Err(e) => Ok(RespFrame::Error(format!("ERR embedding error: {}", e)))
+                 Err(e) => Err(ZedisError::err(format!("embedding error: {}", e)).into())
              }
         } else {
-             Ok(RespFrame::Error("ERR BGE-M3 model not loaded".to_string()))
+             Err(ZedisError::err("BGE-M3 model not loaded").into())
+        }
+    }
+    /// SAVE: blocking RDB snapshot.
+    async fn handle_save(&self) -> Result<RespFrame> {
+        let path = self.config.read().dbfilename.clone();
+        match Persistence::save_rdb(&self.keyspace, &path) {
+            Ok(_) => Ok(RespFrame::SimpleString("OK".to_string())),
+            Err(e) => Err(ZedisError::err(format!("save failed: {}", e)).into()),
         }
     }
+
+    /// PING [message]
+    async fn handle_ping(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        match frames.len() {
+            1 => Ok(RespFrame::SimpleString("PONG".to_string())),
+            2 => Ok(RespFrame::BulkString(frames[1].to_bytes())),
+            _ => Err(ZedisError::WrongArity("ping".to_string()).into()),
+        }
+    }
+
+    /// SHUTDOWN [NOSAVE|SAVE]. The server loop performs the actual
+    /// shutdown (drain clients, flush AOF, optional RDB save).
+    async fn handle_shutdown(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let mode = match frames.get(1).and_then(|f| f.as_str()).map(|s| s.to_uppercase()) {
+            None if frames.len() == 1 => SaveMode::Default,
+            Some(ref m) if m == "NOSAVE" && frames.len() == 2 => SaveMode::NoSave,
+            Some(ref m) if m == "SAVE" && frames.len() == 2 => SaveMode::Save,
+            _ => return Err(ZedisError::Syntax.into()),
+        };
+        log::info!("🛑 Shutdown: requested by SHUTDOWN command ({:?})", mode);
+        self.shutdown.request(mode);
+        Ok(RespFrame::SimpleString("OK".to_string()))
+    }
+
+    /// CLIENT ID|INFO|LIST|SETNAME|GETNAME|KILL|PAUSE|UNPAUSE|NO-EVICT|REPLY
+    async fn handle_client(&self, client: &Client, frames: &[RespFrame]) -> Result<RespFrame> {
+        let sub = arg_str(frames, 1)?.to_uppercase();
+        let args: Vec<String> = frames[2..].iter()
+            .map(|f| f.as_bytes().map(|b| String::from_utf8_lossy(b).into_owned()).unwrap_or_default())
+            .collect();
+        let syntax_error = || -> Result<RespFrame> { Err(ZedisError::Syntax.into()) };
+
+        match sub.as_str() {
+            "ID" => Ok(RespFrame::Integer(client.id as i64)),
+            "INFO" => Ok(RespFrame::bulk(format!("{}\n", client.info_line()))),
+            "LIST" => {
+                // CLIENT LIST [TYPE normal|pubsub] [ID id [id ...]]
+                let mut kind: Option<String> = None;
+                let mut ids: Option<Vec<u64>> = None;
+                let mut i = 0;
+                while i < args.len() {
+                    match args[i].to_uppercase().as_str() {
+                        "TYPE" if i + 1 < args.len() => {
+                            let k = args[i + 1].to_lowercase();
+                            if !matches!(k.as_str(), "normal" | "pubsub" | "master" | "replica") {
+                                return Err(ZedisError::err(format!("Unknown client type '{}'", args[i + 1])).into());
+                            }
+                            kind = Some(k);
+                            i += 2;
+                        }
+                        "ID" if i + 1 < args.len() => {
+                            let mut list = Vec::new();
+                            for id in &args[i + 1..] {
+                                match id.parse::<u64>() {
+                                    Ok(id) => list.push(id),
+                                    Err(_) => return Err(ZedisError::err("Invalid client ID").into()),
+                                }
+                            }
+                            ids = Some(list);
+                            i = args.len();
+                        }
+                        _ => return syntax_error(),
+                    }
+                }
+                let mut out = String::new();
+                for c in self.clients.all() {
+                    if let Some(ids) = &ids { if !ids.contains(&c.id) { continue; } }
+                    if let Some(kind) = &kind {
+                        let is_pubsub = c.is_pubsub();
+                        let matches = match kind.as_str() {
+                            "normal" => !is_pubsub,
+                            "pubsub" => is_pubsub,
+                            _ => false, // no replication links yet
+                        };
+                        if !matches { continue; }
+                    }
+                    out.push_str(&c.info_line());
+                    out.push('\n');
+                }
+                Ok(RespFrame::bulk(out))
+            }
+            "SETNAME" => {
+                if args.len() != 1 { return syntax_error(); }
+                let name = &args[0];
+                if name.bytes().any(|b| b <= b' ' || b > b'~') {
+                    return Err(ZedisError::err("Client names cannot contain spaces, newlines or special characters.").into());
+                }
+                client.set_name(if name.is_empty() { None } else { Some(name.clone()) });
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            "GETNAME" => Ok(match client.name() {
+                Some(name) => RespFrame::bulk(name),
+                None => RespFrame::BulkString(None),
+            }),
+            "KILL" => {
+                // Legacy form: CLIENT KILL addr
+                if args.len() == 1 {
+                    return match self.clients.all().into_iter().find(|c| c.addr == args[0]) {
+                        Some(c) => { c.kill(); Ok(RespFrame::SimpleString("OK".to_string())) }
+                        None => Err(ZedisError::err("No such client").into()),
+                    };
+                }
+                // Filter form: CLIENT KILL [ID id] [ADDR addr] [LADDR addr] [USER user] [TYPE type] [SKIPME yes|no]
+                if args.is_empty() || !args.len().is_multiple_of(2) { return syntax_error(); }
+                let mut skip_me = true;
+                let mut filters: Vec<(String, String)> = Vec::new();
+                for pair in args.chunks(2) {
+                    let opt = pair[0].to_uppercase();
+                    match opt.as_str() {
+                        "SKIPME" => match pair[1].to_lowercase().as_str() {
+                            "yes" => skip_me = true,
+                            "no" => skip_me = false,
+                            _ => return syntax_error(),
+                        },
+                        "ID" => {
+                            if pair[1].parse::<u64>().is_err() {
+                                return Err(ZedisError::err("client-id should be greater than 0").into());
+                            }
+                            filters.push((opt, pair[1].clone()));
+                        }
+                        "ADDR" | "LADDR" | "USER" | "TYPE" => filters.push((opt, pair[1].clone())),
+                        _ => return syntax_error(),
+                    }
+                }
+                let mut killed = 0;
+                for c in self.clients.all() {
+                    if skip_me && c.id == client.id { continue; }
+                    let matches = filters.iter().all(|(opt, val)| match opt.as_str() {
+                        "ID" => val.parse::<u64>().ok() == Some(c.id),
+                        "ADDR" => &c.addr == val,
+                        "LADDR" => &c.laddr == val,
+                        "USER" => &c.user() == val,
+                        "TYPE" => match val.to_lowercase().as_str() {
+                            "normal" => !c.is_pubsub(),
+                            "pubsub" => c.is_pubsub(),
+                            _ => false,
+                        },
+                        _ => false,
+                    });
+                    if matches {
+                        c.kill();
+                        killed += 1;
+                    }
+                }
+                Ok(RespFrame::Integer(killed))
+            }
+            "PAUSE" => {
+                // CLIENT PAUSE timeout-ms [WRITE|ALL]
+                if args.is_empty() || args.len() > 2 { return syntax_error(); }
+                let timeout = match args[0].parse::<u64>() {
+                    Ok(ms) => ms,
+                    Err(_) => return Err(ZedisError::err("timeout is not an integer or out of range").into()),
+                };
+                let mode = match args.get(1).map(|m| m.to_uppercase()) {
+                    None => PauseMode::All,
+                    Some(m) if m == "ALL" => PauseMode::All,
+                    Some(m) if m == "WRITE" => PauseMode::Write,
+                    _ => return syntax_error(),
+                };
+                self.clients.pause(std::time::Duration::from_millis(timeout), mode);
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            "UNPAUSE" => {
+                self.clients.unpause();
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            "NO-EVICT" => {
+                match args.first().map(|a| a.to_uppercase()).as_deref() {
+                    Some("ON") if args.len() == 1 => client.set_no_evict(true),
+                    Some("OFF") if args.len() == 1 => client.set_no_evict(false),
+                    _ => return syntax_error(),
+                }
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            "REPLY" => {
+                // The connection loop decides which replies are actually sent
+                match args.first().map(|a| a.to_uppercase()).as_deref() {
+                    Some("ON") if args.len() == 1 => client.set_reply_mode(ReplyMode::On),
+                    Some("OFF") if args.len() == 1 => client.set_reply_mode(ReplyMode::Off),
+                    Some("SKIP") if args.len() == 1 => client.set_reply_mode(ReplyMode::Skip),
+                    _ => return syntax_error(),
+                }
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            _ => Err(ZedisError::UnknownSubcommand { command: "CLIENT", sub }.into()),
+        }
+    }
+
     /// BATCH EXECUTE (for Transactions)
-    pub async fn execute_transaction(&self, frames: Vec<RespFrame>) -> Result<RespFrame> {
+    /// Errors MULTI catches while queuing (unknown command, wrong arity);
+    /// any of them makes the following EXEC fail with EXECABORT.
+    pub fn check_queued(&self, frame: &RespFrame) -> DbResult<()> {
+        let RespFrame::Array(Some(frames)) = frame else { return Ok(()) };
+        let Some(name) = frames.first().and_then(|f| f.as_bytes()) else { return Ok(()) };
+        let Some(spec) = command::lookup(&String::from_utf8_lossy(name).to_uppercase()) else {
+            return Err(unknown_command(frames));
+        };
+        if !spec.arity_ok(frames.len()) {
+            return Err(ZedisError::WrongArity(spec.name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub async fn execute_transaction(&self, client: &Client, frames: Vec<RespFrame>) -> Result<RespFrame> {
         let mut results = Vec::new();
         // Execute sequentially. Note: No global lock, so not fully ACID across shards in this MVP.
         // But atomic relative to the connection execution.
         for frame in frames {
-             let res = self.execute(frame).await?;
+             let res = self.execute(client, frame).await?;
              results.push(res);
         }
         Ok(RespFrame::Array(Some(results)))
@@ -957,178 +1485,799 @@ use crate::persistence::Persistence;
 
     /// PUB/SUB Handlers
     pub async fn handle_publish(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let channel = match &frames[1] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR channel".to_string())) };
-        let message = match &frames[2] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR message".to_string())) };
+        let channel = arg(frames, 1)?;
+        let message = arg(frames, 2)?;
         
-        let payload = format!("{} {}", channel, message); // Format: "channel message"
         // Broadcast
-        let subs = self.pubsub_tx.send(payload).unwrap_or(0);
+        let subs = self.pubsub_tx.send((Bytes::copy_from_slice(channel), Bytes::copy_from_slice(message))).unwrap_or(0);
         Ok(RespFrame::Integer(subs as i64))
     }
 
-    pub async fn handle_subscribe(&self, frames: &[RespFrame], conn: &mut crate::io::connection::Connection) -> Result<()> {
-         if frames.len() < 2 { 
-             conn.write_frame(&RespFrame::Error("ERR args".to_string())).await?;
-             return Ok(()); 
-         }
-         
-         let mut channels = Vec::new();
-         for i in 1..frames.len() {
-             if let RespFrame::BulkString(Some(s)) = &frames[i] {
-                 channels.push(s.to_string());
-             }
-         }
-         
-         // subscribe confirmation
-         for (i, c) in channels.iter().enumerate() {
-             // array: ["subscribe", channel, count]
-             let resp = RespFrame::Array(Some(vec![
-                 RespFrame::BulkString(Some("subscribe".to_string())),
-                 RespFrame::BulkString(Some(c.clone())),
-                 RespFrame::Integer((i+1) as i64)
-             ]));
-             conn.write_frame(&resp).await?;
-         }
+    /// SUBSCRIBE / PSUBSCRIBE / UNSUBSCRIBE / PUNSUBSCRIBE from a client not
+    /// yet in pub/sub mode. Returns once it holds no subscriptions again.
+    pub async fn handle_subscribe<S: crate::io::traits::AsyncStream>(&self, client: &Client, frames: &[RespFrame], conn: &mut crate::io::connection::Connection<S>) -> Result<()> {
+        // Listen before confirming, so nothing published after the reply is missed
+        let rx = self.pubsub_tx.subscribe();
+        self.pubsub_session(client, frames, rx, conn).await
+    }
+
+    async fn pubsub_session<S: crate::io::traits::AsyncStream>(
+        &self,
+        client: &Client,
+        frames: &[RespFrame],
+        mut rx: tokio::sync::broadcast::Receiver<(Bytes, Bytes)>,
+        conn: &mut crate::io::connection::Connection<S>,
+    ) -> Result<()> {
+        let mut subs = Subscriptions::default();
+        self.pubsub_command(client, &mut subs, frames, conn).await?;
+        if subs.count() == 0 {
+            return conn.flush().await;
+        }
+        let result = self.pubsub_loop(client, &mut subs, &mut rx, conn).await;
+        client.set_subscriptions(0);
+        client.set_pattern_subscriptions(0);
+        result
+    }
+
+    /// Run one command from a subscribed client and queue its replies.
+    async fn pubsub_command<S: crate::io::traits::AsyncStream>(
+        &self,
+        client: &Client,
+        subs: &mut Subscriptions,
+        frames: &[RespFrame],
+        conn: &mut crate::io::connection::Connection<S>,
+    ) -> Result<()> {
+        let name = frames.first().and_then(|f| f.as_str()).unwrap_or_default().to_lowercase();
+        let confirm = |kind: &'static str, target: Option<Bytes>, count: usize| RespFrame::Push(vec![
+            RespFrame::bulk(kind),
+            RespFrame::BulkString(target),
+            RespFrame::Integer(count as i64),
+        ]);
+        match name.as_str() {
+            "subscribe" | "psubscribe" => {
+                if frames.len() < 2 {
+                    conn.queue_frame(&ZedisError::WrongArity(name).into());
+                    return Ok(());
+                }
+                let patterns = name == "psubscribe";
+                let kind = if patterns { "psubscribe" } else { "subscribe" };
+                for target in frames[1..].iter().filter_map(|f| f.to_bytes()) {
+                    subs.set(patterns).insert(target.clone());
+                    conn.queue_frame(&confirm(kind, Some(target), subs.count()));
+                }
+            }
+            "unsubscribe" | "punsubscribe" => {
+                let patterns = name == "punsubscribe";
+                let kind = if patterns { "punsubscribe" } else { "unsubscribe" };
+                // No arguments: everything of that kind
+                let targets: Vec<Bytes> = if frames.len() > 1 {
+                    frames[1..].iter().filter_map(|f| f.to_bytes()).collect()
+                } else {
+                    subs.set(patterns).iter().cloned().collect()
+                };
+                if targets.is_empty() {
+                    conn.queue_frame(&confirm(kind, None, subs.count()));
+                }
+                for target in targets {
+                    subs.set(patterns).remove(&target);
+                    conn.queue_frame(&confirm(kind, Some(target), subs.count()));
+                }
+            }
+            // RESP2 subscribers get PING as a two-element array, like a message
+            "ping" if frames.len() <= 2 && conn.protocol == crate::core::protocol::ProtocolVersion::Resp2 => {
+                let payload = frames.get(1).and_then(|f| f.to_bytes()).unwrap_or_default();
+                conn.queue_frame(&RespFrame::Array(Some(vec![RespFrame::bulk("pong"), RespFrame::BulkString(Some(payload))])));
+            }
+            // RESP3 can tell pushes from replies, so any command may run
+            _ if conn.protocol == crate::core::protocol::ProtocolVersion::Resp3 || name == "ping" => {
+                let reply = self.execute(client, RespFrame::Array(Some(frames.to_vec()))).await?;
+                conn.queue_frame(&reply);
+            }
+            _ => conn.queue_frame(&ZedisError::err(format!(
+                "Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context", name
+            )).into()),
+        }
+        client.set_subscriptions(subs.channels.len());
+        client.set_pattern_subscriptions(subs.patterns.len());
+        Ok(())
+    }
+
+    /// Deliver messages and run pub/sub commands until the client holds no
+    /// subscriptions. Messages queue in the connection's output buffer,
+    /// which the pubsub client-output-buffer-limit class bounds.
+    async fn pubsub_loop<S: crate::io::traits::AsyncStream>(
+        &self,
+        client: &Client,
+        subs: &mut Subscriptions,
+        rx: &mut tokio::sync::broadcast::Receiver<(Bytes, Bytes)>,
+        conn: &mut crate::io::connection::Connection<S>,
+    ) -> Result<()> {
+         use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 
-         let mut rx = self.pubsub_tx.subscribe();
-         
          loop {
+             // Queue everything already published, then write it in one go
+             loop {
+                 match rx.try_recv() {
+                     Ok((chan, payload)) => {
+                         for frame in subs.messages(chan, payload) {
+                             conn.queue_frame(&frame);
+                         }
+                         if self.output_buffer_exceeded(client, conn.pending_output()) {
+                             return Ok(());
+                         }
+                     }
+                     Err(TryRecvError::Empty) => break,
+                     // Fell behind the broadcast backlog: drop the client rather than skip messages
+                     Err(TryRecvError::Lagged(missed)) => {
+                         self.broadcast_lagged(client, missed);
+                         return Ok(());
+                     }
+                     Err(TryRecvError::Closed) => return Ok(()),
+                 }
+             }
+
+             if conn.pending_output() > 0 {
+                 let (qbuf, qbuf_free) = conn.query_buffer();
+                 client.set_buffers(qbuf, qbuf_free, conn.pending_output());
+                 let deadline = self.clients.output_flush_deadline(client);
+                 // Messages keep being taken off the channel while a slow
+                 // reader holds up the write, so what bounds the backlog is
+                 // the pubsub limit on queued bytes, not the channel length
+                 let in_flight = conn.pending_output();
+                 let protocol = conn.protocol;
+                 let mut backlog = Vec::new();
+                 let flushed = {
+                     let flush = conn.flush_until(deadline);
+                     tokio::pin!(flush);
+                     loop {
+                         tokio::select! {
+                             res = &mut flush => break res?,
+                             msg_res = rx.recv() => match msg_res {
+                                 Ok((chan, payload)) => {
+                                     for frame in subs.messages(chan, payload) {
+                                         frame.encode_for(protocol, &mut backlog);
+                                     }
+                                     if self.output_buffer_exceeded(client, in_flight + backlog.len()) {
+                                         return Ok(());
+                                     }
+                                 }
+                                 Err(RecvError::Lagged(missed)) => {
+                                     self.broadcast_lagged(client, missed);
+                                     return Ok(());
+                                 }
+                                 Err(RecvError::Closed) => return Ok(()),
+                             },
+                             // The soft-limit timer may start during this write;
+                             // its window runs out even if nothing else is published
+                             _ = soft_limit_expired(self.clients.output_flush_deadline(client)) => {
+                                 self.output_buffer_disconnect(client);
+                                 return Ok(());
+                             }
+                             _ = self.shutdown.requested() => return Ok(()),
+                             _ = client.killed() => return Ok(()),
+                         }
+                     }
+                 };
+                 if !flushed {
+                     self.output_buffer_disconnect(client);
+                     return Ok(());
+                 }
+                 conn.queue_encoded(&backlog);
+                 // Re-evaluated on what is left; an empty buffer stops the soft-limit timer
+                 if self.output_buffer_exceeded(client, conn.pending_output()) {
+                     return Ok(());
+                 }
+                 client.set_buffers(qbuf, qbuf_free, conn.pending_output());
+                 continue;
+             }
+
              tokio::select! {
                  msg_res = rx.recv() => {
                      match msg_res {
-                         Ok(msg) => {
-                             // msg format "channel message"
-                             if let Some((chan, payload)) = msg.split_once(' ') {
-                                 if channels.contains(&chan.to_string()) {
-                                     let push = RespFrame::Array(Some(vec![
-                                         RespFrame::BulkString(Some("message".to_string())),
-                                         RespFrame::BulkString(Some(chan.to_string())),
-                                         RespFrame::BulkString(Some(payload.to_string())),
-                                     ]));
-                                     conn.write_frame(&push).await?;
-                                 }
+                         Ok((chan, payload)) => {
+                             for frame in subs.messages(chan, payload) {
+                                 conn.queue_frame(&frame);
                              }
                          }
-                         Err(_) => break, // Lagged or closed
+                         Err(RecvError::Lagged(missed)) => {
+                             self.broadcast_lagged(client, missed);
+                             return Ok(());
+                         }
+                         Err(RecvError::Closed) => return Ok(()),
                      }
                  }
-                 // Check if client disconnected or sent unsubscribe? 
-                 // We need to read from conn simultaneously.
-                 input = conn.read_frame() => {
-                     match input {
-                         Ok(Some(frame)) => {
-                              // If UNSUBSCRIBE ... complex logic to remove from filtering
-                              // For MVP, any command breaks out or we implement proper "PubSub Mode" state machine
-                              // Let's assume if we get input, we check if it is unsubscribe.
-                              // If not, we might error or ignore. 
-                              // Current implementation: any client input breaks the loop to be safe/simple
-                              break;
+                 _ = self.shutdown.requested() => return Ok(()),
+                 _ = client.killed() => return Ok(()),
+                 input = conn.read_frame() => match input? {
+                     Some(RespFrame::Array(Some(frames))) if !frames.is_empty() => {
+                         self.pubsub_command(client, subs, &frames, conn).await?;
+                         // The last UNSUBSCRIBE leaves pub/sub mode
+                         if subs.count() == 0 {
+                             return conn.flush().await;
                          }
-                         _ => break, // Disconnect
                      }
-                 }
+                     Some(_) => {}
+                     None => return Ok(()),
+                 },
              }
          }
-         Ok(())
+    }
+
+    /// MONITOR: stream every command executed from now on, filtered by
+    /// what this client's ACL user may run. The next command the client
+    /// sends ends the stream and is handed back to be run normally.
+    pub async fn handle_monitor<S: crate::io::traits::AsyncStream>(&self, client: &Client, conn: &mut crate::io::connection::Connection<S>) -> Result<Option<RespFrame>> {
+        use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+        let user = client.user();
+        if !command::lookup("MONITOR").is_some_and(|spec| self.acl.can_run(&user, spec)) {
+            conn.write_frame(&ZedisError::NoPerm("monitor".to_string()).into()).await?;
+            return Ok(None);
+        }
+        let mut monitor = Monitor::attach();
+        client.set_monitor(true);
+        conn.write_frame(&RespFrame::SimpleString("OK".to_string())).await?;
+
+        let show = |conn: &mut crate::io::connection::Connection<S>, event: &MonitorEvent| {
+            if command::lookup(&event.cmd).is_some_and(|spec| self.acl.can_run(&user, spec)) {
+                conn.queue_frame(&RespFrame::SimpleString(event.line.clone()));
+            }
+        };
+        let result = loop {
+            let lagged = tokio::select! {
+                event = monitor.rx.recv() => match event {
+                    Ok(event) => {
+                        // Write everything already fed in one go
+                        show(conn, &event);
+                        let lagged = loop {
+                            match monitor.rx.try_recv() {
+                                Ok(event) => show(conn, &event),
+                                Err(TryRecvError::Lagged(missed)) => break Some(missed),
+                                Err(_) => break None,
+                            }
+                        };
+                        conn.flush().await?;
+                        lagged
+                    }
+                    Err(RecvError::Lagged(missed)) => Some(missed),
+                    Err(RecvError::Closed) => break Ok(None),
+                },
+                _ = self.shutdown.requested() => break Ok(None),
+                _ = client.killed() => break Ok(None),
+                // Any input (or a disconnect) leaves MONITOR mode
+                input = conn.read_frame() => break Ok(input.ok().flatten()),
+            };
+            // Too slow to keep up with the server
+            if let Some(missed) = lagged {
+                self.broadcast_lagged(client, missed);
+                client.kill();
+                break Ok(None);
+            }
+        };
+        client.set_monitor(false);
+        result
+    }
+
+    /// CONFIG GET|SET|REWRITE|RESETSTAT
+    async fn handle_config(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let sub = match frames.get(1).and_then(|f| f.as_str()) {
+            Some(s) => s.to_uppercase(),
+            None => return Err(ZedisError::WrongArity("config".to_string()).into()),
+        };
+        match sub.as_str() {
+            "GET" if frames.len() >= 3 => {
+                let patterns: Vec<&[u8]> = frames[2..].iter().filter_map(|f| f.as_bytes()).collect();
+                let pairs = self.config.get(&patterns)
+                    .into_iter()
+                    .map(|(name, value)| (RespFrame::bulk(name), RespFrame::bulk(value)))
+                    .collect();
+                Ok(RespFrame::Map(pairs))
+            }
+            "SET" if frames.len() >= 4 && frames.len().is_multiple_of(2) => {
+                let mut pairs = Vec::new();
+                for pair in frames[2..].chunks(2) {
+                    match (pair[0].as_str(), pair[1].as_str()) {
+                        (Some(name), Some(value)) => pairs.push((name.to_string(), value.to_string())),
+                        _ => return Err(ZedisError::err("CONFIG SET failed - arguments must be valid UTF-8").into()),
+                    }
+                }
+                match self.config.set(&pairs) {
+                    Ok(()) => Ok(RespFrame::SimpleString("OK".to_string())),
+                    Err(e) => Err(ZedisError::err(format!("CONFIG SET failed - {}", e)).into()),
+                }
+            }
+            "REWRITE" if frames.len() == 2 => match self.config.rewrite() {
+                Ok(()) => Ok(RespFrame::SimpleString("OK".to_string())),
+                Err(e) => Err(ZedisError::err(format!("Rewriting config file: {}", e)).into()),
+            },
+            "RESETSTAT" if frames.len() == 2 => {
+                self.stats.reset();
+                self.evictor.reset_stats();
+                for db in self.keyspace.iter() {
+                    db.reset_stats();
+                }
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            "GET" | "SET" | "REWRITE" | "RESETSTAT" => {
+                Err(ZedisError::WrongArity(format!("config|{}", sub.to_lowercase())).into())
+            }
+            _ => Err(ZedisError::UnknownSubcommand { command: "CONFIG", sub }.into()),
+        }
+    }
+
+    /// COMMAND | COUNT | INFO [name ...] | DOCS [name ...] | GETKEYS cmd [arg ...]
+    /// | LIST [FILTERBY ACLCAT category | PATTERN pattern]
+    async fn handle_command(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let sub = frames.get(1).and_then(|f| f.as_str()).map(|s| s.to_uppercase()).unwrap_or_default();
+        // Named commands, or every command when none are given
+        let named = |names: &[RespFrame]| -> Vec<Option<&'static command::CommandSpec>> {
+            if names.is_empty() {
+                return command::all().iter().map(Some).collect();
+            }
+            names.iter().map(|f| f.as_str().and_then(command::lookup)).collect()
+        };
+        match sub.as_str() {
+            "" => Ok(RespFrame::Array(Some(command::all().iter().map(command_info).collect()))),
+            "COUNT" if frames.len() == 2 => Ok(RespFrame::Integer(command::all().len() as i64)),
+            "INFO" => Ok(RespFrame::Array(Some(
+                named(&frames[2..]).into_iter().map(|spec| spec.map_or(RespFrame::BulkString(None), command_info)).collect(),
+            ))),
+            "DOCS" => Ok(RespFrame::Map(
+                named(&frames[2..]).into_iter().flatten().map(|spec| (RespFrame::bulk(spec.name), command_docs(spec))).collect(),
+            )),
+            "GETKEYS" if frames.len() >= 3 => {
+                let args = &frames[2..];
+                let Some(spec) = args[0].as_str().and_then(command::lookup) else {
+                    return Err(ZedisError::err("Invalid command specified").into());
+                };
+                if !spec.arity_ok(args.len()) {
+                    return Err(ZedisError::err("Invalid number of arguments specified for command").into());
+                }
+                let keys = spec.keys_of(args);
+                if keys.is_empty() {
+                    return Err(ZedisError::err("The command has no key arguments").into());
+                }
+                Ok(RespFrame::Array(Some(keys.into_iter().map(|k| RespFrame::bulk(Bytes::copy_from_slice(k))).collect())))
+            }
+            "LIST" => {
+                let filter = match frames.len() {
+                    2 => None,
+                    5 if frames[2].as_str().is_some_and(|f| f.eq_ignore_ascii_case("FILTERBY")) => {
+                        let by = frames[3].as_str().map(|s| s.to_uppercase()).unwrap_or_default();
+                        let value = frames[4].as_str().unwrap_or_default().to_string();
+                        match by.as_str() {
+                            "ACLCAT" | "PATTERN" => Some((by, value)),
+                            _ => return Err(ZedisError::Syntax.into()),
+                        }
+                    }
+                    _ => return Err(ZedisError::Syntax.into()),
+                };
+                if let Some(("ACLCAT", category)) = filter.as_ref().map(|(by, v)| (by.as_str(), v)) {
+                    if !command::is_category(&category.to_lowercase()) {
+                        return Err(ZedisError::err(format!("Unknown category '{}'", category)).into());
+                    }
+                }
+                let names = command::all().iter().filter(|spec| match &filter {
+                    None => true,
+                    Some((by, category)) if by == "ACLCAT" => spec.in_category(category),
+                    Some((_, pattern)) => glob_match(pattern.as_bytes(), spec.name.as_bytes(), true),
+                });
+                Ok(RespFrame::Array(Some(names.map(|spec| RespFrame::bulk(spec.name)).collect())))
+            }
+            "COUNT" | "GETKEYS" => Err(ZedisError::WrongArity(format!("command|{}", sub.to_lowercase())).into()),
+            _ => Err(ZedisError::UnknownSubcommand { command: "COMMAND", sub }.into()),
+        }
+    }
+
+    /// SLOWLOG GET [count] | LEN | RESET
+    async fn handle_slowlog(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let sub = frames.get(1).and_then(|f| f.as_str()).map(|s| s.to_uppercase()).unwrap_or_default();
+        match (sub.as_str(), frames.len()) {
+            ("GET", 2 | 3) => {
+                // Default 10, -1 for the whole log
+                let count = match frames.get(2).map(frame_i64) {
+                    None => 10,
+                    Some(Some(-1)) => usize::MAX,
+                    Some(Some(n)) if n >= 0 => n as usize,
+                    Some(_) => return Err(ZedisError::err("count should be greater than or equal to -1").into()),
+                };
+                let entries = self.slowlog.entries(count).into_iter().map(|e| {
+                    RespFrame::Array(Some(vec![
+                        RespFrame::Integer(e.id as i64),
+                        RespFrame::Integer(e.time as i64),
+                        RespFrame::Integer(e.duration_us as i64),
+                        RespFrame::Array(Some(e.args.into_iter().map(|a| RespFrame::BulkString(Some(a))).collect())),
+                        RespFrame::bulk(e.addr),
+                        RespFrame::bulk(e.name),
+                    ]))
+                }).collect();
+                Ok(RespFrame::Array(Some(entries)))
+            }
+            ("LEN", 2) => Ok(RespFrame::Integer(self.slowlog.len() as i64)),
+            ("RESET", 2) => {
+                self.slowlog.reset();
+                Ok(RespFrame::SimpleString("OK".to_string()))
+            }
+            ("GET" | "LEN" | "RESET", _) | ("", _) => Err(ZedisError::WrongArity("slowlog".to_string()).into()),
+            _ => Err(ZedisError::UnknownSubcommand { command: "SLOWLOG", sub }.into()),
+        }
+    }
+
+    /// LATENCY LATEST | HISTORY event | RESET [event ...] | DOCTOR
+    async fn handle_latency(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let sub = frames.get(1).and_then(|f| f.as_str()).map(|s| s.to_uppercase()).unwrap_or_default();
+        let int = |n: u64| RespFrame::Integer(n as i64);
+        match (sub.as_str(), frames.len()) {
+            ("LATEST", 2) => {
+                let events = latency::latest().into_iter().map(|(event, at, ms, max)| {
+                    RespFrame::Array(Some(vec![RespFrame::bulk(event), int(at), int(ms), int(max)]))
+                }).collect();
+                Ok(RespFrame::Array(Some(events)))
+            }
+            ("HISTORY", 3) => {
+                let event = frames[2].as_str().unwrap_or_default().to_lowercase();
+                let samples = latency::history(&event).into_iter().map(|(at, ms)| {
+                    RespFrame::Array(Some(vec![int(at), int(ms)]))
+                }).collect();
+                Ok(RespFrame::Array(Some(samples)))
+            }
+            ("RESET", _) => {
+                let events: Vec<String> = frames[2..].iter().filter_map(|f| f.as_str()).map(|s| s.to_lowercase()).collect();
+                Ok(RespFrame::Integer(latency::reset(&events) as i64))
+            }
+            ("DOCTOR", 2) => Ok(RespFrame::VerbatimString("txt".to_string(), Bytes::from(latency::doctor()))),
+            ("LATEST" | "HISTORY" | "DOCTOR", _) | ("", _) => Err(ZedisError::WrongArity("latency".to_string()).into()),
+            _ => Err(ZedisError::UnknownSubcommand { command: "LATENCY", sub }.into()),
+        }
+    }
+
+    /// INFO [section ...]: default, all, everything, or any of server,
+    /// clients, memory, persistence, stats, keyspace, zedis.
+    async fn handle_info(&self, frames: &[RespFrame]) -> Result<RespFrame> {
+        let requested: Vec<String> = frames[1..].iter().filter_map(|f| f.as_str()).map(|s| s.to_lowercase()).collect();
+        let wants = |section: &str| {
+            if requested.is_empty() {
+                return DEFAULT_INFO_SECTIONS.contains(&section);
+            }
+            requested.iter().any(|r| match r.as_str() {
+                "all" | "everything" => true,
+                "default" => DEFAULT_INFO_SECTIONS.contains(&section),
+                other => other == section,
+            })
+        };
+
+        let mut sections = Vec::new();
+        if wants("server") {
+            let config = self.config.read();
+            let uptime = self.started.elapsed().as_secs();
+            let mut out = String::from("# Server\r\n");
+            info_field(&mut out, "redis_version", REDIS_COMPAT_VERSION);
+            info_field(&mut out, "zedis_version", env!("CARGO_PKG_VERSION"));
+            info_field(&mut out, "redis_mode", "standalone");
+            info_field(&mut out, "os", format!("{} {}", std::env::consts::OS, std::env::consts::ARCH));
+            info_field(&mut out, "arch_bits", usize::BITS);
+            info_field(&mut out, "process_id", std::process::id());
+            info_field(&mut out, "run_id", &self.run_id);
+            info_field(&mut out, "tcp_port", config.port);
+            info_field(&mut out, "uptime_in_seconds", uptime);
+            info_field(&mut out, "uptime_in_days", uptime / 86400);
+            info_field(&mut out, "executable", std::env::current_exe().map(|p| p.display().to_string()).unwrap_or_default());
+            info_field(&mut out, "config_file", config.config_file.clone().unwrap_or_default());
+            sections.push(out);
+        }
+        if wants("clients") {
+            let clients = self.clients.all();
+            let mut out = String::from("# Clients\r\n");
+            info_field(&mut out, "connected_clients", clients.len());
+            info_field(&mut out, "maxclients", self.config.read().maxclients);
+            info_field(&mut out, "pubsub_clients", clients.iter().filter(|c| c.is_pubsub()).count());
+            info_field(&mut out, "blocked_clients", 0);
+            sections.push(out);
+        }
+        if wants("memory") {
+            let (used, peak) = (used_memory(), peak_memory());
+            let total = self.subsystems.hw.total_memory;
+            let maxmemory = self.evictor.maxmemory();
+            let mut out = String::from("# Memory\r\n");
+            info_field(&mut out, "used_memory", used);
+            info_field(&mut out, "used_memory_human", bytes_to_human(used));
+            info_field(&mut out, "used_memory_peak", peak);
+            info_field(&mut out, "used_memory_peak_human", bytes_to_human(peak));
+            info_field(&mut out, "total_system_memory", total);
+            info_field(&mut out, "total_system_memory_human", bytes_to_human(total));
+            info_field(&mut out, "maxmemory", maxmemory);
+            info_field(&mut out, "maxmemory_human", bytes_to_human(maxmemory));
+            info_field(&mut out, "maxmemory_policy", self.evictor.policy().name());
+            info_field(&mut out, "mem_allocator", "mimalloc");
+            info_field(&mut out, "lazyfree_pending_objects", lazyfree::pending());
+            info_field(&mut out, "lazyfreed_objects", lazyfree::freed());
+            sections.push(out);
+        }
+        if wants("persistence") {
+            let rdb = Persistence::rdb_status();
+            let ok_err = |ok: bool| if ok { "ok" } else { "err" };
+            let mut out = String::from("# Persistence\r\n");
+            info_field(&mut out, "loading", 0);
+            info_field(&mut out, "rdb_changes_since_last_save", rdb.changes_since_last_save);
+            info_field(&mut out, "rdb_bgsave_in_progress", 0);
+            info_field(&mut out, "rdb_last_save_time", rdb.last_save_time);
+            info_field(&mut out, "rdb_last_bgsave_status", ok_err(rdb.last_save_ok));
+            info_field(&mut out, "aof_enabled", self.aof.is_enabled() as u8);
+            info_field(&mut out, "aof_rewrite_in_progress", 0);
+            info_field(&mut out, "aof_last_write_status", ok_err(self.aof.last_write_ok()));
+            if self.aof.is_enabled() {
+                info_field(&mut out, "aof_current_size", self.aof.file_size());
+            }
+            sections.push(out);
+        }
+        if wants("stats") {
+            let sum = |f: fn(&Db) -> u64| self.keyspace.iter().map(|db| f(db)).sum::<u64>();
+            let mut out = String::from("# Stats\r\n");
+            self.stats.render(&mut out);
+            info_field(&mut out, "expired_keys", sum(Db::expired_keys));
+            info_field(&mut out, "evicted_keys", self.evictor.evicted_keys());
+            info_field(&mut out, "keyspace_hits", sum(Db::keyspace_hits));
+            info_field(&mut out, "keyspace_misses", sum(Db::keyspace_misses));
+            sections.push(out);
+        }
+        if wants("keyspace") {
+            let mut out = String::from("# Keyspace\r\n");
+            for db in self.keyspace.iter().filter(|db| db.len() > 0) {
+                info_field(&mut out, &format!("db{}", db.index()),
+                    format!("keys={},expires={},avg_ttl={}", db.len(), db.volatile_len(), db.avg_ttl()));
+            }
+            sections.push(out);
+        }
+        if wants("zedis") {
+            let hw = &self.subsystems.hw;
+            let config = self.config.read().clone();
+            let flows = self.subsystems.flow.task_states().await;
+            let mut out = String::from("# Zedis\r\n");
+            info_field(&mut out, "bge_m3", if self.bge_model.is_some() { "loaded" } else { "not_loaded" });
+            info_field(&mut out, "bge_model_dir", &config.bge_model_dir);
+            info_field(&mut out, "zflow_config", &config.zflow_config);
+            info_field(&mut out, "zflow_tasks", flows.len());
+            for (i, (name, state)) in flows.iter().enumerate() {
+                info_field(&mut out, &format!("zflow_task{}", i), format!("name={},state={}", name, state));
+            }
+            info_field(&mut out, "elastic_mask", self.subsystems.mask.name());
+            info_field(&mut out, "elastic_port", config.elastic_port);
+            info_field(&mut out, "cpu_model", &hw.cpu_brand);
+            info_field(&mut out, "cpu_cores", hw.core_ids.len());
+            info_field(&mut out, "simd", hw.simd_level());
+            sections.push(out);
+        }
+        Ok(RespFrame::bulk(sections.join("\r\n")))
     }
 
     // --- PROBABILISTIC HANDLERS ---
 
-    async fn handle_pfadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() < 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_pfadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg_bytes(frames, 1)?;
         
         let mut updated = 0;
         for i in 2..frames.len() {
-             let elm = match &frames[i] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => continue };
-             if self.db.pf_add(key.clone(), elm) { updated = 1; }
+             if db.pf_add(key.clone(), arg(frames, i)?)? { updated = 1; }
         }
         Ok(RespFrame::Integer(updated))
     }
 
-    async fn handle_pfcount(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-        if frames.len() != 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-        let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k, _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-        let count = self.db.pf_count(key);
+    async fn handle_pfcount(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+        let key = arg(frames, 1)?;
+        let count = db.pf_count(key)?;
         Ok(RespFrame::Integer(count as i64))
     }
 
-    async fn handle_cfadd(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-         let item = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR item".to_string())) };
+    async fn handle_cfadd(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg_bytes(frames, 1)?;
+         let item = arg_bytes(frames, 2)?;
          
-         let ok = self.db.cf_add(key, item);
+         let ok = db.cf_add(key, &item)?;
          Ok(RespFrame::Integer(if ok { 1 } else { 0 }))
     }
 
-    async fn handle_cfexists(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k, _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-         let item = match &frames[2] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR item".to_string())) };
+    async fn handle_cfexists(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg(frames, 1)?;
+         let item = arg(frames, 2)?;
          
-         let exists = self.db.cf_exists(key, item);
+         let exists = db.cf_exists(key, item)?;
          Ok(RespFrame::Integer(if exists { 1 } else { 0 }))
     }
 
-    async fn handle_cms_incr(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 4 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-         let item = match &frames[2] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => return Ok(RespFrame::Error("ERR item".to_string())) };
-         let incr = match &frames[3] { RespFrame::BulkString(Some(s)) => s.parse::<usize>().unwrap_or(1), _ => 1 };
+    async fn handle_cms_incr(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg_bytes(frames, 1)?;
+         let item = arg_bytes(frames, 2)?;
+         let incr = usize::try_from(arg_i64(frames, 3)?).map_err(|_| ZedisError::NotInteger)?;
 
-         self.db.cms_incr(key, item, incr);
+         db.cms_incr(key, &item, incr)?;
          Ok(RespFrame::SimpleString("OK".to_string()))
     }
 
-    async fn handle_cms_query(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k, _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-         let item = match &frames[2] { RespFrame::BulkString(Some(s)) => s, _ => return Ok(RespFrame::Error("ERR item".to_string())) };
+    async fn handle_cms_query(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg(frames, 1)?;
+         let item = arg(frames, 2)?;
          
-         let count = self.db.cms_query(key, item);
+         let count = db.cms_query(key, item)?;
          Ok(RespFrame::Integer(count as i64))
     }
 
-    async fn handle_topk_add(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() < 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_topk_add(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg_bytes(frames, 1)?;
          
          for i in 2..frames.len() {
-              let item = match &frames[i] { RespFrame::BulkString(Some(s)) => s.to_string(), _ => continue };
-              self.db.topk_add(key.clone(), item);
+              db.topk_add(key.clone(), arg(frames, i)?)?;
          }
          Ok(RespFrame::SimpleString("OK".to_string()))
     }
 
-    async fn handle_topk_list(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 2 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k, _ => return Ok(RespFrame::Error("ERR key".to_string())) };
+    async fn handle_topk_list(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg(frames, 1)?;
          
-         let list = self.db.topk_list(key);
+         let list = db.topk_list(key)?;
          let mut resp = Vec::new();
-         for (item, count) in list {
+         for (item, _count) in list {
               resp.push(RespFrame::BulkString(Some(item)));
          }
          Ok(RespFrame::Array(Some(resp)))
     }
 
-    async fn handle_tdigest_add(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k.to_string(), _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-         let val = match &frames[2] { RespFrame::BulkString(Some(s)) => s.parse::<f64>().unwrap_or(0.0), _ => return Ok(RespFrame::Error("ERR val".to_string())) };
+    async fn handle_tdigest_add(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg_bytes(frames, 1)?;
+         let val = arg_f64(frames, 2)?;
 
-         self.db.tdigest_add(key, val);
+         db.tdigest_add(key, val)?;
          Ok(RespFrame::SimpleString("OK".to_string()))
     }
 
-    async fn handle_tdigest_quantile(&self, frames: &[RespFrame]) -> Result<RespFrame> {
-         if frames.len() != 3 { return Ok(RespFrame::Error("ERR args".to_string())); }
-         let key = match &frames[1] { RespFrame::BulkString(Some(k)) => k, _ => return Ok(RespFrame::Error("ERR key".to_string())) };
-         let q = match &frames[2] { RespFrame::BulkString(Some(s)) => s.parse::<f64>().unwrap_or(0.5), _ => 0.5 };
+    async fn handle_tdigest_quantile(&self, db: &Arc<Db>, frames: &[RespFrame]) -> Result<RespFrame> {
+         let key = arg(frames, 1)?;
+         let q = arg_f64(frames, 2)?;
+         if !(0.0..=1.0).contains(&q) {
+             return Err(ZedisError::err("T-Digest: quantile should be in [0,1]").into());
+         }
          
-         let val = self.db.tdigest_quantile(key, q);
-         Ok(RespFrame::SimpleString(format!("{:.6}", val)))
+         let val = db.tdigest_quantile(key, q)?;
+         Ok(RespFrame::Double(val))
+    }
+}
+
+/// Channels and patterns of a client in pub/sub mode.
+#[derive(Default)]
+struct Subscriptions {
+    channels: hashbrown::HashSet<Bytes>,
+    patterns: hashbrown::HashSet<Bytes>,
+}
+
+impl Subscriptions {
+    fn set(&mut self, patterns: bool) -> &mut hashbrown::HashSet<Bytes> {
+        if patterns { &mut self.patterns } else { &mut self.channels }
+    }
+
+    /// What SUBSCRIBE and UNSUBSCRIBE confirmations report.
+    fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// A "message" if subscribed to `chan`, plus a "pmessage" per matching pattern.
+    fn messages(&self, chan: Bytes, payload: Bytes) -> Vec<RespFrame> {
+        let mut frames: Vec<RespFrame> = self.patterns.iter().filter(|p| glob_match(p, &chan, false)).map(|pattern| RespFrame::Push(vec![
+            RespFrame::bulk("pmessage"),
+            RespFrame::BulkString(Some(pattern.clone())),
+            RespFrame::BulkString(Some(chan.clone())),
+            RespFrame::BulkString(Some(payload.clone())),
+        ])).collect();
+        if self.channels.contains(&chan) {
+            frames.insert(0, RespFrame::Push(vec![
+                RespFrame::bulk("message"),
+                RespFrame::BulkString(Some(chan)),
+                RespFrame::BulkString(Some(payload)),
+            ]));
+        }
+        frames
+    }
+}
+
+/// Resolves when a running soft-limit timer's window has passed; never if
+/// no timer is running.
+async fn soft_limit_expired(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// One `name:value` INFO line.
+fn info_field(out: &mut String, name: &str, value: impl std::fmt::Display) {
+    let _ = write!(out, "{}:{}\r\n", name, value);
+}
+
+/// Redis-style human readable size: 512B, 1.50K, 2.00M, ...
+fn bytes_to_human(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
     }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", value, UNITS[unit])
+}
+
+/// Integer argument, sent either as a RESP integer or as text.
+fn frame_i64(frame: &RespFrame) -> Option<i64> {
+    match frame {
+        RespFrame::Integer(i) => Some(*i),
+        other => other.as_str()?.parse().ok(),
+    }
+}
+
+/// Argument `i` as raw bytes. Arity is checked before dispatch, so a
+/// missing or non-string argument is a syntax error.
+fn arg(frames: &[RespFrame], i: usize) -> DbResult<&[u8]> {
+    frames.get(i).and_then(|f| f.as_bytes()).ok_or(ZedisError::Syntax)
+}
+
+fn arg_bytes(frames: &[RespFrame], i: usize) -> DbResult<Bytes> {
+    frames.get(i).and_then(|f| f.to_bytes()).ok_or(ZedisError::Syntax)
+}
+
+fn arg_str(frames: &[RespFrame], i: usize) -> DbResult<&str> {
+    frames.get(i).and_then(|f| f.as_str()).ok_or(ZedisError::Syntax)
+}
+
+fn arg_i64(frames: &[RespFrame], i: usize) -> DbResult<i64> {
+    frames.get(i).and_then(frame_i64).ok_or(ZedisError::NotInteger)
+}
+
+/// Float argument; NaN is refused as Redis does.
+fn arg_f64(frames: &[RespFrame], i: usize) -> DbResult<f64> {
+    let value = match frames.get(i) {
+        Some(RespFrame::Integer(n)) => Some(*n as f64),
+        Some(RespFrame::Double(d)) => Some(*d),
+        Some(other) => other.as_str().and_then(|s| s.parse::<f64>().ok()),
+        None => None,
+    };
+    value.filter(|v| !v.is_nan()).ok_or(ZedisError::NotFloat)
+}
+
+/// Redis's reply to an unknown command, quoting the first arguments.
+fn unknown_command(frames: &[RespFrame]) -> ZedisError {
+    let name = frames[0].as_bytes().map(String::from_utf8_lossy).unwrap_or_default();
+    let mut out = format!("unknown command '{}', with args beginning with: ", name);
+    for arg in frames[1..].iter().filter_map(|f| f.as_bytes()) {
+        let _ = write!(out, "'{}' ", String::from_utf8_lossy(arg));
+    }
+    ZedisError::err(out)
+}
+
+/// One COMMAND / COMMAND INFO entry, in the Redis 7 layout.
+fn command_info(spec: &command::CommandSpec) -> RespFrame {
+    let names = |names: Vec<&str>| RespFrame::Set(names.into_iter().map(|n| RespFrame::SimpleString(n.to_string())).collect());
+    RespFrame::Array(Some(vec![
+        RespFrame::bulk(spec.name),
+        RespFrame::Integer(spec.arity as i64),
+        names(spec.flag_names()),
+        RespFrame::Integer(spec.first_key as i64),
+        RespFrame::Integer(spec.last_key as i64),
+        RespFrame::Integer(spec.step as i64),
+        RespFrame::Set(spec.category_names().into_iter().map(|c| RespFrame::SimpleString(format!("@{}", c))).collect()),
+        // tips, key specs, subcommands
+        RespFrame::Array(Some(Vec::new())),
+        RespFrame::Array(Some(Vec::new())),
+        RespFrame::Array(Some(Vec::new())),
+    ]))
+}
+
+/// One COMMAND DOCS entry.
+fn command_docs(spec: &command::CommandSpec) -> RespFrame {
+    let field = |name: &str, value: &str| (RespFrame::SimpleString(name.to_string()), RespFrame::bulk(value.to_string()));
+    RespFrame::Map(vec![
+        field("summary", spec.summary),
+        field("since", spec.since),
+        field("group", spec.group),
+        field("complexity", spec.complexity),
+    ])
 }
@@ -0,0 +1,439 @@
+//! The command table: one entry per command with its arity, flags, key
+//! positions, ACL categories, docs and handler. `Dispatcher::execute` is
+//! driven by it, and COMMAND exposes it to client libraries.
+
+use super::Dispatcher;
+use crate::core::client::Client;
+use crate::core::error::ZedisError;
+use crate::core::protocol::RespFrame;
+use crate::core::storage::Db;
+use anyhow::Result;
+use hashbrown::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+
+// Command flags, named as in COMMAND INFO.
+pub const WRITE: u32 = 1 << 0;
+pub const READONLY: u32 = 1 << 1;
+pub const DENYOOM: u32 = 1 << 2; // refused with -OOM when maxmemory can't be met
+pub const ADMIN: u32 = 1 << 3; // also kept out of MONITOR
+pub const PUBSUB: u32 = 1 << 4;
+pub const NOSCRIPT: u32 = 1 << 5;
+pub const FAST: u32 = 1 << 6;
+pub const MOVABLEKEYS: u32 = 1 << 7; // keys found by parsing (EVAL numkeys)
+pub const MAY_REPLICATE: u32 = 1 << 8; // not a write, but held by CLIENT PAUSE WRITE
+/// Not shown by COMMAND: the handler appends its own, normalized AOF record
+/// (relative TTLs made absolute, XADD * resolved, ...) instead of the
+/// command being logged as sent.
+pub const OWN_AOF: u32 = 1 << 9;
+
+const FLAG_NAMES: &[(u32, &str)] = &[
+    (WRITE, "write"), (READONLY, "readonly"), (DENYOOM, "denyoom"), (ADMIN, "admin"),
+    (PUBSUB, "pubsub"), (NOSCRIPT, "noscript"), (FAST, "fast"), (MOVABLEKEYS, "movablekeys"),
+    (MAY_REPLICATE, "may_replicate"),
+];
+
+// ACL categories. @read, @write, @admin, @dangerous, @pubsub, @fast and
+// @slow follow from the flags and are not listed per command.
+pub const KEYSPACE: u64 = 1 << 0;
+pub const STRING: u64 = 1 << 1;
+pub const LIST: u64 = 1 << 2;
+pub const HASH: u64 = 1 << 3;
+pub const SET: u64 = 1 << 4;
+pub const SORTEDSET: u64 = 1 << 5;
+pub const BITMAP: u64 = 1 << 6;
+pub const HYPERLOGLOG: u64 = 1 << 7;
+pub const GEO: u64 = 1 << 8;
+pub const STREAM: u64 = 1 << 9;
+pub const CONNECTION: u64 = 1 << 10;
+pub const TRANSACTION: u64 = 1 << 11;
+pub const SCRIPTING: u64 = 1 << 12;
+pub const DANGEROUS: u64 = 1 << 13;
+pub const VECTOR: u64 = 1 << 14;
+pub const JSON: u64 = 1 << 15;
+pub const TIMESERIES: u64 = 1 << 16;
+pub const GRAPH: u64 = 1 << 17;
+pub const BLOOM: u64 = 1 << 18;
+pub const CUCKOO: u64 = 1 << 19;
+pub const CMS: u64 = 1 << 20;
+pub const TOPK: u64 = 1 << 21;
+pub const TDIGEST: u64 = 1 << 22;
+pub const ML: u64 = 1 << 23;
+
+const CATEGORY_NAMES: &[(u64, &str)] = &[
+    (KEYSPACE, "keyspace"), (STRING, "string"), (LIST, "list"), (HASH, "hash"), (SET, "set"),
+    (SORTEDSET, "sortedset"), (BITMAP, "bitmap"), (HYPERLOGLOG, "hyperloglog"), (GEO, "geo"),
+    (STREAM, "stream"), (CONNECTION, "connection"), (TRANSACTION, "transaction"),
+    (SCRIPTING, "scripting"), (DANGEROUS, "dangerous"), (VECTOR, "vector"), (JSON, "json"),
+    (TIMESERIES, "timeseries"), (GRAPH, "graph"), (BLOOM, "bloom"), (CUCKOO, "cuckoo"),
+    (CMS, "cms"), (TOPK, "topk"), (TDIGEST, "tdigest"), (ML, "ml"),
+];
+
+pub type HandlerFuture<'a> = Pin<Box<dyn Future<Output = Result<RespFrame>> + Send + 'a>>;
+pub type Handler = for<'a> fn(&'a Dispatcher, &'a Client, &'a Arc<Db>, &'a [RespFrame]) -> HandlerFuture<'a>;
+
+pub struct CommandSpec {
+    /// Lower-case, as COMMAND reports it.
+    pub name: &'static str,
+    /// Argument count including the name; negative means "at least".
+    pub arity: i32,
+    pub flags: u32,
+    pub first_key: i32,
+    /// Negative counts from the end: -1 is the last argument.
+    pub last_key: i32,
+    pub step: i32,
+    /// `(from, len)`: arguments from index `from` on come in groups of
+    /// `len` (HSET field/value pairs). `len` 0 means no grouping.
+    pub repeat: (usize, usize),
+    pub categories: u64,
+    pub group: &'static str,
+    pub since: &'static str,
+    pub complexity: &'static str,
+    pub summary: &'static str,
+    pub handler: Handler,
+}
+
+impl CommandSpec {
+    const fn new(name: &'static str, arity: i32, flags: u32, categories: u64, handler: Handler) -> Self {
+        Self {
+            name,
+            arity,
+            flags,
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            repeat: (0, 0),
+            categories,
+            group: "generic",
+            since: "1.0.0",
+            complexity: "O(1)",
+            summary: "",
+            handler,
+        }
+    }
+
+    const fn keys(mut self, first: i32, last: i32, step: i32) -> Self {
+        self.first_key = first;
+        self.last_key = last;
+        self.step = step;
+        self
+    }
+
+    const fn repeat(mut self, from: usize, len: usize) -> Self {
+        self.repeat = (from, len);
+        self
+    }
+
+    const fn doc(mut self, group: &'static str, since: &'static str, complexity: &'static str, summary: &'static str) -> Self {
+        self.group = group;
+        self.since = since;
+        self.complexity = complexity;
+        self.summary = summary;
+        self
+    }
+
+    pub fn has(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub fn arity_ok(&self, argc: usize) -> bool {
+        let (from, len) = self.repeat;
+        if len > 0 && (argc < from || !(argc - from).is_multiple_of(len)) {
+            return false;
+        }
+        let argc = argc as i32;
+        if self.arity >= 0 { argc == self.arity } else { argc >= -self.arity }
+    }
+
+    /// Key arguments of `args` (the full command, name included).
+    pub fn keys_of<'a>(&self, args: &'a [RespFrame]) -> Vec<&'a [u8]> {
+        if self.has(MOVABLEKEYS) {
+            // EVAL script numkeys key [key ...] arg [arg ...]
+            let numkeys = args.get(2).and_then(|f| f.as_str()).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
+            return args.iter().skip(3).take(numkeys).filter_map(|f| f.as_bytes()).collect();
+        }
+        if self.first_key <= 0 {
+            return Vec::new();
+        }
+        let last = if self.last_key < 0 { args.len() as i32 + self.last_key } else { self.last_key };
+        (self.first_key..=last.min(args.len() as i32 - 1))
+            .step_by(self.step.max(1) as usize)
+            .filter_map(|i| args[i as usize].as_bytes())
+            .collect()
+    }
+
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        FLAG_NAMES.iter().filter(|(f, _)| self.flags & f != 0).map(|(_, n)| *n).collect()
+    }
+
+    /// Every ACL category of the command, the implied ones included.
+    pub fn category_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = CATEGORY_NAMES.iter().filter(|(c, _)| self.categories & c != 0).map(|(_, n)| *n).collect();
+        for (flag, name) in [(WRITE, "write"), (READONLY, "read"), (ADMIN, "admin"), (PUBSUB, "pubsub")] {
+            if self.has(flag) {
+                names.push(name);
+            }
+        }
+        if self.has(ADMIN) && !names.contains(&"dangerous") {
+            names.push("dangerous");
+        }
+        names.push(if self.has(FAST) { "fast" } else { "slow" });
+        names
+    }
+
+    pub fn in_category(&self, category: &str) -> bool {
+        category.eq_ignore_ascii_case("all") || self.category_names().iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+}
+
+/// Is `name` a known ACL category (any case)? Checks COMMAND LIST FILTERBY
+/// ACLCAT and ACL rules.
+pub fn is_category(name: &str) -> bool {
+    matches!(name, "all" | "read" | "write" | "admin" | "pubsub" | "fast" | "slow")
+        || CATEGORY_NAMES.iter().any(|(_, n)| n.eq_ignore_ascii_case(name))
+}
+
+/// Spec of `name` (any case).
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    static INDEX: OnceLock<HashMap<String, &'static CommandSpec>> = OnceLock::new();
+    let index = INDEX.get_or_init(|| COMMANDS.iter().map(|c| (c.name.to_uppercase(), c)).collect());
+    match index.get(name) {
+        Some(spec) => Some(spec),
+        None => index.get(&name.to_uppercase()).copied(),
+    }
+}
+
+pub fn all() -> &'static [CommandSpec] {
+    COMMANDS
+}
+
+/// Handled by the connection loop (transactions, pub/sub, MONITOR, HELLO);
+/// only reachable from places that can't switch modes, like EXEC.
+fn connection_only<'a>(_: &'a Dispatcher, _: &'a Client, _: &'a Arc<Db>, frames: &'a [RespFrame]) -> HandlerFuture<'a> {
+    Box::pin(async move {
+        let name = frames[0].as_str().unwrap_or_default().to_lowercase();
+        Err(ZedisError::err(format!("'{}' is not allowed in this context", name)).into())
+    })
+}
+
+const ZEDIS: &str = "0.1.0";
+
+static COMMANDS: &[CommandSpec] = &[
+    // --- Keyspace ---
+    CommandSpec::new("del", -2, WRITE | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_del(db, f)))
+        .keys(1, -1, 1).doc("generic", "1.0.0", "O(N) where N is the number of keys", "Deletes one or more keys."),
+    CommandSpec::new("unlink", -2, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_unlink(db, f)))
+        .keys(1, -1, 1).doc("generic", "4.0.0", "O(1) for each key removed", "Deletes one or more keys, freeing large values in the background."),
+    CommandSpec::new("exists", -2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_exists(db, f)))
+        .keys(1, -1, 1).doc("generic", "1.0.0", "O(N) where N is the number of keys", "Determines whether one or more keys exist."),
+    CommandSpec::new("ttl", 2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_ttl(db, "TTL", f)))
+        .keys(1, 1, 1).doc("generic", "1.0.0", "O(1)", "Returns the expiration time in seconds of a key."),
+    CommandSpec::new("pttl", 2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_ttl(db, "PTTL", f)))
+        .keys(1, 1, 1).doc("generic", "2.6.0", "O(1)", "Returns the expiration time in milliseconds of a key."),
+    CommandSpec::new("expiretime", 2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_expiretime(db, "EXPIRETIME", f)))
+        .keys(1, 1, 1).doc("generic", "7.0.0", "O(1)", "Returns the expiration time of a key as a Unix timestamp."),
+    CommandSpec::new("pexpiretime", 2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_expiretime(db, "PEXPIRETIME", f)))
+        .keys(1, 1, 1).doc("generic", "7.0.0", "O(1)", "Returns the expiration time of a key as a Unix milliseconds timestamp."),
+    CommandSpec::new("expire", -3, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_expire(db, "EXPIRE", f)))
+        .keys(1, 1, 1).doc("generic", "1.0.0", "O(1)", "Sets the expiration time of a key in seconds."),
+    CommandSpec::new("pexpire", -3, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_expire(db, "PEXPIRE", f)))
+        .keys(1, 1, 1).doc("generic", "2.6.0", "O(1)", "Sets the expiration time of a key in milliseconds."),
+    CommandSpec::new("expireat", -3, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_expire(db, "EXPIREAT", f)))
+        .keys(1, 1, 1).doc("generic", "1.2.0", "O(1)", "Sets the expiration time of a key to a Unix timestamp."),
+    CommandSpec::new("pexpireat", -3, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_expire(db, "PEXPIREAT", f)))
+        .keys(1, 1, 1).doc("generic", "2.6.0", "O(1)", "Sets the expiration time of a key to a Unix milliseconds timestamp."),
+    CommandSpec::new("persist", 2, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_persist(db, f)))
+        .keys(1, 1, 1).doc("generic", "2.2.0", "O(1)", "Removes the expiration time of a key."),
+    CommandSpec::new("scan", -2, READONLY, KEYSPACE, |d, _, db, f| Box::pin(d.handle_scan(db, f)))
+        .doc("generic", "2.8.0", "O(1) for every call. O(N) for a complete iteration", "Iterates over the key names in the database."),
+    CommandSpec::new("keys", 2, READONLY, KEYSPACE | DANGEROUS, |d, _, db, f| Box::pin(d.handle_keys(db, f)))
+        .doc("generic", "1.0.0", "O(N) with N being the number of keys in the database", "Returns all key names that match a pattern."),
+    CommandSpec::new("type", 2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_type(db, f)))
+        .keys(1, 1, 1).doc("generic", "1.0.0", "O(1)", "Determines the type of value stored at a key."),
+    CommandSpec::new("randomkey", 1, READONLY, KEYSPACE, |_, _, db, _| Box::pin(async move { Ok(RespFrame::BulkString(db.random_key())) }))
+        .doc("generic", "1.0.0", "O(1)", "Returns a random key name from the database."),
+    CommandSpec::new("dbsize", 1, READONLY | FAST, KEYSPACE, |_, _, db, _| Box::pin(async move { Ok(RespFrame::Integer(db.len() as i64)) }))
+        .doc("server", "1.0.0", "O(1)", "Returns the number of keys in the database."),
+    CommandSpec::new("rename", 3, WRITE | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_rename(db, "RENAME", f)))
+        .keys(1, 2, 1).doc("generic", "1.0.0", "O(1)", "Renames a key and overwrites the destination."),
+    CommandSpec::new("renamenx", 3, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_rename(db, "RENAMENX", f)))
+        .keys(1, 2, 1).doc("generic", "1.0.0", "O(1)", "Renames a key only when the target key name doesn't exist."),
+    CommandSpec::new("copy", -3, WRITE | DENYOOM | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_copy(db, f)))
+        .keys(1, 2, 1).doc("generic", "6.2.0", "O(N) worst case for collections, where N is the number of nested items", "Copies the value of a key to a new key."),
+    CommandSpec::new("touch", -2, READONLY | FAST, KEYSPACE, |d, _, db, f| Box::pin(d.handle_touch(db, f)))
+        .keys(1, -1, 1).doc("generic", "3.2.1", "O(N) where N is the number of keys that will be touched", "Returns the number of existing keys out of those specified after updating the time they were last accessed."),
+    CommandSpec::new("dump", 2, READONLY, KEYSPACE, |d, _, db, f| Box::pin(d.handle_dump(db, f)))
+        .keys(1, 1, 1).doc("generic", "2.6.0", "O(1) to access the key and additional O(N*M) to serialize it", "Returns a serialized representation of the value stored at a key."),
+    CommandSpec::new("restore", -4, WRITE | DENYOOM | OWN_AOF, KEYSPACE | DANGEROUS, |d, _, db, f| Box::pin(d.handle_restore(db, f)))
+        .keys(1, 1, 1).doc("generic", "2.6.0", "O(1) to create the new key and additional O(N*M) to reconstruct the serialized value", "Creates a key from the serialized representation of a value."),
+    CommandSpec::new("move", 3, WRITE | FAST | OWN_AOF, KEYSPACE, |d, _, db, f| Box::pin(d.handle_move(db, f)))
+        .keys(1, 1, 1).doc("generic", "1.0.0", "O(1)", "Moves a key to another database."),
+    CommandSpec::new("select", 2, FAST, CONNECTION, |d, c, _, f| Box::pin(d.handle_select(c, f)))
+        .doc("connection", "1.0.0", "O(1)", "Changes the selected database."),
+    CommandSpec::new("swapdb", 3, WRITE | FAST | OWN_AOF, KEYSPACE | DANGEROUS, |d, _, _, f| Box::pin(d.handle_swapdb(f)))
+        .doc("server", "4.0.0", "O(N) where N is the count of clients watching or blocking on keys from both databases", "Swaps two databases."),
+    CommandSpec::new("flushdb", -1, WRITE | OWN_AOF, KEYSPACE | DANGEROUS, |d, _, db, f| Box::pin(d.handle_flush(db, "FLUSHDB", f)))
+        .doc("server", "1.0.0", "O(N) where N is the number of keys in the selected database", "Removes all keys from the current database."),
+    CommandSpec::new("flushall", -1, WRITE | OWN_AOF, KEYSPACE | DANGEROUS, |d, _, db, f| Box::pin(d.handle_flush(db, "FLUSHALL", f)))
+        .doc("server", "1.0.0", "O(N) where N is the total number of keys in all databases", "Removes all keys from all databases."),
+    CommandSpec::new("memory", -2, READONLY, 0, |d, _, db, f| Box::pin(d.handle_memory(db, f)))
+        .doc("server", "4.0.0", "O(N) where N is the number of samples", "MEMORY USAGE: estimates the memory usage of a key."),
+
+    // --- Strings ---
+    CommandSpec::new("get", 2, READONLY | FAST, STRING, |d, _, db, f| Box::pin(d.handle_get(db, f)))
+        .keys(1, 1, 1).doc("string", "1.0.0", "O(1)", "Returns the string value of a key."),
+    CommandSpec::new("set", -3, WRITE | DENYOOM | OWN_AOF, STRING, |d, _, db, f| Box::pin(d.handle_set(db, f)))
+        .keys(1, 1, 1).doc("string", "1.0.0", "O(1)", "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist."),
+    CommandSpec::new("setex", 4, WRITE | DENYOOM | OWN_AOF, STRING, |d, _, db, f| Box::pin(d.handle_setex(db, f)))
+        .keys(1, 1, 1).doc("string", "2.0.0", "O(1)", "Sets the string value and expiration time of a key."),
+    CommandSpec::new("incr", 2, WRITE | DENYOOM | FAST | OWN_AOF, STRING, |d, _, db, f| Box::pin(d.handle_incr(db, f)))
+        .keys(1, 1, 1).doc("string", "1.0.0", "O(1)", "Increments the integer value of a key by one."),
+    CommandSpec::new("incrby", 3, WRITE | DENYOOM | FAST | OWN_AOF, STRING, |d, _, db, f| Box::pin(d.handle_incrby(db, f)))
+        .keys(1, 1, 1).doc("string", "1.0.0", "O(1)", "Increments the integer value of a key by a number."),
+
+    // --- Bitmaps ---
+    CommandSpec::new("bitcount", 2, READONLY, BITMAP, |d, _, db, f| Box::pin(d.handle_bitcount(db, f)))
+        .keys(1, 1, 1).doc("bitmap", "2.6.0", "O(N)", "Counts the number of set bits (population counting) in a string."),
+    CommandSpec::new("bitfield", -2, WRITE | DENYOOM, BITMAP, |d, _, db, f| Box::pin(d.handle_bitfield(db, f)))
+        .keys(1, 1, 1).doc("bitmap", "3.2.0", "O(1) for each subcommand specified", "Performs arbitrary bitfield integer operations on strings."),
+
+    // --- Lists ---
+    CommandSpec::new("rpush", -3, WRITE | DENYOOM | FAST, LIST, |d, _, db, f| Box::pin(d.handle_rpush(db, f)))
+        .keys(1, 1, 1).doc("list", "1.0.0", "O(1) for each element added", "Appends one or more elements to a list. Creates the key if it doesn't exist."),
+    CommandSpec::new("lpop", 2, WRITE | FAST, LIST, |d, _, db, f| Box::pin(d.handle_lpop(db, f)))
+        .keys(1, 1, 1).doc("list", "1.0.0", "O(1)", "Returns the first element of a list after removing it. Deletes the list if the last element was popped."),
+    CommandSpec::new("lrange", 4, READONLY, LIST, |d, _, db, f| Box::pin(d.handle_lrange(db, f)))
+        .keys(1, 1, 1).doc("list", "1.0.0", "O(S+N)", "Returns a range of elements from a list."),
+
+    // --- Hashes ---
+    CommandSpec::new("hset", -4, WRITE | DENYOOM | FAST, HASH, |d, _, db, f| Box::pin(d.handle_hset(db, f)))
+        .keys(1, 1, 1).repeat(2, 2).doc("hash", "2.0.0", "O(1) for each field/value pair added", "Creates or modifies the value of a field in a hash."),
+    CommandSpec::new("hget", 3, READONLY | FAST, HASH, |d, _, db, f| Box::pin(d.handle_hget(db, f)))
+        .keys(1, 1, 1).doc("hash", "2.0.0", "O(1)", "Returns the value of a field in a hash."),
+    CommandSpec::new("hgetall", 2, READONLY, HASH, |d, _, db, f| Box::pin(d.handle_hgetall(db, f)))
+        .keys(1, 1, 1).doc("hash", "2.0.0", "O(N) where N is the size of the hash", "Returns all fields and values in a hash."),
+
+    // --- Sets ---
+    CommandSpec::new("sadd", -3, WRITE | DENYOOM | FAST, SET, |d, _, db, f| Box::pin(d.handle_sadd(db, f)))
+        .keys(1, 1, 1).doc("set", "1.0.0", "O(1) for each element added", "Adds one or more members to a set. Creates the key if it doesn't exist."),
+    CommandSpec::new("smembers", 2, READONLY, SET, |d, _, db, f| Box::pin(d.handle_smembers(db, f)))
+        .keys(1, 1, 1).doc("set", "1.0.0", "O(N) where N is the set cardinality", "Returns all members of a set."),
+
+    // --- Sorted sets ---
+    CommandSpec::new("zadd", -4, WRITE | DENYOOM | FAST, SORTEDSET, |d, _, db, f| Box::pin(d.handle_zadd(db, f)))
+        .keys(1, 1, 1).repeat(2, 2).doc("sorted-set", "1.2.0", "O(log(N)) for each item added", "Adds one or more members to a sorted set, or updates their scores."),
+    CommandSpec::new("zrange", -4, READONLY, SORTEDSET, |d, c, db, f| Box::pin(d.handle_zrange(c, db, f)))
+        .keys(1, 1, 1).doc("sorted-set", "1.2.0", "O(log(N)+M)", "Returns members in a sorted set within a range of indexes."),
+
+    // --- Geo / streams / HyperLogLog ---
+    CommandSpec::new("geoadd", -5, WRITE | DENYOOM, GEO, |d, _, db, f| Box::pin(d.handle_geoadd(db, f)))
+        .keys(1, 1, 1).repeat(2, 3).doc("geo", "3.2.0", "O(log(N)) for each item added", "Adds one or more members to a geospatial index."),
+    CommandSpec::new("xadd", -5, WRITE | DENYOOM | FAST | OWN_AOF, STREAM, |d, _, db, f| Box::pin(d.handle_xadd(db, f)))
+        .keys(1, 1, 1).repeat(3, 2).doc("stream", "5.0.0", "O(1)", "Appends a new message to a stream. Creates the key if it doesn't exist."),
+    CommandSpec::new("xrange", 4, READONLY, STREAM, |d, _, db, f| Box::pin(d.handle_xrange(db, f)))
+        .keys(1, 1, 1).doc("stream", "5.0.0", "O(N) with N being the number of elements being returned", "Returns the messages from a stream within a range of IDs."),
+    CommandSpec::new("pfadd", -2, WRITE | DENYOOM | FAST, HYPERLOGLOG, |d, _, db, f| Box::pin(d.handle_pfadd(db, f)))
+        .keys(1, 1, 1).doc("hyperloglog", "2.8.9", "O(1) to add every element", "Adds elements to a HyperLogLog key. Creates the key if it doesn't exist."),
+    CommandSpec::new("pfcount", 2, READONLY, HYPERLOGLOG, |d, _, db, f| Box::pin(d.handle_pfcount(db, f)))
+        .keys(1, 1, 1).doc("hyperloglog", "2.8.9", "O(1)", "Returns the approximated cardinality of the set observed by the HyperLogLog at a key."),
+
+    // --- Vectors (Zedis) ---
+    CommandSpec::new("vadd", -3, WRITE | DENYOOM, VECTOR, |d, _, db, f| Box::pin(d.handle_vadd(db, f)))
+        .keys(1, 1, 1).doc("vector", ZEDIS, "O(D) where D is the vector dimension", "Stores a float vector at a key."),
+    CommandSpec::new("vadd.text", 3, WRITE | DENYOOM, VECTOR, |d, _, db, f| Box::pin(d.handle_vadd_text(db, f)))
+        .keys(1, 1, 1).doc("vector", ZEDIS, "O(T) BGE-M3 inference over T tokens", "Embeds a text with BGE-M3 and stores its hybrid vector at a key."),
+    CommandSpec::new("vadd.m3", 3, WRITE | DENYOOM, VECTOR, |d, _, db, f| Box::pin(d.handle_vadd_m3(db, f)))
+        .keys(1, 1, 1).doc("vector", ZEDIS, "O(T) BGE-M3 inference over T tokens", "Embeds a text with BGE-M3 and stores its dense and sparse vectors at a key."),
+    CommandSpec::new("vsearch", -4, READONLY, VECTOR, |d, _, db, f| Box::pin(d.handle_vsearch(db, f)))
+        .keys(1, 1, 1).doc("vector", ZEDIS, "O(N*D) brute force over N vectors", "Returns the K vectors nearest to a query vector."),
+    CommandSpec::new("vsearch.text", 4, READONLY, VECTOR, |d, _, db, f| Box::pin(d.handle_vsearch_text(db, f)))
+        .keys(1, 1, 1).doc("vector", ZEDIS, "O(T) inference plus O(N*D) search", "Embeds a query text and returns the K nearest documents."),
+    CommandSpec::new("vsearch.hybrid", -4, READONLY, VECTOR, |d, _, db, f| Box::pin(d.handle_vsearch_hybrid(db, f)))
+        .keys(1, 1, 1).doc("vector", ZEDIS, "O(T) inference plus O(N*D) search", "Hybrid dense and sparse search, weighted by alpha."),
+
+    // --- JSON / time series / graph / ML (Zedis) ---
+    CommandSpec::new("json.set", 3, WRITE | DENYOOM, JSON, |d, _, db, f| Box::pin(d.handle_jsonset(db, f)))
+        .keys(1, 1, 1).doc("json", ZEDIS, "O(N) where N is the size of the document", "Stores a JSON document at a key."),
+    CommandSpec::new("json.get", 3, READONLY, JSON, |d, _, db, f| Box::pin(d.handle_jsonget(db, f)))
+        .keys(1, 1, 1).doc("json", ZEDIS, "O(N) where N is the size of the value at the path", "Returns the value at a path of a JSON document."),
+    CommandSpec::new("ts.add", 4, WRITE | DENYOOM, TIMESERIES, |d, _, db, f| Box::pin(d.handle_tsadd(db, f)))
+        .keys(1, 1, 1).doc("timeseries", ZEDIS, "O(1)", "Appends a sample to a time series."),
+    CommandSpec::new("ts.range", 4, READONLY, TIMESERIES, |d, _, db, f| Box::pin(d.handle_tsrange(db, f)))
+        .keys(1, 1, 1).doc("timeseries", ZEDIS, "O(N) where N is the number of samples returned", "Returns the samples of a time series within a time range."),
+    CommandSpec::new("graph.add", 4, WRITE | DENYOOM, GRAPH, |d, _, db, f| Box::pin(d.handle_graphadd(db, f)))
+        .keys(1, 1, 1).doc("graph", ZEDIS, "O(1)", "Adds an edge to a graph."),
+    CommandSpec::new("graph.bfs", 4, READONLY, GRAPH, |d, _, db, f| Box::pin(d.handle_graphbfs(db, f)))
+        .keys(1, 1, 1).doc("graph", ZEDIS, "O(V+E) within the depth limit", "Breadth-first traversal of a graph from a node."),
+    CommandSpec::new("ml.load", 3, WRITE | DENYOOM, ML, |d, _, db, f| Box::pin(d.handle_mlload(db, f)))
+        .keys(1, 1, 1).doc("ml", ZEDIS, "O(M) where M is the model size", "Loads a model and stores it at a key."),
+    CommandSpec::new("ml.run", -3, READONLY, ML, |d, _, db, f| Box::pin(d.handle_mlrun(db, f)))
+        .keys(1, 1, 1).doc("ml", ZEDIS, "Depends on the model", "Runs the model stored at a key on an input vector."),
+
+    // --- Probabilistic (Zedis) ---
+    CommandSpec::new("bf.add", 3, WRITE | DENYOOM, BLOOM, |d, _, db, f| Box::pin(d.handle_bfadd(db, f)))
+        .keys(1, 1, 1).doc("bloom", ZEDIS, "O(K) where K is the number of hash functions", "Adds an item to a Bloom filter."),
+    CommandSpec::new("bf.exists", 3, READONLY, BLOOM, |d, _, db, f| Box::pin(d.handle_bfexists(db, f)))
+        .keys(1, 1, 1).doc("bloom", ZEDIS, "O(K) where K is the number of hash functions", "Checks whether an item may exist in a Bloom filter."),
+    CommandSpec::new("cf.add", 3, WRITE | DENYOOM, CUCKOO, |d, _, db, f| Box::pin(d.handle_cfadd(db, f)))
+        .keys(1, 1, 1).doc("cuckoo", ZEDIS, "O(1)", "Adds an item to a Cuckoo filter."),
+    CommandSpec::new("cf.exists", 3, READONLY, CUCKOO, |d, _, db, f| Box::pin(d.handle_cfexists(db, f)))
+        .keys(1, 1, 1).doc("cuckoo", ZEDIS, "O(1)", "Checks whether an item may exist in a Cuckoo filter."),
+    CommandSpec::new("cms.incrby", 4, WRITE | DENYOOM, CMS, |d, _, db, f| Box::pin(d.handle_cms_incr(db, f)))
+        .keys(1, 1, 1).doc("cms", ZEDIS, "O(D) where D is the sketch depth", "Increases the count of an item in a Count-Min Sketch."),
+    CommandSpec::new("cms.query", 3, READONLY, CMS, |d, _, db, f| Box::pin(d.handle_cms_query(db, f)))
+        .keys(1, 1, 1).doc("cms", ZEDIS, "O(D) where D is the sketch depth", "Returns the estimated count of an item in a Count-Min Sketch."),
+    CommandSpec::new("topk.add", -3, WRITE | DENYOOM, TOPK, |d, _, db, f| Box::pin(d.handle_topk_add(db, f)))
+        .keys(1, 1, 1).doc("topk", ZEDIS, "O(N*K) where N is the number of items", "Adds items to a Top-K sketch."),
+    CommandSpec::new("topk.list", 2, READONLY, TOPK, |d, _, db, f| Box::pin(d.handle_topk_list(db, f)))
+        .keys(1, 1, 1).doc("topk", ZEDIS, "O(K)", "Returns the items of a Top-K sketch."),
+    CommandSpec::new("tdigest.add", 3, WRITE | DENYOOM, TDIGEST, |d, _, db, f| Box::pin(d.handle_tdigest_add(db, f)))
+        .keys(1, 1, 1).doc("tdigest", ZEDIS, "O(1)", "Adds an observation to a t-digest sketch."),
+    CommandSpec::new("tdigest.quantile", 3, READONLY, TDIGEST, |d, _, db, f| Box::pin(d.handle_tdigest_quantile(db, f)))
+        .keys(1, 1, 1).doc("tdigest", ZEDIS, "O(C) where C is the number of centroids", "Returns an estimation of the value at a quantile."),
+
+    // --- Scripting / pub/sub / transactions ---
+    // Lua's redis.call logs its own writes to the AOF
+    CommandSpec::new("eval", -3, NOSCRIPT | DENYOOM | MOVABLEKEYS | MAY_REPLICATE | OWN_AOF, SCRIPTING, |d, _, db, f| Box::pin(d.handle_eval(db, f)))
+        .doc("scripting", "2.6.0", "Depends on the script that is executed", "Executes a server-side Lua script."),
+    CommandSpec::new("publish", 3, PUBSUB | FAST | MAY_REPLICATE, 0, |d, _, _, f| Box::pin(d.handle_publish(f)))
+        .doc("pubsub", "2.0.0", "O(N+M) where N is the number of clients subscribed to the receiving channel", "Posts a message to a channel."),
+    CommandSpec::new("subscribe", -2, PUBSUB | NOSCRIPT, 0, connection_only)
+        .doc("pubsub", "2.0.0", "O(N) where N is the number of channels to subscribe to", "Listens for messages published to channels."),
+    CommandSpec::new("psubscribe", -2, PUBSUB | NOSCRIPT, 0, connection_only)
+        .doc("pubsub", "2.0.0", "O(N) where N is the number of patterns to subscribe to", "Listens for messages published to channels that match one or more patterns."),
+    CommandSpec::new("unsubscribe", -1, PUBSUB | NOSCRIPT, 0, connection_only)
+        .doc("pubsub", "2.0.0", "O(N) where N is the number of channels to unsubscribe", "Stops listening to messages posted to channels."),
+    CommandSpec::new("punsubscribe", -1, PUBSUB | NOSCRIPT, 0, connection_only)
+        .doc("pubsub", "2.0.0", "O(N) where N is the number of patterns to unsubscribe", "Stops listening to messages published to channels that match one or more patterns."),
+    CommandSpec::new("multi", 1, NOSCRIPT | FAST, TRANSACTION, connection_only)
+        .doc("transactions", "1.2.0", "O(1)", "Starts a transaction."),
+    CommandSpec::new("exec", 1, NOSCRIPT, TRANSACTION, connection_only)
+        .doc("transactions", "1.2.0", "Depends on commands in the transaction", "Executes all commands in a transaction."),
+    CommandSpec::new("discard", 1, NOSCRIPT | FAST, TRANSACTION, connection_only)
+        .doc("transactions", "2.0.0", "O(N), when N is the number of queued commands", "Discards a transaction."),
+
+    // --- Connection / server ---
+    CommandSpec::new("ping", -1, FAST, CONNECTION, |d, _, _, f| Box::pin(d.handle_ping(f)))
+        .doc("connection", "1.0.0", "O(1)", "Returns the server's liveliness response."),
+    CommandSpec::new("hello", -1, NOSCRIPT | FAST, CONNECTION, connection_only)
+        .doc("connection", "6.0.0", "O(1)", "Handshakes with the server."),
+    CommandSpec::new("client", -2, NOSCRIPT, CONNECTION, |d, c, _, f| Box::pin(d.handle_client(c, f)))
+        .doc("connection", "2.4.0", "Depends on subcommand", "CLIENT LIST, KILL, PAUSE, REPLY, SETNAME and friends."),
+    CommandSpec::new("command", -1, 0, CONNECTION, |d, _, _, f| Box::pin(d.handle_command(f)))
+        .doc("server", "2.8.13", "O(N) where N is the total number of commands", "Returns detailed information about all commands."),
+    CommandSpec::new("info", -1, 0, DANGEROUS, |d, _, _, f| Box::pin(d.handle_info(f)))
+        .doc("server", "1.0.0", "O(1)", "Returns information and statistics about the server."),
+    CommandSpec::new("config", -2, ADMIN | NOSCRIPT, 0, |d, _, _, f| Box::pin(d.handle_config(f)))
+        .doc("server", "2.0.0", "Depends on subcommand", "CONFIG GET, SET, REWRITE and RESETSTAT."),
+    CommandSpec::new("slowlog", -2, ADMIN, 0, |d, _, _, f| Box::pin(d.handle_slowlog(f)))
+        .doc("server", "2.2.12", "Depends on subcommand", "SLOWLOG GET, LEN and RESET."),
+    CommandSpec::new("latency", -2, ADMIN | NOSCRIPT, 0, |d, _, _, f| Box::pin(d.handle_latency(f)))
+        .doc("server", "2.8.13", "Depends on subcommand", "LATENCY LATEST, HISTORY, RESET and DOCTOR."),
+    CommandSpec::new("monitor", 1, ADMIN | NOSCRIPT, 0, connection_only)
+        .doc("server", "1.0.0", "O(1)", "Listens for all requests received by the server in real-time."),
+    CommandSpec::new("save", 1, ADMIN | NOSCRIPT, 0, |d, _, _, _| Box::pin(d.handle_save()))
+        .doc("server", "1.0.0", "O(N) where N is the total number of keys in all databases", "Synchronously saves the database(s) to disk."),
+    CommandSpec::new("shutdown", -1, ADMIN | NOSCRIPT, 0, |d, _, _, f| Box::pin(d.handle_shutdown(f)))
+        .doc("server", "1.0.0", "O(N) when saving, where N is the total number of keys in all databases", "Synchronously saves the database(s) to disk and shuts down the server."),
+];
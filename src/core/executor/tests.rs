@@ -0,0 +1,425 @@
+//! Dispatcher-level tests: commands run through `execute`, connection-mode
+//! commands through an in-memory `Connection`.
+
+use super::*;
+use crate::config::Config;
+use crate::core::client::{OutputBufferLimit, OutputBufferLimits};
+use crate::core::evict::EvictionPolicy;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// A dispatcher over a fresh keyspace, with the AOF off.
+pub(super) fn dispatcher_with(config: Config) -> Dispatcher {
+    dispatcher_with_evictor(config, Evictor::new(0, EvictionPolicy::NoEviction, 5))
+}
+
+pub(super) fn dispatcher_with_evictor(config: Config, evictor: Evictor) -> Dispatcher {
+    static N: AtomicUsize = AtomicUsize::new(0);
+    let aof_path = std::env::temp_dir().join(format!("zedis-exec-{}-{}.aof", std::process::id(), N.fetch_add(1, Ordering::Relaxed)));
+    let keyspace = Arc::new(Keyspace::new(config.databases));
+    let db = keyspace.db(0).clone();
+    Dispatcher::new(
+        keyspace,
+        Arc::new(AofManager::with_policy(&aof_path.to_string_lossy(), false, config.appendfsync).unwrap()),
+        Arc::new(ConfigStore::new(config)),
+        None,
+        Arc::new(evictor),
+        Subsystems {
+            hw: Arc::new(HardwareManager::new()),
+            flow: Arc::new(FlowManager::new(db, None)),
+            mask: Arc::new(MaskStatus::default()),
+        },
+    )
+}
+
+pub(super) fn dispatcher() -> Dispatcher {
+    dispatcher_with(Config::default())
+}
+
+/// A client as if it had just connected.
+pub(super) fn connect(d: &Dispatcher) -> Arc<Client> {
+    connect_from(d, "127.0.0.1:50000")
+}
+
+pub(super) fn connect_from(d: &Dispatcher, addr: &str) -> Arc<Client> {
+    d.clients.try_register(addr.to_string(), "127.0.0.1:6379".to_string(), usize::MAX).unwrap()
+}
+
+pub(super) async fn exec(d: &Dispatcher, client: &Client, args: &[&[u8]]) -> RespFrame {
+    let frames = args.iter().map(|a| RespFrame::bulk(Bytes::copy_from_slice(a))).collect();
+    d.execute(client, RespFrame::Array(Some(frames))).await.unwrap()
+}
+
+fn error(message: &str) -> RespFrame {
+    RespFrame::Error(message.to_string())
+}
+
+#[tokio::test]
+async fn restore_errors_carry_a_single_err_prefix() {
+    let d = dispatcher();
+    let c = connect(&d);
+    let reply = exec(&d, &c, &[b"RESTORE", b"k", b"0", b"garbage"]).await;
+    assert_eq!(reply, error("ERR DUMP payload version or checksum are wrong"));
+}
+
+#[tokio::test]
+async fn admin_errors_are_typed_replies() {
+    let d = dispatcher();
+    let c = connect(&d);
+    assert_eq!(exec(&d, &c, &[b"CLIENT", b"NOPE"]).await, error("ERR unknown subcommand 'NOPE'. Try CLIENT HELP."));
+    assert_eq!(exec(&d, &c, &[b"CONFIG", b"GET"]).await, error("ERR wrong number of arguments for 'config|get' command"));
+    assert_eq!(exec(&d, &c, &[b"COMMAND", b"LIST", b"FILTERBY", b"NOPE", b"x"]).await, error("ERR syntax error"));
+    assert_eq!(exec(&d, &c, &[b"SLOWLOG", b"LEN", b"extra"]).await, error("ERR wrong number of arguments for 'slowlog' command"));
+    assert_eq!(exec(&d, &c, &[b"CLIENT", b"KILL", b"1.2.3.4:5"]).await, error("ERR No such client"));
+}
+
+#[tokio::test]
+async fn repeated_argument_groups_are_checked_by_the_spec() {
+    let d = dispatcher();
+    let c = connect(&d);
+    assert_eq!(exec(&d, &c, &[b"HSET", b"h", b"f1", b"v1", b"f2"]).await, error("ERR wrong number of arguments for 'hset' command"));
+    assert_eq!(exec(&d, &c, &[b"ZADD", b"z", b"1", b"a", b"2"]).await, error("ERR wrong number of arguments for 'zadd' command"));
+    assert_eq!(exec(&d, &c, &[b"GEOADD", b"g", b"13.3", b"38.1", b"a", b"15"]).await, error("ERR wrong number of arguments for 'geoadd' command"));
+    assert_eq!(exec(&d, &c, &[b"XADD", b"s", b"*", b"f"]).await, error("ERR wrong number of arguments for 'xadd' command"));
+    assert_eq!(exec(&d, &c, &[b"HSET", b"h", b"f1", b"v1", b"f2", b"v2"]).await, RespFrame::Integer(2));
+    assert_eq!(exec(&d, &c, &[b"ZRANGE", b"z", b"0", b"-1", b"WITHSCORES", b"x"]).await, error("ERR syntax error"));
+}
+
+#[tokio::test]
+async fn dump_and_restore_keep_value_and_ttl() {
+    let d = dispatcher();
+    let c = connect(&d);
+    exec(&d, &c, &[b"SET", b"k", b"\xff\x00v", b"PX", b"100000"]).await;
+    let at = match exec(&d, &c, &[b"PEXPIRETIME", b"k"]).await {
+        RespFrame::Integer(at) => at.to_string(),
+        other => panic!("PEXPIRETIME replied {:?}", other),
+    };
+    let payload = match exec(&d, &c, &[b"DUMP", b"k"]).await {
+        RespFrame::BulkString(Some(payload)) => payload,
+        other => panic!("DUMP replied {:?}", other),
+    };
+
+    assert_eq!(exec(&d, &c, &[b"RESTORE", b"copy", at.as_bytes(), &payload, b"ABSTTL"]).await, RespFrame::SimpleString("OK".to_string()));
+    assert_eq!(exec(&d, &c, &[b"GET", b"copy"]).await, RespFrame::bulk(Bytes::from_static(b"\xff\x00v")));
+    assert_eq!(exec(&d, &c, &[b"PEXPIRETIME", b"copy"]).await, RespFrame::Integer(at.parse().unwrap()));
+
+    // A relative TTL of 0 restores a persistent key
+    assert_eq!(exec(&d, &c, &[b"RESTORE", b"forever", b"0", &payload]).await, RespFrame::SimpleString("OK".to_string()));
+    assert_eq!(exec(&d, &c, &[b"PTTL", b"forever"]).await, RespFrame::Integer(-1));
+    assert_eq!(exec(&d, &c, &[b"RESTORE", b"copy", b"0", &payload]).await, error("BUSYKEY Target key name already exists."));
+}
+
+#[tokio::test]
+async fn noeviction_refuses_denyoom_commands_only() {
+    let d = dispatcher_with_evictor(Config::default(), Evictor::new(1, EvictionPolicy::NoEviction, 5));
+    let c = connect(&d);
+    let oom = error("OOM command not allowed when used memory > 'maxmemory'.");
+    assert_eq!(exec(&d, &c, &[b"SET", b"k", b"v"]).await, oom);
+    assert_eq!(exec(&d, &c, &[b"RPUSH", b"l", b"v"]).await, oom);
+    assert_eq!(exec(&d, &c, &[b"GET", b"k"]).await, RespFrame::BulkString(None));
+    assert_eq!(exec(&d, &c, &[b"DEL", b"k"]).await, RespFrame::Integer(0));
+}
+
+fn client_list(reply: RespFrame) -> Vec<String> {
+    match reply {
+        RespFrame::BulkString(Some(text)) => String::from_utf8_lossy(&text).lines().map(str::to_string).collect(),
+        other => panic!("CLIENT LIST replied {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn client_setname_list_and_kill() {
+    let d = dispatcher();
+    let me = connect_from(&d, "127.0.0.1:50001");
+    let other = connect_from(&d, "127.0.0.1:50002");
+    let ok = RespFrame::SimpleString("OK".to_string());
+
+    assert_eq!(exec(&d, &me, &[b"CLIENT", b"SETNAME", b"worker"]).await, ok);
+    assert_eq!(exec(&d, &me, &[b"CLIENT", b"GETNAME"]).await, RespFrame::bulk("worker"));
+    assert_eq!(exec(&d, &me, &[b"CLIENT", b"SETNAME", b"two words"]).await,
+        error("ERR Client names cannot contain spaces, newlines or special characters."));
+
+    let list = client_list(exec(&d, &me, &[b"CLIENT", b"LIST"]).await);
+    assert_eq!(list.len(), 2);
+    let mine = list.iter().find(|l| l.starts_with(&format!("id={} ", me.id))).unwrap();
+    assert!(mine.contains(" addr=127.0.0.1:50001 ") && mine.contains(" name=worker "), "{}", mine);
+    let id = other.id.to_string();
+    let only = client_list(exec(&d, &me, &[b"CLIENT", b"LIST", b"ID", id.as_bytes()]).await);
+    assert_eq!(only.len(), 1);
+    assert!(only[0].contains(" addr=127.0.0.1:50002 "), "{}", only[0]);
+
+    // SKIPME defaults to yes: a filter matching only the caller kills nothing
+    assert_eq!(exec(&d, &me, &[b"CLIENT", b"KILL", b"ADDR", b"127.0.0.1:50001"]).await, RespFrame::Integer(0));
+    assert!(!me.is_killed());
+    assert_eq!(exec(&d, &me, &[b"CLIENT", b"KILL", b"ID", id.as_bytes()]).await, RespFrame::Integer(1));
+    assert!(other.is_killed());
+    let legacy = connect_from(&d, "127.0.0.1:50003");
+    assert_eq!(exec(&d, &me, &[b"CLIENT", b"KILL", b"127.0.0.1:50003"]).await, ok);
+    assert!(legacy.is_killed());
+}
+
+/// `client` subscribed to `channel` over an in-memory connection with a
+/// 1KB pipe. Returns the reader's end and the subscriber's session task;
+/// messages published once this returns are delivered.
+async fn subscriber(d: &Arc<Dispatcher>, client: &Arc<Client>, channel: &'static str) -> (tokio::io::DuplexStream, tokio::task::JoinHandle<()>) {
+    use tokio::io::AsyncReadExt;
+    let (mut reader, server) = tokio::io::duplex(1024);
+    let (d2, client) = (d.clone(), client.clone());
+    let task = tokio::spawn(async move {
+        let mut conn = crate::io::connection::Connection::new(server, crate::core::protocol::ProtocolLimits::default());
+        let frames = [RespFrame::bulk("SUBSCRIBE"), RespFrame::bulk(channel)];
+        d2.handle_subscribe(&client, &frames, &mut conn).await.unwrap();
+    });
+    let confirmation = format!("*3\r\n$9\r\nsubscribe\r\n${}\r\n{}\r\n:1\r\n", channel.len(), channel);
+    let mut buf = vec![0; confirmation.len()];
+    reader.read_exact(&mut buf).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), confirmation);
+    (reader, task)
+}
+
+fn output_limit_disconnections(d: &Dispatcher) -> u64 {
+    d.stats.client_output_buffer_limit_disconnections.load(Ordering::Relaxed)
+}
+
+#[tokio::test]
+async fn stalled_subscriber_is_dropped_at_the_hard_limit() {
+    let d = Arc::new(dispatcher());
+    d.clients.set_output_limits(OutputBufferLimits { pubsub: OutputBufferLimit::new(8 * 1024, 0, 0), ..OutputBufferLimits::default() });
+    let (publisher, sub) = (connect_from(&d, "127.0.0.1:50001"), connect_from(&d, "127.0.0.1:50002"));
+    let (_reader, task) = subscriber(&d, &sub, "news").await;
+
+    let payload = vec![b'x'; 1024];
+    for _ in 0..64 {
+        if task.is_finished() {
+            break;
+        }
+        exec(&d, &publisher, &[b"PUBLISH", b"news", &payload]).await;
+        tokio::task::yield_now().await;
+    }
+    tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+    assert_eq!(output_limit_disconnections(&d), 1);
+}
+
+#[tokio::test]
+async fn subscriber_over_the_soft_limit_is_dropped_after_its_window() {
+    let d = Arc::new(dispatcher());
+    d.clients.set_output_limits(OutputBufferLimits { pubsub: OutputBufferLimit::new(0, 4 * 1024, 1), ..OutputBufferLimits::default() });
+    let (publisher, sub) = (connect_from(&d, "127.0.0.1:50001"), connect_from(&d, "127.0.0.1:50002"));
+    let (_reader, task) = subscriber(&d, &sub, "news").await;
+
+    // A short burst, then silence: the window alone must end the stall
+    let started = Instant::now();
+    let payload = vec![b'x'; 1024];
+    for _ in 0..8 {
+        exec(&d, &publisher, &[b"PUBLISH", b"news", &payload]).await;
+        tokio::task::yield_now().await;
+    }
+    tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(900), "dropped after {:?}", started.elapsed());
+    assert_eq!(output_limit_disconnections(&d), 1);
+}
+
+#[tokio::test]
+async fn subscriber_that_catches_up_is_kept() {
+    use tokio::io::AsyncReadExt;
+    let d = Arc::new(dispatcher());
+    d.clients.set_output_limits(OutputBufferLimits { pubsub: OutputBufferLimit::new(0, 4 * 1024, 1), ..OutputBufferLimits::default() });
+    let (publisher, sub) = (connect_from(&d, "127.0.0.1:50001"), connect_from(&d, "127.0.0.1:50002"));
+    let (mut reader, task) = subscriber(&d, &sub, "news").await;
+
+    let payload = vec![b'x'; 1024];
+    for _ in 0..8 {
+        exec(&d, &publisher, &[b"PUBLISH", b"news", &payload]).await;
+        tokio::task::yield_now().await;
+    }
+    // Drain everything well inside the window
+    let message = format!("*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$1024\r\n{}\r\n", "x".repeat(1024));
+    let mut buf = vec![0; 8 * message.len()];
+    tokio::time::timeout(Duration::from_millis(500), reader.read_exact(&mut buf)).await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    assert!(!task.is_finished());
+    assert_eq!(output_limit_disconnections(&d), 0);
+    sub.kill();
+    tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn slowlog_get_len_and_reset() {
+    let d = dispatcher_with(Config { slowlog_log_slower_than: 0, slowlog_max_len: 2, ..Config::default() });
+    let c = connect(&d);
+    exec(&d, &c, &[b"SET", b"a", b"1"]).await;
+    exec(&d, &c, &[b"GET", b"a"]).await;
+    // Each SLOWLOG call is logged after it replies
+    assert_eq!(exec(&d, &c, &[b"SLOWLOG", b"LEN"]).await, RespFrame::Integer(2));
+    let entries = match exec(&d, &c, &[b"SLOWLOG", b"GET", b"-1"]).await {
+        RespFrame::Array(Some(entries)) => entries,
+        other => panic!("SLOWLOG GET replied {:?}", other),
+    };
+    let summary: Vec<(RespFrame, RespFrame, RespFrame)> = entries.into_iter().map(|e| match e {
+        RespFrame::Array(Some(fields)) => (fields[0].clone(), fields[3].clone(), fields[4].clone()),
+        other => panic!("entry {:?}", other),
+    }).collect();
+    let args = |a: &[&'static str]| RespFrame::Array(Some(a.iter().map(|s| RespFrame::bulk(*s)).collect()));
+    assert_eq!(summary, vec![
+        (RespFrame::Integer(2), args(&["SLOWLOG", "LEN"]), RespFrame::bulk("127.0.0.1:50000")),
+        (RespFrame::Integer(1), args(&["GET", "a"]), RespFrame::bulk("127.0.0.1:50000")),
+    ]);
+    assert_eq!(exec(&d, &c, &[b"SLOWLOG", b"RESET"]).await, RespFrame::SimpleString("OK".to_string()));
+    // Only the RESET itself
+    assert_eq!(exec(&d, &c, &[b"SLOWLOG", b"LEN"]).await, RespFrame::Integer(1));
+
+    // The default 10ms threshold keeps quick commands out
+    let d = dispatcher();
+    let c = connect(&d);
+    exec(&d, &c, &[b"SET", b"a", b"1"]).await;
+    assert_eq!(exec(&d, &c, &[b"SLOWLOG", b"LEN"]).await, RespFrame::Integer(0));
+}
+
+#[tokio::test]
+async fn latency_history_and_reset() {
+    let d = dispatcher();
+    let c = connect(&d);
+    // Events are global; this one is used nowhere else
+    latency::set_threshold(100);
+    latency::add_sample("test-history", Duration::from_millis(200));
+    let at = latency::history("test-history")[0].0 as i64;
+    let sample = RespFrame::Array(Some(vec![RespFrame::Integer(at), RespFrame::Integer(200)]));
+    assert_eq!(exec(&d, &c, &[b"LATENCY", b"HISTORY", b"TEST-HISTORY"]).await, RespFrame::Array(Some(vec![sample])));
+    match exec(&d, &c, &[b"LATENCY", b"LATEST"]).await {
+        RespFrame::Array(Some(events)) => assert!(events.contains(&RespFrame::Array(Some(vec![
+            RespFrame::bulk("test-history"), RespFrame::Integer(at), RespFrame::Integer(200), RespFrame::Integer(200),
+        ])))),
+        other => panic!("LATENCY LATEST replied {:?}", other),
+    }
+    assert!(matches!(exec(&d, &c, &[b"LATENCY", b"DOCTOR"]).await, RespFrame::VerbatimString(..)));
+    assert_eq!(exec(&d, &c, &[b"LATENCY", b"RESET", b"test-history"]).await, RespFrame::Integer(1));
+    assert_eq!(exec(&d, &c, &[b"LATENCY", b"HISTORY", b"test-history"]).await, RespFrame::Array(Some(vec![])));
+}
+
+/// Run MONITOR for `client` over an in-memory connection. Returns the
+/// reader's end and the session task, which yields the frame that ended it.
+fn monitor(d: &Arc<Dispatcher>, client: &Arc<Client>) -> (tokio::io::DuplexStream, tokio::task::JoinHandle<Option<RespFrame>>) {
+    let (reader, server) = tokio::io::duplex(64 * 1024);
+    let (d, client) = (d.clone(), client.clone());
+    let task = tokio::spawn(async move {
+        let mut conn = crate::io::connection::Connection::new(server, crate::core::protocol::ProtocolLimits::default());
+        d.handle_monitor(&client, &mut conn).await.unwrap()
+    });
+    (reader, task)
+}
+
+async fn read_line(reader: &mut tokio::io::DuplexStream) -> String {
+    use tokio::io::AsyncReadExt;
+    let mut line = Vec::new();
+    while !line.ends_with(b"\r\n") {
+        let mut byte = [0];
+        tokio::time::timeout(Duration::from_secs(5), reader.read_exact(&mut byte)).await.unwrap().unwrap();
+        line.push(byte[0]);
+    }
+    String::from_utf8_lossy(&line).into_owned()
+}
+
+#[tokio::test]
+async fn monitor_only_shows_commands_the_user_may_run() {
+    use tokio::io::AsyncWriteExt;
+    let d = Arc::new(dispatcher());
+    d.acl.add_user("reader".to_string(), String::new(), vec!["@read".to_string(), "monitor".to_string()], vec!["*".to_string()]);
+    d.acl.add_user("nomonitor".to_string(), String::new(), vec!["@read".to_string()], vec!["*".to_string()]);
+    let writer = connect_from(&d, "127.0.0.1:50001");
+
+    let refused = connect_from(&d, "127.0.0.1:50002");
+    refused.set_user("nomonitor".to_string());
+    let (mut reader, task) = monitor(&d, &refused);
+    assert_eq!(read_line(&mut reader).await, "-NOPERM this user has no permissions to run the 'monitor' command\r\n");
+    assert_eq!(task.await.unwrap(), None);
+
+    let watcher = connect_from(&d, "127.0.0.1:50003");
+    watcher.set_user("reader".to_string());
+    let (mut reader, task) = monitor(&d, &watcher);
+    assert_eq!(read_line(&mut reader).await, "+OK\r\n");
+
+    // The feed is global: other tests' commands show up too, so look for this key only
+    exec(&d, &writer, &[b"SET", b"monitor-test:k", b"v"]).await;
+    exec(&d, &writer, &[b"GET", b"monitor-test:k"]).await;
+    let line = loop {
+        let line = read_line(&mut reader).await;
+        if line.contains("\"monitor-test:k\"") {
+            break line;
+        }
+    };
+    assert!(line.ends_with("[0 127.0.0.1:50001] \"GET\" \"monitor-test:k\"\r\n"), "{}", line);
+
+    // Any input ends MONITOR mode and is handed back to run
+    reader.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+    assert_eq!(next, Some(RespFrame::Array(Some(vec![RespFrame::bulk("PING")]))));
+}
+
+/// Send `request` and expect exactly `reply` back.
+async fn roundtrip(stream: &mut tokio::io::DuplexStream, request: &[u8], reply: &str) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    stream.write_all(request).await.unwrap();
+    let mut buf = vec![0; reply.len()];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf)).await.unwrap().unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), reply);
+}
+
+#[tokio::test]
+async fn subscribed_clients_keep_subscribing_and_counting() {
+    let d = Arc::new(dispatcher());
+    let (publisher, sub) = (connect_from(&d, "127.0.0.1:50001"), connect_from(&d, "127.0.0.1:50002"));
+    let (mut reader, task) = subscriber(&d, &sub, "news").await;
+
+    roundtrip(&mut reader, b"SUBSCRIBE sport\r\n", "*3\r\n$9\r\nsubscribe\r\n$5\r\nsport\r\n:2\r\n").await;
+    roundtrip(&mut reader, b"PSUBSCRIBE n*\r\n", "*3\r\n$10\r\npsubscribe\r\n$2\r\nn*\r\n:3\r\n").await;
+    assert_eq!((sub.subscriptions(), sub.pattern_subscriptions()), (2, 1));
+    roundtrip(&mut reader, b"PING\r\n", "*2\r\n$4\r\npong\r\n$0\r\n\r\n").await;
+    roundtrip(&mut reader, b"GET k\r\n", "-ERR Can't execute 'get': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context\r\n").await;
+
+    // Both the channel and the pattern match
+    exec(&d, &publisher, &[b"PUBLISH", b"news", b"hi"]).await;
+    roundtrip(&mut reader, b"", "*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n*4\r\n$8\r\npmessage\r\n$2\r\nn*\r\n$4\r\nnews\r\n$2\r\nhi\r\n").await;
+
+    roundtrip(&mut reader, b"UNSUBSCRIBE news sport\r\n",
+        "*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:2\r\n*3\r\n$11\r\nunsubscribe\r\n$5\r\nsport\r\n:1\r\n").await;
+    assert!(!task.is_finished());
+    // Dropping the last subscription leaves pub/sub mode
+    roundtrip(&mut reader, b"PUNSUBSCRIBE\r\n", "*3\r\n$12\r\npunsubscribe\r\n$2\r\nn*\r\n:0\r\n").await;
+    tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+    assert!(!sub.is_pubsub());
+}
+
+#[tokio::test]
+async fn keyspace_events_reach_matching_patterns() {
+    use crate::core::notify;
+    use tokio::io::AsyncReadExt;
+    let d = Arc::new(dispatcher());
+    let (writer, sub) = (connect_from(&d, "127.0.0.1:50001"), connect_from(&d, "127.0.0.1:50002"));
+    notify::set_flags(notify::parse("Ex").unwrap());
+
+    // Events go out on the first Dispatcher's channel, which may be another test's
+    let rx = notify::subscribe().unwrap();
+    let (mut reader, server) = tokio::io::duplex(64 * 1024);
+    let (d2, sub2) = (d.clone(), sub.clone());
+    let task = tokio::spawn(async move {
+        let mut conn = crate::io::connection::Connection::new(server, crate::core::protocol::ProtocolLimits::default());
+        let frames = [RespFrame::bulk("PSUBSCRIBE"), RespFrame::bulk("__keyevent@*__:expired")];
+        d2.pubsub_session(&sub2, &frames, rx, &mut conn).await.unwrap();
+    });
+    roundtrip(&mut reader, b"", "*3\r\n$10\r\npsubscribe\r\n$22\r\n__keyevent@*__:expired\r\n:1\r\n").await;
+
+    exec(&d, &writer, &[b"SET", b"notify-test:k", b"v", b"PX", b"1"]).await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(exec(&d, &writer, &[b"GET", b"notify-test:k"]).await, RespFrame::BulkString(None));
+
+    // Other tests' keys may expire too; wait for this one
+    let expected = "*4\r\n$8\r\npmessage\r\n$22\r\n__keyevent@*__:expired\r\n$22\r\n__keyevent@0__:expired\r\n$13\r\nnotify-test:k\r\n";
+    let mut received = Vec::new();
+    while !String::from_utf8_lossy(&received).contains(expected) {
+        let mut chunk = [0; 1024];
+        let n = tokio::time::timeout(Duration::from_secs(5), reader.read(&mut chunk)).await.unwrap().unwrap();
+        received.extend_from_slice(&chunk[..n]);
+    }
+    sub.kill();
+    tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+}
@@ -0,0 +1,123 @@
+use bytes::Bytes;
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use rand::Rng;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SHARDS: usize = 64;
+
+/// Wall-clock time in Unix milliseconds, the unit of every expire deadline.
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Key -> absolute deadline (Unix ms), the Redis `expires` dict.
+/// Sharded by hand rather than a DashMap so the active expiry cycle can
+/// walk one shard at a time.
+pub struct Expires {
+    shards: Box<[Mutex<HashMap<Bytes, u64>>]>,
+}
+
+impl Expires {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    fn shard(&self, key: &[u8]) -> &Mutex<HashMap<Bytes, u64>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<u64> {
+        self.shard(key).lock().get(key).copied()
+    }
+
+    pub fn set(&self, key: Bytes, at: u64) {
+        self.shard(&key).lock().insert(key, at);
+    }
+
+    pub fn remove(&self, key: &[u8]) -> Option<u64> {
+        self.shard(key).lock().remove(key)
+    }
+
+    /// Remove the deadline if it is at or before `now`. True if it was.
+    pub fn remove_if_expired(&self, key: &[u8], now: u64) -> bool {
+        let mut shard = self.shard(key).lock();
+        match shard.get(key) {
+            Some(&at) if at <= now => {
+                shard.remove(key);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.lock().len()).sum()
+    }
+
+    pub fn shard_count(&self) -> usize {
+        SHARDS
+    }
+
+    /// Keys of shard `index` whose deadline passed, plus how many keys with
+    /// a deadline the shard holds.
+    pub fn expired_in_shard(&self, index: usize, now: u64) -> (Vec<Bytes>, usize) {
+        let shard = self.shards[index % SHARDS].lock();
+        let expired = shard.iter().filter(|(_, &at)| at <= now).map(|(k, _)| k.clone()).collect();
+        (expired, shard.len())
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().clear();
+        }
+    }
+
+    /// Exchange contents with `other` (SWAPDB). Shard placement does not
+    /// depend on the instance, so shards swap one to one.
+    pub fn swap_with(&self, other: &Expires) {
+        for (a, b) in self.shards.iter().zip(other.shards.iter()) {
+            std::mem::swap(&mut *a.lock(), &mut *b.lock());
+        }
+    }
+
+    /// Up to `n` random (key, deadline) pairs, possibly repeated (volatile-* eviction).
+    pub fn sample(&self, n: usize) -> Vec<(Bytes, u64)> {
+        let mut rng = rand::thread_rng();
+        let mut picked = Vec::with_capacity(n);
+        for _ in 0..n * 4 {
+            if picked.len() == n {
+                break;
+            }
+            let shard = self.shards[rng.gen_range(0..SHARDS)].lock();
+            if shard.is_empty() {
+                continue;
+            }
+            let pick = rng.gen_range(0..shard.len());
+            if let Some((k, at)) = shard.iter().nth(pick) {
+                picked.push((k.clone(), *at));
+            }
+        }
+        if picked.is_empty() {
+            let start = rng.gen_range(0..SHARDS);
+            picked.extend((0..SHARDS)
+                .find_map(|i| self.shards[(start + i) % SHARDS].lock().iter().next().map(|(k, at)| (k.clone(), *at))));
+        }
+        picked
+    }
+
+    /// Every (key, deadline) pair, for snapshots.
+    pub fn snapshot(&self) -> HashMap<Bytes, u64> {
+        let mut all = HashMap::new();
+        for shard in self.shards.iter() {
+            all.extend(shard.lock().iter().map(|(k, v)| (k.clone(), *v)));
+        }
+        all
+    }
+}
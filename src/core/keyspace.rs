@@ -0,0 +1,88 @@
+use crate::core::storage::Db;
+use crate::core::error::{DbResult, ZedisError};
+use serde::ser::SerializeSeq;
+use serde::{Serialize, Serializer};
+use std::sync::Arc;
+
+/// The logical databases (`databases`), addressed by SELECT index.
+/// A database keeps its index for life: SWAPDB and FLUSHDB change contents
+/// in place, so anything holding an `Arc<Db>` stays on the same index.
+pub struct Keyspace {
+    dbs: Box<[Arc<Db>]>,
+}
+
+impl Keyspace {
+    pub fn new(count: usize) -> Self {
+        Self {
+            dbs: (0..count).map(|i| Arc::new(Db::with_index(i))).collect(),
+        }
+    }
+
+    /// Build from the databases of a snapshot; the rest start empty.
+    pub fn from_snapshot(count: usize, loaded: Vec<(usize, Db)>) -> Result<Self, String> {
+        let mut dbs: Vec<Option<Db>> = (0..count).map(|_| None).collect();
+        for (index, mut db) in loaded {
+            let Some(slot) = dbs.get_mut(index) else {
+                return Err(format!("snapshot has DB {} but databases is {}", index, count));
+            };
+            db.set_index(index);
+            *slot = Some(db);
+        }
+        Ok(Self {
+            dbs: dbs.into_iter()
+                .enumerate()
+                .map(|(i, db)| Arc::new(db.unwrap_or_else(|| Db::with_index(i))))
+                .collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.dbs.len()
+    }
+
+    /// Database `index`. Callers validate client input with `check_index` first.
+    pub fn db(&self, index: usize) -> &Arc<Db> {
+        &self.dbs[index]
+    }
+
+    /// Parse and range-check a DB index argument.
+    pub fn check_index(&self, arg: Option<&str>) -> DbResult<usize> {
+        let index = arg
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(ZedisError::NotInteger)?;
+        if index < 0 || index as usize >= self.dbs.len() {
+            return Err(ZedisError::err("DB index is out of range"));
+        }
+        Ok(index as usize)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Db>> {
+        self.dbs.iter()
+    }
+
+    pub fn swap(&self, a: usize, b: usize) {
+        self.dbs[a].swap_contents(&self.dbs[b]);
+    }
+
+    /// FLUSHALL
+    pub fn flush_all(&self, lazy: bool) {
+        for db in self.dbs.iter() {
+            db.flush(lazy);
+        }
+    }
+}
+
+// Snapshot form: (index, db) for every non-empty database
+impl Serialize for Keyspace {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let used: Vec<&Arc<Db>> = self.dbs.iter().filter(|db| db.len() > 0).collect();
+        let mut seq = serializer.serialize_seq(Some(used.len()))?;
+        for db in used {
+            seq.serialize_element(&(db.index() as u32, &**db))?;
+        }
+        seq.end()
+    }
+}
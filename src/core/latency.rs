@@ -0,0 +1,158 @@
+use crate::core::expire::now_ms;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Event classes of the latency monitor (LATENCY LATEST / HISTORY / DOCTOR)
+pub const COMMAND: &str = "command"; // any command, measured in Dispatcher::execute
+pub const SAVE: &str = "save"; // RDB snapshot (SAVE, shutdown save)
+pub const AOF_FSYNC: &str = "aof-fsync"; // AOF writer flush
+pub const EMBEDDING: &str = "embedding"; // BGE-M3 inference
+pub const EXPIRE_CYCLE: &str = "expire-cycle"; // active expiry tick
+
+/// Samples kept per event: one per second, the worst of that second.
+const HISTORY_LEN: usize = 160;
+
+/// latency-monitor-threshold in milliseconds; 0 = monitor off.
+static THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
+static EVENTS: Mutex<BTreeMap<&'static str, EventHistory>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct EventHistory {
+    /// (unix time, milliseconds), oldest first.
+    samples: VecDeque<(u64, u64)>,
+    /// All-time worst, survives the ring rolling over.
+    max: u64,
+}
+
+pub fn set_threshold(ms: u64) {
+    THRESHOLD_MS.store(ms, Ordering::Relaxed);
+}
+
+/// Run `f` and record how long it took under `event`.
+pub fn measure<T>(event: &'static str, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let out = f();
+    add_sample(event, started.elapsed());
+    out
+}
+
+/// Record a latency spike if `elapsed` reaches latency-monitor-threshold.
+/// A single atomic load when the monitor is off.
+pub fn add_sample(event: &'static str, elapsed: Duration) {
+    let threshold = THRESHOLD_MS.load(Ordering::Relaxed);
+    let ms = elapsed.as_millis() as u64;
+    if threshold == 0 || ms < threshold {
+        return;
+    }
+    let now = now_ms() / 1000;
+    let mut events = EVENTS.lock();
+    let history = events.entry(event).or_default();
+    history.max = history.max.max(ms);
+    match history.samples.back_mut() {
+        Some((at, worst)) if *at == now => *worst = (*worst).max(ms),
+        _ => {
+            if history.samples.len() == HISTORY_LEN {
+                history.samples.pop_front();
+            }
+            history.samples.push_back((now, ms));
+        }
+    }
+}
+
+/// LATENCY LATEST: (event, time of the latest spike, its latency, all-time max).
+pub fn latest() -> Vec<(&'static str, u64, u64, u64)> {
+    EVENTS
+        .lock()
+        .iter()
+        .filter_map(|(event, h)| h.samples.back().map(|&(at, ms)| (*event, at, ms, h.max)))
+        .collect()
+}
+
+/// LATENCY HISTORY event: (unix time, milliseconds), oldest first.
+pub fn history(event: &str) -> Vec<(u64, u64)> {
+    EVENTS.lock().get(event).map(|h| h.samples.iter().copied().collect()).unwrap_or_default()
+}
+
+/// LATENCY RESET [event ...]: drop the given events (all when none) and
+/// return how many were dropped.
+pub fn reset(events: &[String]) -> usize {
+    let mut all = EVENTS.lock();
+    if events.is_empty() {
+        let n = all.len();
+        all.clear();
+        return n;
+    }
+    events.iter().filter(|e| all.remove(e.as_str()).is_some()).count()
+}
+
+/// LATENCY DOCTOR: a human readable analysis of the recorded spikes.
+pub fn doctor() -> String {
+    let threshold = THRESHOLD_MS.load(Ordering::Relaxed);
+    let events = EVENTS.lock();
+    let mut out = String::new();
+    if events.is_empty() {
+        if threshold == 0 {
+            out.push_str("The latency monitor is disabled. Enable it with CONFIG SET latency-monitor-threshold <milliseconds>.\n");
+        } else {
+            out.push_str("Dave, no latency spike was observed during the lifetime of this Zedis instance, not in the slightest bit. I honestly think you ought to sleep tonight.\n");
+        }
+        return out;
+    }
+
+    let _ = writeln!(out, "Dave, I have observed latency spikes in this Zedis instance. You don't mind talking about it, do you Dave?\n");
+    for (i, (event, h)) in events.iter().enumerate() {
+        let count = h.samples.len() as u64;
+        let avg = h.samples.iter().map(|s| s.1).sum::<u64>() / count.max(1);
+        let mad = h.samples.iter().map(|s| s.1.abs_diff(avg)).sum::<u64>() / count.max(1);
+        let _ = write!(out, "{}. {}: {} latency spikes (average {}ms, mean deviation {}ms", i + 1, event, count, avg, mad);
+        if let (Some(first), Some(last)) = (h.samples.front(), h.samples.back()) {
+            if count > 1 {
+                let _ = write!(out, ", period {:.2} sec", (last.0 - first.0) as f64 / (count - 1) as f64);
+            }
+        }
+        let _ = writeln!(out, "). Worst all time event {}ms.", h.max);
+    }
+
+    out.push_str("\nI have a few advices for you:\n\n");
+    for event in events.keys() {
+        let advice = match *event {
+            COMMAND => "Check SLOWLOG GET for the slow commands. KEYS, brute-force VSEARCH and large LRANGE / SMEMBERS / HGETALL replies are O(N): prefer SCAN and smaller ranges.",
+            SAVE => "SAVE blocks every client while the snapshot is written. Save less often, or on a faster disk.",
+            AOF_FSYNC => "The AOF writer is waiting on the disk. Consider appendfsync everysec (or no), or a faster disk for the append only file.",
+            EMBEDDING => "BGE-M3 inference runs on the request path (VADD.TEXT, VADD.M3, VSEARCH.TEXT, VSEARCH.HYBRID). Keep texts short or embed ahead of time with VADD.",
+            EXPIRE_CYCLE => "Many keys expire at the same time. Add some jitter to the TTLs so expiry is spread out.",
+            _ => continue,
+        };
+        let _ = writeln!(out, "- {}: {}", event, advice);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spikes_over_the_threshold_are_recorded_per_second() {
+        // Events are global; this one is used nowhere else
+        const EVENT: &str = "test-spike";
+        set_threshold(100);
+        add_sample(EVENT, Duration::from_millis(99));
+        assert!(history(EVENT).is_empty());
+        add_sample(EVENT, Duration::from_millis(150));
+        add_sample(EVENT, Duration::from_millis(120));
+        let samples = history(EVENT);
+        // Same second: the worst is kept. A second may tick between the two
+        assert!(samples.iter().any(|&(_, ms)| ms == 150), "{:?}", samples);
+        assert!(samples.len() <= 2);
+        let latest = latest().into_iter().find(|(e, _, _, _)| *e == EVENT).unwrap();
+        assert_eq!(latest.3, 150);
+        assert!(doctor().contains(&format!("{}: {} latency spikes", EVENT, samples.len())));
+
+        assert_eq!(reset(&[EVENT.to_string(), "never-recorded".to_string()]), 1);
+        assert!(history(EVENT).is_empty());
+    }
+}
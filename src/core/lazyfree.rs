@@ -0,0 +1,59 @@
+use crate::core::storage::DataType;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::thread;
+
+/// Values that own more allocations than this are dropped off-thread.
+const LAZYFREE_THRESHOLD: usize = 64;
+
+static QUEUE: OnceLock<mpsc::Sender<Box<dyn Send>>> = OnceLock::new();
+static PENDING: AtomicU64 = AtomicU64::new(0);
+static FREED: AtomicU64 = AtomicU64::new(0);
+
+fn queue() -> &'static mpsc::Sender<Box<dyn Send>> {
+    QUEUE.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<Box<dyn Send>>();
+        thread::Builder::new()
+            .name("zedis-lazyfree".into())
+            .spawn(move || {
+                for value in rx {
+                    drop(value);
+                    PENDING.fetch_sub(1, Ordering::Relaxed);
+                    FREED.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .expect("failed to spawn lazyfree thread");
+        tx
+    })
+}
+
+/// Release a value removed from the keyspace (UNLINK, overwrites).
+/// Small values are dropped right here; big ones go to the lazyfree thread
+/// so the caller does not pay for walking them.
+pub fn free(value: DataType) {
+    if value.free_effort() <= LAZYFREE_THRESHOLD {
+        return;
+    }
+    free_later(value);
+}
+
+/// Release anything on the lazyfree thread, whatever its size.
+pub fn free_later<T: Send + 'static>(value: T) {
+    PENDING.fetch_add(1, Ordering::Relaxed);
+    let value: Box<dyn Send> = Box::new(value);
+    if let Err(mpsc::SendError(value)) = queue().send(value) {
+        PENDING.fetch_sub(1, Ordering::Relaxed);
+        drop(value);
+    }
+}
+
+/// Objects waiting on the lazyfree thread.
+pub fn pending() -> u64 {
+    PENDING.load(Ordering::Relaxed)
+}
+
+/// Objects released by the lazyfree thread since startup.
+pub fn freed() -> u64 {
+    FREED.load(Ordering::Relaxed)
+}
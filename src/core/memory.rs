@@ -0,0 +1,77 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicIsize, AtomicU64, Ordering};
+
+/// Bytes currently allocated through the global allocator (INFO used_memory,
+/// maxmemory). Threads batch their deltas, so the figure can lag by up to
+/// `FLUSH_BYTES` per thread.
+static USED: AtomicIsize = AtomicIsize::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+const FLUSH_BYTES: isize = 64 * 1024;
+
+thread_local! {
+    static PENDING: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Global allocator wrapper that keeps `used_memory()` up to date, the way
+/// Redis's zmalloc counts every allocation.
+pub struct TrackingAllocator<A>(pub A);
+
+#[inline]
+fn record(delta: isize) {
+    let batched = PENDING.try_with(|pending| {
+        let total = pending.get() + delta;
+        if total.abs() >= FLUSH_BYTES {
+            USED.fetch_add(total, Ordering::Relaxed);
+            pending.set(0);
+        } else {
+            pending.set(total);
+        }
+    });
+    if batched.is_err() {
+        USED.fetch_add(delta, Ordering::Relaxed);
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.0.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+/// Bytes allocated by the process right now.
+pub fn used_memory() -> u64 {
+    USED.load(Ordering::Relaxed).max(0) as u64
+}
+
+/// Highest `used_memory()` observed; the server cron samples it every 100ms.
+pub fn peak_memory() -> u64 {
+    let used = used_memory();
+    PEAK.fetch_max(used, Ordering::Relaxed).max(used)
+}
@@ -0,0 +1,84 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+/// Lines a monitor may fall behind before it is disconnected.
+const BACKLOG: usize = 4096;
+
+/// One executed command, as MONITOR shows it.
+pub struct MonitorEvent {
+    /// Upper-cased command name, checked against the monitor's ACL.
+    pub cmd: String,
+    /// `1339518083.107412 [0 127.0.0.1:60866] "keys" "*"`
+    pub line: String,
+}
+
+static MONITORS: AtomicUsize = AtomicUsize::new(0);
+static CHANNEL: OnceLock<Sender<Arc<MonitorEvent>>> = OnceLock::new();
+
+fn channel() -> &'static Sender<Arc<MonitorEvent>> {
+    CHANNEL.get_or_init(|| broadcast::channel(BACKLOG).0)
+}
+
+/// A connection in MONITOR mode; the feed stays off once the last one drops.
+pub struct Monitor {
+    pub rx: Receiver<Arc<MonitorEvent>>,
+}
+
+impl Monitor {
+    pub fn attach() -> Self {
+        let rx = channel().subscribe();
+        MONITORS.fetch_add(1, Ordering::Relaxed);
+        Self { rx }
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        MONITORS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether any connection is in MONITOR mode.
+pub fn is_active() -> bool {
+    MONITORS.load(Ordering::Relaxed) > 0
+}
+
+/// Show a command run against database `db` by `source` (a client address,
+/// `lua` or `aof`). A single atomic load while nobody is monitoring.
+pub fn feed(db: usize, source: &str, args: &[&[u8]]) {
+    if !is_active() || args.is_empty() {
+        return;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut line = format!("{}.{:06} [{} {}]", now.as_secs(), now.subsec_micros(), db, source);
+    for arg in args {
+        line.push(' ');
+        quote(&mut line, arg);
+    }
+    let cmd = String::from_utf8_lossy(args[0]).to_uppercase();
+    let _ = channel().send(Arc::new(MonitorEvent { cmd, line }));
+}
+
+/// Double-quoted and escaped like Redis's sdscatrepr.
+fn quote(out: &mut String, arg: &[u8]) {
+    out.push('"');
+    for &b in arg {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            b if b.is_ascii_graphic() || b == b' ' => out.push(b as char),
+            b => {
+                let _ = write!(out, "\\x{:02x}", b);
+            }
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,98 @@
+use bytes::Bytes;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::broadcast::Sender;
+
+// notify-keyspace-events classes. The Redis letters keep their meaning;
+// the Zedis types get their own.
+pub const KEYSPACE: u32 = 1 << 0; // K: __keyspace@<db>__:<key> -> <event>
+pub const KEYEVENT: u32 = 1 << 1; // E: __keyevent@<db>__:<event> -> <key>
+pub const GENERIC: u32 = 1 << 2; // g: del, expire, rename_from, copy_to, ...
+pub const STRING: u32 = 1 << 3; // $
+pub const LIST: u32 = 1 << 4; // l
+pub const SET: u32 = 1 << 5; // s
+pub const HASH: u32 = 1 << 6; // h
+pub const ZSET: u32 = 1 << 7; // z
+pub const EXPIRED: u32 = 1 << 8; // x
+pub const EVICTED: u32 = 1 << 9; // e
+pub const STREAM: u32 = 1 << 10; // t
+pub const VECTOR: u32 = 1 << 11; // v: vadd
+pub const JSON: u32 = 1 << 12; // j: json.set
+pub const TIMESERIES: u32 = 1 << 13; // T: ts.add
+pub const GRAPH: u32 = 1 << 14; // G: graph.add
+pub const SKETCH: u32 = 1 << 15; // b: bf.add, cf.add, cms.incrby, topk.add, tdigest.add
+pub const MODEL: u32 = 1 << 16; // M: ml.load
+
+/// Every event class, what `A` stands for.
+const ALL: u32 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM
+    | VECTOR | JSON | TIMESERIES | GRAPH | SKETCH | MODEL;
+
+const LETTERS: &[(char, u32)] = &[
+    ('K', KEYSPACE), ('E', KEYEVENT), ('g', GENERIC), ('$', STRING), ('l', LIST), ('s', SET),
+    ('h', HASH), ('z', ZSET), ('x', EXPIRED), ('e', EVICTED), ('t', STREAM), ('v', VECTOR),
+    ('j', JSON), ('T', TIMESERIES), ('G', GRAPH), ('b', SKETCH), ('M', MODEL),
+];
+
+static FLAGS: AtomicU32 = AtomicU32::new(0);
+static CHANNEL: OnceLock<Sender<(Bytes, Bytes)>> = OnceLock::new();
+
+/// Parse a notify-keyspace-events value such as `KEA` or `Ex`. None on an
+/// unknown letter.
+pub fn parse(value: &str) -> Option<u32> {
+    value.chars().try_fold(0, |flags, c| match c {
+        'A' => Some(flags | ALL),
+        _ => LETTERS.iter().find(|(l, _)| *l == c).map(|(_, class)| flags | class),
+    })
+}
+
+/// Canonical text of `flags`, as CONFIG GET shows it.
+pub fn to_string(flags: u32) -> String {
+    let mut out = String::new();
+    if flags & ALL == ALL {
+        out.push('A');
+    }
+    for (letter, class) in LETTERS {
+        let folded = flags & ALL == ALL && class & ALL != 0;
+        if flags & class != 0 && !folded {
+            out.push(*letter);
+        }
+    }
+    out
+}
+
+/// Pub/sub channel the events go out on: the Dispatcher's.
+pub fn install(tx: Sender<(Bytes, Bytes)>) {
+    let _ = CHANNEL.set(tx);
+}
+
+/// A listener on the installed channel, whichever Dispatcher installed it.
+#[cfg(test)]
+pub fn subscribe() -> Option<tokio::sync::broadcast::Receiver<(Bytes, Bytes)>> {
+    CHANNEL.get().map(Sender::subscribe)
+}
+
+pub fn set_flags(flags: u32) {
+    FLAGS.store(flags, Ordering::Relaxed);
+}
+
+/// Publish `event` on `key` of database `db` if its class is enabled. A
+/// single atomic load when notifications are off.
+pub fn keyspace_event(class: u32, event: &str, db: usize, key: &[u8]) {
+    let flags = FLAGS.load(Ordering::Relaxed);
+    if flags & class == 0 || flags & (KEYSPACE | KEYEVENT) == 0 {
+        return;
+    }
+    let Some(tx) = CHANNEL.get() else { return };
+    if tx.receiver_count() == 0 {
+        return;
+    }
+    if flags & KEYSPACE != 0 {
+        let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+        channel.extend_from_slice(key);
+        let _ = tx.send((Bytes::from(channel), Bytes::copy_from_slice(event.as_bytes())));
+    }
+    if flags & KEYEVENT != 0 {
+        let channel = format!("__keyevent@{}__:{}", db, event);
+        let _ = tx.send((Bytes::from(channel), Bytes::copy_from_slice(key)));
+    }
+}
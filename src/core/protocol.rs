@@ -1,26 +1,84 @@
+use bytes::{Buf, Bytes, BytesMut};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take, take_until},
     character::complete::{crlf, i64 as parse_i64},
-    combinator::map,
+    combinator::{map, map_res},
     multi::count,
-    sequence::{delimited, terminated},
+    sequence::{delimited, pair, terminated},
     IResult,
 };
 
+/// Wire protocol negotiated by a connection (`HELLO 2|3`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl ProtocolVersion {
+    pub fn as_i64(self) -> i64 {
+        match self {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum RespFrame {
     SimpleString(String),
     Error(String),
     Integer(i64),
-    BulkString(Option<String>),
+    BulkString(Option<Bytes>),
     Array(Option<Vec<RespFrame>>),
-    #[allow(dead_code)]
     Null,
+    // RESP3 types. Downgraded to their RESP2 equivalents for RESP2 clients.
+    Map(Vec<(RespFrame, RespFrame)>),
+    Set(Vec<RespFrame>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    VerbatimString(String, Bytes), // 3-char format ("txt", "mkd"), payload
+    Push(Vec<RespFrame>),
+    Attribute(Vec<(RespFrame, RespFrame)>, Box<RespFrame>), // attributes, annotated frame
 }
 
 impl RespFrame {
-    pub fn encode(&self, buf: &mut Vec<u8>) {
+    /// Bulk string reply from any owned byte payload.
+    pub fn bulk(data: impl Into<Bytes>) -> Self {
+        RespFrame::BulkString(Some(data.into()))
+    }
+
+    /// Raw payload of a string argument (bulk or simple).
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            RespFrame::BulkString(Some(b)) => Some(b),
+            RespFrame::SimpleString(s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Owned payload of a string argument. Zero-copy for bulk strings.
+    pub fn to_bytes(&self) -> Option<Bytes> {
+        match self {
+            RespFrame::BulkString(Some(b)) => Some(b.clone()),
+            RespFrame::SimpleString(s) => Some(Bytes::copy_from_slice(s.as_bytes())),
+            _ => None,
+        }
+    }
+
+    /// UTF-8 view of a textual argument (numbers, options, JSON).
+    /// Returns `None` for non-string frames and invalid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    /// Encode using the native types of `proto`. RESP3-only types are
+    /// mapped to the closest RESP2 shape the way Redis does it.
+    pub fn encode_for(&self, proto: ProtocolVersion, buf: &mut Vec<u8>) {
+        let resp3 = proto == ProtocolVersion::Resp3;
         match self {
             RespFrame::SimpleString(s) => {
                 buf.extend_from_slice(b"+");
@@ -37,45 +95,378 @@ impl RespFrame {
                 buf.extend_from_slice(i.to_string().as_bytes());
                 buf.extend_from_slice(b"\r\n");
             }
-            RespFrame::BulkString(Some(s)) => {
-                buf.extend_from_slice(b"$");
-                buf.extend_from_slice(s.len().to_string().as_bytes());
-                buf.extend_from_slice(b"\r\n");
-                buf.extend_from_slice(s.as_bytes());
-                buf.extend_from_slice(b"\r\n");
+            RespFrame::BulkString(Some(s)) => encode_blob(b'$', s, buf),
+            RespFrame::Array(Some(frames)) => encode_aggregate(b'*', frames, proto, buf),
+            RespFrame::BulkString(None) | RespFrame::Null if resp3 => {
+                buf.extend_from_slice(b"_\r\n");
             }
-            RespFrame::BulkString(None) => {
-                buf.extend_from_slice(b"$-1\r\n");
+            RespFrame::Array(None) if resp3 => {
+                buf.extend_from_slice(b"_\r\n");
             }
-            RespFrame::Array(Some(frames)) => {
-                buf.extend_from_slice(b"*");
-                buf.extend_from_slice(frames.len().to_string().as_bytes());
-                buf.extend_from_slice(b"\r\n");
-                for frame in frames {
-                    frame.encode(buf);
-                }
+            RespFrame::BulkString(None) | RespFrame::Null => {
+                buf.extend_from_slice(b"$-1\r\n");
             }
             RespFrame::Array(None) => {
                 buf.extend_from_slice(b"*-1\r\n");
             }
-            RespFrame::Null => {
-                 buf.extend_from_slice(b"_\r\n");
+            RespFrame::Map(pairs) => {
+                if resp3 {
+                    buf.extend_from_slice(format!("%{}\r\n", pairs.len()).as_bytes());
+                } else {
+                    buf.extend_from_slice(format!("*{}\r\n", pairs.len() * 2).as_bytes());
+                }
+                for (k, v) in pairs {
+                    k.encode_for(proto, buf);
+                    v.encode_for(proto, buf);
+                }
+            }
+            RespFrame::Set(items) => encode_aggregate(if resp3 { b'~' } else { b'*' }, items, proto, buf),
+            RespFrame::Push(items) => encode_aggregate(if resp3 { b'>' } else { b'*' }, items, proto, buf),
+            RespFrame::Double(d) => {
+                let text = format_double(*d);
+                if resp3 {
+                    buf.extend_from_slice(b",");
+                    buf.extend_from_slice(text.as_bytes());
+                    buf.extend_from_slice(b"\r\n");
+                } else {
+                    encode_blob(b'$', text.as_bytes(), buf);
+                }
+            }
+            RespFrame::Boolean(b) => {
+                if resp3 {
+                    buf.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" });
+                } else {
+                    buf.extend_from_slice(if *b { b":1\r\n" } else { b":0\r\n" });
+                }
+            }
+            RespFrame::BigNumber(n) => {
+                if resp3 {
+                    buf.extend_from_slice(b"(");
+                    buf.extend_from_slice(n.as_bytes());
+                    buf.extend_from_slice(b"\r\n");
+                } else {
+                    encode_blob(b'$', n.as_bytes(), buf);
+                }
+            }
+            RespFrame::VerbatimString(format, data) => {
+                if resp3 {
+                    let mut payload = Vec::with_capacity(4 + data.len());
+                    payload.extend_from_slice(format.as_bytes());
+                    payload.push(b':');
+                    payload.extend_from_slice(data);
+                    encode_blob(b'=', &payload, buf);
+                } else {
+                    encode_blob(b'$', data, buf);
+                }
+            }
+            RespFrame::Attribute(attrs, frame) => {
+                // RESP2 has no out-of-band data: attributes are dropped.
+                if resp3 {
+                    buf.extend_from_slice(format!("|{}\r\n", attrs.len()).as_bytes());
+                    for (k, v) in attrs {
+                        k.encode_for(proto, buf);
+                        v.encode_for(proto, buf);
+                    }
+                }
+                frame.encode_for(proto, buf);
             }
         }
     }
 }
 
+fn encode_blob(prefix: u8, data: &[u8], buf: &mut Vec<u8>) {
+    buf.push(prefix);
+    buf.extend_from_slice(data.len().to_string().as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(b"\r\n");
+}
+
+fn encode_aggregate(prefix: u8, items: &[RespFrame], proto: ProtocolVersion, buf: &mut Vec<u8>) {
+    buf.push(prefix);
+    buf.extend_from_slice(items.len().to_string().as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    for item in items {
+        item.encode_for(proto, buf);
+    }
+}
+
+/// Doubles use the RESP3 spellings `inf`, `-inf` and `nan`.
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf".to_string() } else { "-inf".to_string() }
+    } else {
+        d.to_string()
+    }
+}
+
+
+/// Malformed client request. The connection replies with this and closes.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("ERR Protocol error: {0}")]
+pub struct ProtocolError(pub String);
+
+/// Longest header or inline line accepted without a terminator (Redis' PROTO_INLINE_MAX_SIZE).
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Bounds enforced while decoding untrusted input. Exceeding any of them is a
+/// protocol error that closes the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    /// proto-max-bulk-len: largest single bulk string.
+    pub max_bulk_len: usize,
+    /// Largest element count for a multi-bulk / aggregate.
+    pub max_multibulk_len: usize,
+    /// Deepest aggregate nesting accepted by `parse_frame`.
+    pub max_nesting_depth: usize,
+    /// client-query-buffer-limit: unparsed bytes buffered per connection.
+    pub max_query_buffer: usize,
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: 1024 * 1024,
+            max_nesting_depth: 128,
+            max_query_buffer: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Incremental request decoder owned by a connection.
+///
+/// Requests are either RESP multi-bulks (`*N` of `$` bulk strings) or inline
+/// commands (`PING\r\n` from telnet / health checks). A multi-bulk is
+/// consumed element by element, so a large request arriving over several
+/// reads is never rescanned from the start.
+#[derive(Debug, Default)]
+pub struct RequestParser {
+    limits: ProtocolLimits,
+    // Declared element count and the arguments decoded so far
+    pending: Option<(usize, Vec<RespFrame>)>,
+}
+
+impl RequestParser {
+    pub fn new(limits: ProtocolLimits) -> Self {
+        Self { limits, pending: None }
+    }
+
+    /// Decode the next complete request from `buf`, consuming its bytes.
+    /// Returns `Ok(None)` when more data is needed.
+    pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, ProtocolError> {
+        let frame = self.parse_request(buf)?;
+        if frame.is_none() && buf.len() > self.limits.max_query_buffer {
+            return Err(ProtocolError("query buffer limit exceeded".to_string()));
+        }
+        Ok(frame)
+    }
+
+    fn parse_request(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, ProtocolError> {
+        while self.pending.is_none() {
+            match buf.first() {
+                None => return Ok(None),
+                Some(b'*') => {
+                    let Some(end) = find_line(buf, "too big mbulk count string")? else { return Ok(None) };
+                    let len = match parse_len(&buf[1..end]) {
+                        Some(len) if len <= self.limits.max_multibulk_len as i64 => len,
+                        _ => return Err(ProtocolError("invalid multibulk length".to_string())),
+                    };
+                    buf.advance(end + 2);
+                    // Like Redis, `*0` / `*-1` are silently skipped
+                    if len > 0 {
+                        let len = len as usize;
+                        self.pending = Some((len, Vec::with_capacity(len.min(1024))));
+                    }
+                }
+                Some(_) => {
+                    let Some(end) = buf.iter().position(|&b| b == b'\n') else {
+                        if buf.len() > MAX_INLINE_LEN {
+                            return Err(ProtocolError("too big inline request".to_string()));
+                        }
+                        return Ok(None);
+                    };
+                    let line = buf.split_to(end + 1);
+                    let line = line[..end].strip_suffix(b"\r").unwrap_or(&line[..end]);
+                    let args = split_inline_args(line)
+                        .ok_or_else(|| ProtocolError("unbalanced quotes in request".to_string()))?;
+                    if !args.is_empty() {
+                        let frames = args.into_iter().map(|a| RespFrame::BulkString(Some(a))).collect();
+                        return Ok(Some(RespFrame::Array(Some(frames))));
+                    }
+                }
+            }
+        }
+
+        let Some((expected, items)) = self.pending.as_mut() else { return Ok(None) };
+        while items.len() < *expected {
+            match buf.first() {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(&c) => return Err(ProtocolError(format!("expected '$', got '{}'", c as char))),
+            }
+            let Some(end) = find_line(buf, "too big bulk count string")? else { return Ok(None) };
+            // Checked before waiting for the payload so a huge declared
+            // length can never make us buffer it
+            let len = match parse_len(&buf[1..end]) {
+                Some(len) if len >= 0 && len <= self.limits.max_bulk_len as i64 => len as usize,
+                _ => return Err(ProtocolError("invalid bulk length".to_string())),
+            };
+            if buf.len() < end + 2 + len + 2 {
+                return Ok(None);
+            }
+            if &buf[end + 2 + len..end + 2 + len + 2] != b"\r\n" {
+                return Err(ProtocolError("invalid bulk format".to_string()));
+            }
+            buf.advance(end + 2);
+            // Zero-copy: the argument shares the connection's read buffer
+            let data = buf.split_to(len).freeze();
+            buf.advance(2);
+            items.push(RespFrame::BulkString(Some(data)));
+        }
+
+        Ok(self.pending.take().map(|(_, items)| RespFrame::Array(Some(items))))
+    }
+}
+
+/// Position of the CRLF ending a header line, or `None` if it hasn't arrived.
+fn find_line(buf: &[u8], too_big: &str) -> Result<Option<usize>, ProtocolError> {
+    match find_crlf(buf) {
+        Some(end) => Ok(Some(end)),
+        None if buf.len() > MAX_INLINE_LEN => Err(ProtocolError(too_big.to_string())),
+        None => Ok(None),
+    }
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+fn parse_len(digits: &[u8]) -> Option<i64> {
+    std::str::from_utf8(digits).ok()?.parse::<i64>().ok()
+}
+
+/// Split an inline command into arguments, following the Redis rules:
+/// whitespace separated, "double quotes" with \n \r \t \b \a \\ \" and \xHH
+/// escapes, 'single quotes' with only \' escaped. A closing quote must be
+/// followed by whitespace. Returns `None` on unbalanced quotes.
+pub fn split_inline_args(line: &[u8]) -> Option<Vec<Bytes>> {
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Some(args);
+        }
+
+        let mut current = Vec::new();
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            let c = line.get(i).copied();
+            if in_double {
+                match c {
+                    None => return None,
+                    Some(b'\\') if i + 3 < line.len()
+                        && line[i + 1] == b'x'
+                        && line[i + 2].is_ascii_hexdigit()
+                        && line[i + 3].is_ascii_hexdigit() =>
+                    {
+                        let hex = std::str::from_utf8(&line[i + 2..i + 4]).ok()?;
+                        current.push(u8::from_str_radix(hex, 16).ok()?);
+                        i += 3;
+                    }
+                    Some(b'\\') if i + 1 < line.len() => {
+                        i += 1;
+                        current.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    }
+                    Some(b'"') => {
+                        if line.get(i + 1).is_some_and(|n| !n.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => current.push(c),
+                }
+            } else if in_single {
+                match c {
+                    None => return None,
+                    Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                        i += 1;
+                        current.push(b'\'');
+                    }
+                    Some(b'\'') => {
+                        if line.get(i + 1).is_some_and(|n| !n.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => current.push(c),
+                }
+            } else {
+                match c {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double = true,
+                    Some(b'\'') => in_single = true,
+                    Some(c) => current.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(Bytes::from(current));
+    }
+}
 
 pub fn parse_frame(input: &[u8]) -> IResult<&[u8], RespFrame> {
+    parse_frame_limited(input, &ProtocolLimits::default(), 0)
+}
+
+/// `parse_frame` with explicit limits. `depth` is the current aggregate nesting.
+pub fn parse_frame_limited<'a>(input: &'a [u8], limits: &ProtocolLimits, depth: usize) -> IResult<&'a [u8], RespFrame> {
+    if depth > limits.max_nesting_depth {
+        return Err(nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::TooLarge)));
+    }
+    let nested = |i| parse_frame_limited(i, limits, depth + 1);
     alt((
         parse_simple_string,
         parse_error,
         parse_integer,
-        parse_bulk_string,
-        parse_array,
+        move |i| parse_bulk_string(i, limits),
+        move |i| parse_array(i, limits, nested),
+        parse_null,
+        parse_double,
+        parse_boolean,
+        parse_big_number,
+        move |i| parse_verbatim_string(i, limits),
+        move |i| parse_map(i, limits, nested),
+        move |i| parse_set(i, limits, nested),
+        move |i| parse_push(i, limits, nested),
+        move |i| parse_attribute(i, limits, nested),
     ))(input)
 }
 
+/// Aggregate/blob length header, rejecting anything over `max` (or below -1).
+fn parse_length<'a>(input: &'a [u8], prefix: &'static str, max: usize) -> IResult<&'a [u8], i64> {
+    let (rest, len) = delimited(tag(prefix), parse_i64, crlf)(input)?;
+    if len < -1 || len > max as i64 {
+        return Err(nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::TooLarge)));
+    }
+    Ok((rest, len))
+}
 
 fn parse_simple_string(input: &[u8]) -> IResult<&[u8], RespFrame> {
     map(
@@ -98,8 +489,8 @@ fn parse_integer(input: &[u8]) -> IResult<&[u8], RespFrame> {
     )(input)
 }
 
-fn parse_bulk_string(input: &[u8]) -> IResult<&[u8], RespFrame> {
-    let (input, len) = delimited(tag("$"), parse_i64, crlf)(input)?;
+fn parse_bulk_string<'a>(input: &'a [u8], limits: &ProtocolLimits) -> IResult<&'a [u8], RespFrame> {
+    let (input, len) = parse_length(input, "$", limits.max_bulk_len)?;
 
     if len == -1 {
         return Ok((input, RespFrame::BulkString(None)));
@@ -107,19 +498,243 @@ fn parse_bulk_string(input: &[u8]) -> IResult<&[u8], RespFrame> {
 
     let len = len as usize;
     map(terminated(take(len), crlf), |s: &[u8]| {
-        RespFrame::BulkString(Some(String::from_utf8_lossy(s).to_string()))
+        RespFrame::BulkString(Some(Bytes::copy_from_slice(s)))
     })(input)
 }
 
-fn parse_array(input: &[u8]) -> IResult<&[u8], RespFrame> {
-    let (input, len) = delimited(tag("*"), parse_i64, crlf)(input)?;
+fn parse_array<'a, F>(input: &'a [u8], limits: &ProtocolLimits, nested: F) -> IResult<&'a [u8], RespFrame>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], RespFrame>,
+{
+    let (input, len) = parse_length(input, "*", limits.max_multibulk_len)?;
 
     if len == -1 {
         return Ok((input, RespFrame::Array(None)));
     }
 
     let len = len as usize;
-    map(count(parse_frame, len), |frames| {
+    map(count(nested, len), |frames| {
         RespFrame::Array(Some(frames))
     })(input)
 }
+
+fn parse_null(input: &[u8]) -> IResult<&[u8], RespFrame> {
+    map(tag("_\r\n"), |_| RespFrame::Null)(input)
+}
+
+fn parse_double(input: &[u8]) -> IResult<&[u8], RespFrame> {
+    map_res(
+        delimited(tag(","), take_until("\r\n"), crlf),
+        |s: &[u8]| std::str::from_utf8(s).map_err(|_| ()).and_then(|v| v.parse::<f64>().map_err(|_| ())).map(RespFrame::Double),
+    )(input)
+}
+
+fn parse_boolean(input: &[u8]) -> IResult<&[u8], RespFrame> {
+    alt((
+        map(tag("#t\r\n"), |_| RespFrame::Boolean(true)),
+        map(tag("#f\r\n"), |_| RespFrame::Boolean(false)),
+    ))(input)
+}
+
+fn parse_big_number(input: &[u8]) -> IResult<&[u8], RespFrame> {
+    map(
+        delimited(tag("("), take_until("\r\n"), crlf),
+        |s: &[u8]| RespFrame::BigNumber(String::from_utf8_lossy(s).to_string()),
+    )(input)
+}
+
+fn parse_verbatim_string<'a>(input: &'a [u8], limits: &ProtocolLimits) -> IResult<&'a [u8], RespFrame> {
+    let (input, len) = parse_length(input, "=", limits.max_bulk_len)?;
+    let (input, payload) = terminated(take(len.max(0) as usize), crlf)(input)?;
+    // Payload is "fmt:data"; the format is always 3 bytes
+    if payload.len() < 4 || payload[3] != b':' {
+        return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify)));
+    }
+    let format = String::from_utf8_lossy(&payload[..3]).to_string();
+    Ok((input, RespFrame::VerbatimString(format, Bytes::copy_from_slice(&payload[4..]))))
+}
+
+fn parse_map<'a, F>(input: &'a [u8], limits: &ProtocolLimits, nested: F) -> IResult<&'a [u8], RespFrame>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], RespFrame>,
+{
+    let (input, len) = parse_length(input, "%", limits.max_multibulk_len)?;
+    map(count(pair(&nested, &nested), len.max(0) as usize), RespFrame::Map)(input)
+}
+
+fn parse_set<'a, F>(input: &'a [u8], limits: &ProtocolLimits, nested: F) -> IResult<&'a [u8], RespFrame>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], RespFrame>,
+{
+    let (input, len) = parse_length(input, "~", limits.max_multibulk_len)?;
+    map(count(nested, len.max(0) as usize), RespFrame::Set)(input)
+}
+
+fn parse_push<'a, F>(input: &'a [u8], limits: &ProtocolLimits, nested: F) -> IResult<&'a [u8], RespFrame>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], RespFrame>,
+{
+    let (input, len) = parse_length(input, ">", limits.max_multibulk_len)?;
+    map(count(nested, len.max(0) as usize), RespFrame::Push)(input)
+}
+
+fn parse_attribute<'a, F>(input: &'a [u8], limits: &ProtocolLimits, nested: F) -> IResult<&'a [u8], RespFrame>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], RespFrame>,
+{
+    let (input, len) = parse_length(input, "|", limits.max_multibulk_len)?;
+    let (input, attrs) = count(pair(&nested, &nested), len.max(0) as usize)(input)?;
+    let (input, frame) = nested(input)?;
+    Ok((input, RespFrame::Attribute(attrs, Box::new(frame))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(frame: RespFrame) -> Vec<Vec<u8>> {
+        match frame {
+            RespFrame::Array(Some(items)) => items.iter().map(|i| i.as_bytes().unwrap().to_vec()).collect(),
+            other => panic!("expected a request array, got {:?}", other),
+        }
+    }
+
+    fn parse_all(input: &[u8]) -> Result<Vec<Vec<Vec<u8>>>, ProtocolError> {
+        let mut parser = RequestParser::default();
+        let mut buf = BytesMut::from(input);
+        let mut requests = Vec::new();
+        while let Some(frame) = parser.parse(&mut buf)? {
+            requests.push(args(frame));
+        }
+        Ok(requests)
+    }
+
+    #[test]
+    fn multibulk_split_across_reads() {
+        let request = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n";
+        for split in 1..request.len() {
+            let mut parser = RequestParser::default();
+            let mut buf = BytesMut::from(&request[..split]);
+            assert!(parser.parse(&mut buf).unwrap().is_none(), "complete after {} bytes", split);
+            buf.extend_from_slice(&request[split..]);
+            let frame = parser.parse(&mut buf).unwrap().expect("request after the rest arrives");
+            assert_eq!(args(frame), vec![b"SET".to_vec(), b"key".to_vec(), b"value".to_vec()]);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn pipelined_requests_parse_in_order() {
+        let requests = parse_all(b"*1\r\n$4\r\nPING\r\nECHO hi\r\n*0\r\n\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n").unwrap();
+        assert_eq!(
+            requests,
+            vec![vec![b"PING".to_vec()], vec![b"ECHO".to_vec(), b"hi".to_vec()], vec![b"GET".to_vec(), b"k".to_vec()]]
+        );
+    }
+
+    #[test]
+    fn inline_commands_honour_quotes() {
+        let requests = parse_all(b"SET k \"hello world\\n\\x41\"\r\nSET k 'it\\'s'\nSET  k   v\r\n").unwrap();
+        assert_eq!(requests[0], vec![b"SET".to_vec(), b"k".to_vec(), b"hello world\nA".to_vec()]);
+        assert_eq!(requests[1], vec![b"SET".to_vec(), b"k".to_vec(), b"it's".to_vec()]);
+        assert_eq!(requests[2], vec![b"SET".to_vec(), b"k".to_vec(), b"v".to_vec()]);
+    }
+
+    #[test]
+    fn inline_unbalanced_quotes_are_an_error() {
+        for line in [&b"SET k \"open\r\n"[..], b"SET k 'open\r\n", b"SET k \"a\"b\r\n"] {
+            let err = parse_all(line).unwrap_err();
+            assert_eq!(err.0, "unbalanced quotes in request");
+        }
+    }
+
+    #[test]
+    fn malformed_multibulk_is_an_error() {
+        assert_eq!(parse_all(b"*x\r\n").unwrap_err().0, "invalid multibulk length");
+        assert_eq!(parse_all(b"*1\r\n+PING\r\n").unwrap_err().0, "expected '$', got '+'");
+        assert_eq!(parse_all(b"*1\r\n$4\r\nPINGPONG\r\n").unwrap_err().0, "invalid bulk format");
+    }
+
+    fn limited() -> RequestParser {
+        RequestParser::new(ProtocolLimits { max_bulk_len: 16, max_multibulk_len: 4, max_nesting_depth: 2, max_query_buffer: 1024 })
+    }
+
+    #[test]
+    fn multibulk_count_over_the_limit_is_rejected() {
+        let mut buf = BytesMut::from(&b"*5\r\n"[..]);
+        assert_eq!(limited().parse(&mut buf).unwrap_err().0, "invalid multibulk length");
+        let mut buf = BytesMut::from(&b"*2147483647\r\n"[..]);
+        assert_eq!(RequestParser::default().parse(&mut buf).unwrap_err().0, "invalid multibulk length");
+        let mut buf = BytesMut::from(&b"*4\r\n"[..]);
+        assert!(limited().parse(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn bulk_length_over_the_limit_is_rejected_before_the_payload() {
+        // Only the header has arrived: the declared length alone is enough
+        let mut buf = BytesMut::from(&b"*1\r\n$17\r\n"[..]);
+        assert_eq!(limited().parse(&mut buf).unwrap_err().0, "invalid bulk length");
+        let mut buf = BytesMut::from(&b"*1\r\n$-1\r\n"[..]);
+        assert_eq!(limited().parse(&mut buf).unwrap_err().0, "invalid bulk length");
+        let mut buf = BytesMut::from(&b"*1\r\n$16\r\n0123456789abcdef\r\n"[..]);
+        assert!(limited().parse(&mut buf).unwrap().is_some());
+    }
+
+    #[test]
+    fn unterminated_headers_and_query_buffer_are_bounded() {
+        let mut header = b"*".to_vec();
+        header.resize(MAX_INLINE_LEN + 1, b'1');
+        assert_eq!(parse_all(&header).unwrap_err().0, "too big mbulk count string");
+        let mut bulk = b"*1\r\n$".to_vec();
+        bulk.resize(MAX_INLINE_LEN + 8, b'1');
+        assert_eq!(parse_all(&bulk).unwrap_err().0, "too big bulk count string");
+        assert_eq!(parse_all(&vec![b'a'; MAX_INLINE_LEN + 1]).unwrap_err().0, "too big inline request");
+
+        // A partial request larger than client-query-buffer-limit
+        let mut parser = RequestParser::new(ProtocolLimits { max_query_buffer: 512, ..ProtocolLimits::default() });
+        let mut pending = BytesMut::from(&b"*2\r\n$1000\r\n"[..]);
+        pending.extend_from_slice(&[b'x'; 600]);
+        assert_eq!(parser.parse(&mut pending).unwrap_err().0, "query buffer limit exceeded");
+    }
+
+    #[test]
+    fn nesting_depth_is_bounded() {
+        let limits = ProtocolLimits { max_nesting_depth: 2, ..ProtocolLimits::default() };
+        assert!(parse_frame_limited(b"*1\r\n*1\r\n:1\r\n", &limits, 0).is_ok());
+        assert!(parse_frame_limited(b"*1\r\n*1\r\n*1\r\n:1\r\n", &limits, 0).is_err());
+    }
+
+    fn encoded(frame: &RespFrame, proto: ProtocolVersion) -> String {
+        let mut buf = Vec::new();
+        frame.encode_for(proto, &mut buf);
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn resp3_types_downgrade_for_resp2() {
+        let map = RespFrame::Map(vec![(RespFrame::bulk("k"), RespFrame::Integer(1))]);
+        assert_eq!(encoded(&map, ProtocolVersion::Resp3), "%1\r\n$1\r\nk\r\n:1\r\n");
+        assert_eq!(encoded(&map, ProtocolVersion::Resp2), "*2\r\n$1\r\nk\r\n:1\r\n");
+
+        let set = RespFrame::Set(vec![RespFrame::bulk("a")]);
+        assert_eq!(encoded(&set, ProtocolVersion::Resp3), "~1\r\n$1\r\na\r\n");
+        assert_eq!(encoded(&set, ProtocolVersion::Resp2), "*1\r\n$1\r\na\r\n");
+
+        let double = RespFrame::Double(1.5);
+        assert_eq!(encoded(&double, ProtocolVersion::Resp3), ",1.5\r\n");
+        assert_eq!(encoded(&double, ProtocolVersion::Resp2), "$3\r\n1.5\r\n");
+        assert_eq!(encoded(&RespFrame::Double(f64::NEG_INFINITY), ProtocolVersion::Resp2), "$4\r\n-inf\r\n");
+
+        assert_eq!(encoded(&RespFrame::Null, ProtocolVersion::Resp3), "_\r\n");
+        assert_eq!(encoded(&RespFrame::Null, ProtocolVersion::Resp2), "$-1\r\n");
+        assert_eq!(encoded(&RespFrame::Array(None), ProtocolVersion::Resp2), "*-1\r\n");
+
+        let push = RespFrame::Push(vec![RespFrame::bulk("message"), RespFrame::bulk("ch"), RespFrame::bulk("hi")]);
+        assert_eq!(encoded(&push, ProtocolVersion::Resp3), ">3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n");
+        assert_eq!(encoded(&push, ProtocolVersion::Resp2), "*3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n");
+
+        let attribute = RespFrame::Attribute(vec![(RespFrame::bulk("ttl"), RespFrame::Integer(5))], Box::new(RespFrame::Integer(7)));
+        assert_eq!(encoded(&attribute, ProtocolVersion::Resp3), "|1\r\n$3\r\nttl\r\n:5\r\n:7\r\n");
+        assert_eq!(encoded(&attribute, ProtocolVersion::Resp2), ":7\r\n");
+    }
+}
@@ -0,0 +1,166 @@
+use crate::core::client::Client;
+use crate::core::expire::now_ms;
+use crate::core::protocol::RespFrame;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Arguments kept per entry, and bytes kept per argument, as in Redis.
+const MAX_ARGC: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+/// One SLOWLOG GET entry.
+#[derive(Clone)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    /// Unix time the command was logged.
+    pub time: u64,
+    pub duration_us: u64,
+    pub args: Vec<Bytes>,
+    pub addr: String,
+    pub name: String,
+}
+
+/// Ring buffer of commands slower than slowlog-log-slower-than.
+pub struct SlowLog {
+    /// Newest first.
+    entries: Mutex<VecDeque<SlowLogEntry>>,
+    next_id: AtomicU64,
+    /// slowlog-log-slower-than in microseconds; negative = off, 0 = log everything.
+    slower_than: AtomicI64,
+    /// slowlog-max-len
+    max_len: AtomicUsize,
+}
+
+impl SlowLog {
+    pub fn new(slower_than: i64, max_len: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+            slower_than: AtomicI64::new(slower_than),
+            max_len: AtomicUsize::new(max_len),
+        }
+    }
+
+    /// Apply CONFIG SET slowlog-*; a shorter slowlog-max-len drops the oldest entries.
+    pub fn configure(&self, slower_than: i64, max_len: usize) {
+        self.slower_than.store(slower_than, Ordering::Relaxed);
+        self.max_len.store(max_len, Ordering::Relaxed);
+        self.entries.lock().truncate(max_len);
+    }
+
+    /// Log `args` if the command took long enough.
+    pub fn record(&self, client: &Client, args: &[RespFrame], elapsed: Duration) {
+        let slower_than = self.slower_than.load(Ordering::Relaxed);
+        let duration_us = elapsed.as_micros() as u64;
+        if slower_than < 0 || duration_us < slower_than as u64 {
+            return;
+        }
+        let max_len = self.max_len.load(Ordering::Relaxed);
+        if max_len == 0 {
+            return;
+        }
+
+        // Long commands are cut down so the log stays small
+        let kept = args.len().min(MAX_ARGC);
+        let mut logged: Vec<Bytes> = Vec::with_capacity(kept);
+        for (i, arg) in args.iter().take(kept).enumerate() {
+            if i == kept - 1 && kept < args.len() {
+                logged.push(Bytes::from(format!("... ({} more arguments)", args.len() - kept + 1)));
+                break;
+            }
+            let bytes = arg.to_bytes().unwrap_or_default();
+            if bytes.len() > MAX_ARG_LEN {
+                let mut cut = bytes[..MAX_ARG_LEN].to_vec();
+                cut.extend_from_slice(format!("... ({} more bytes)", bytes.len() - MAX_ARG_LEN).as_bytes());
+                logged.push(Bytes::from(cut));
+            } else {
+                logged.push(bytes);
+            }
+        }
+
+        let entry = SlowLogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            time: now_ms() / 1000,
+            duration_us,
+            args: logged,
+            addr: client.addr.clone(),
+            name: client.name().unwrap_or_default(),
+        };
+        let mut entries = self.entries.lock();
+        entries.push_front(entry);
+        entries.truncate(max_len);
+    }
+
+    /// SLOWLOG GET [count]: the `count` newest entries, newest first.
+    pub fn entries(&self, count: usize) -> Vec<SlowLogEntry> {
+        self.entries.lock().iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<RespFrame> {
+        args.iter().map(|a| RespFrame::bulk(a.to_string())).collect()
+    }
+
+    #[test]
+    fn only_commands_at_or_over_the_threshold_are_logged() {
+        let client = Client::internal("test");
+        let log = SlowLog::new(1000, 128);
+        log.record(&client, &args(&["GET", "fast"]), Duration::from_micros(999));
+        log.record(&client, &args(&["GET", "slow"]), Duration::from_micros(1000));
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.entries(10)[0].args, vec![Bytes::from("GET"), Bytes::from("slow")]);
+        assert_eq!(log.entries(10)[0].duration_us, 1000);
+
+        // Negative turns the log off, 0 logs everything
+        log.configure(-1, 128);
+        log.record(&client, &args(&["GET", "slow"]), Duration::from_secs(1));
+        assert_eq!(log.len(), 1);
+        log.configure(0, 128);
+        log.record(&client, &args(&["GET", "fast"]), Duration::ZERO);
+        assert_eq!(log.len(), 2);
+    }
+
+    #[test]
+    fn max_len_keeps_the_newest_entries() {
+        let client = Client::internal("test");
+        let log = SlowLog::new(0, 2);
+        for key in ["a", "b", "c"] {
+            log.record(&client, &args(&["GET", key]), Duration::ZERO);
+        }
+        let ids: Vec<u64> = log.entries(10).iter().map(|e| e.id).collect();
+        assert_eq!(ids, [2, 1]);
+        log.configure(0, 1);
+        assert_eq!(log.len(), 1);
+        log.reset();
+        assert_eq!(log.len(), 0);
+    }
+
+    #[test]
+    fn long_commands_are_cut_down() {
+        let client = Client::internal("test");
+        let log = SlowLog::new(0, 128);
+        let long = "x".repeat(MAX_ARG_LEN + 10);
+        let mut many = vec!["DEL"; MAX_ARGC + 5];
+        many[1] = &long;
+        log.record(&client, &args(&many), Duration::ZERO);
+        let entry = &log.entries(1)[0];
+        assert_eq!(entry.args.len(), MAX_ARGC);
+        assert_eq!(entry.args[1], Bytes::from(format!("{}... (10 more bytes)", &long[..MAX_ARG_LEN])));
+        assert_eq!(entry.args[MAX_ARGC - 1], Bytes::from("... (6 more arguments)"));
+    }
+}
@@ -0,0 +1,78 @@
+use parking_lot::Mutex;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Samples averaged for instantaneous_ops_per_sec, as in Redis.
+const OPS_SAMPLES: usize = 16;
+
+/// Server-wide counters reported by INFO stats.
+#[derive(Default)]
+pub struct ServerStats {
+    pub total_connections_received: AtomicU64,
+    pub total_commands_processed: AtomicU64,
+    /// Connections refused because of maxclients.
+    pub rejected_connections: AtomicU64,
+    /// Clients closed for overcoming client-output-buffer-limit.
+    pub client_output_buffer_limit_disconnections: AtomicU64,
+    ops: Mutex<OpsMeter>,
+}
+
+/// Rolling commands-per-second rate fed by `sample_ops`.
+#[derive(Default)]
+struct OpsMeter {
+    last: Option<(Instant, u64)>,
+    rates: [u64; OPS_SAMPLES],
+    next: usize,
+}
+
+impl ServerStats {
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// CONFIG RESETSTAT
+    pub fn reset(&self) {
+        for counter in [
+            &self.total_connections_received,
+            &self.total_commands_processed,
+            &self.rejected_connections,
+            &self.client_output_buffer_limit_disconnections,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        *self.ops.lock() = OpsMeter::default();
+    }
+
+    /// Record one sample of the command rate; called by the server cron.
+    pub fn sample_ops(&self) {
+        let now = Instant::now();
+        let total = self.total_commands_processed.load(Ordering::Relaxed);
+        let mut ops = self.ops.lock();
+        if let Some((at, count)) = ops.last {
+            let ms = now.duration_since(at).as_millis().max(1) as u64;
+            let slot = ops.next;
+            ops.rates[slot] = total.saturating_sub(count) * 1000 / ms;
+            ops.next = (slot + 1) % OPS_SAMPLES;
+        }
+        ops.last = Some((now, total));
+    }
+
+    pub fn instantaneous_ops_per_sec(&self) -> u64 {
+        self.ops.lock().rates.iter().sum::<u64>() / OPS_SAMPLES as u64
+    }
+
+    /// The counters of the `# Stats` INFO section.
+    pub fn render(&self, out: &mut String) {
+        let get = |c: &AtomicU64| c.load(Ordering::Relaxed);
+        let _ = write!(
+            out,
+            "total_connections_received:{}\r\ntotal_commands_processed:{}\r\ninstantaneous_ops_per_sec:{}\r\nrejected_connections:{}\r\nclient_output_buffer_limit_disconnections:{}\r\n",
+            get(&self.total_connections_received),
+            get(&self.total_commands_processed),
+            self.instantaneous_ops_per_sec(),
+            get(&self.rejected_connections),
+            get(&self.client_output_buffer_limit_disconnections),
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use dashmap::DashMap;
 use hashbrown::HashMap;
 use std::collections::hash_map::DefaultHasher;
@@ -6,6 +7,38 @@ use crate::core::structs::zset::ZSet;
 use crate::core::structs::sso_string::ZedisString;
 use crate::core::structs::probabilistic::{HyperLogLogWrapper, CuckooFilterWrapper, TopKWrapper, CountMinSketchWrapper, TDigestWrapper};
 use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use dashmap::mapref::entry::Entry;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::core::expire::{now_ms, Expires};
+use crate::core::glob::glob_match;
+use crate::core::{dump, lazyfree, evict, notify};
+use crate::core::evict::{AccessTable, EvictionPolicy, Tracking};
+use crate::core::error::{DbResult, ZedisError};
+use rand::Rng;
+use std::collections::hash_map::RandomState;
+use std::sync::OnceLock;
+
+/// Hasher shared by every database's DashMap (see `Db::swap_contents`).
+fn shard_hasher() -> RandomState {
+    static HASHER: OnceLock<RandomState> = OnceLock::new();
+    HASHER.get_or_init(RandomState::new).clone()
+}
+
+/// RANDOMKEY probes up to ROUNDS batches of SAMPLES keys for a live one.
+const RANDOM_KEY_ROUNDS: usize = 16;
+const RANDOM_KEY_SAMPLES: usize = 8;
+
+/// SCAN cursors keep the shard index above this many position bits.
+const SCAN_POSITION_BITS: u32 = 48;
+const SCAN_POSITION_MASK: u64 = (1 << SCAN_POSITION_BITS) - 1;
+
+/// Order of a key within its shard for SCAN. Never 0, so the first cursor
+/// of shard 0 cannot be mistaken for the end of the scan.
+fn scan_position(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() >> (64 - SCAN_POSITION_BITS)).max(1)
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum BitType {
@@ -28,16 +61,110 @@ pub enum BitOverflow {
 
 /// The main Database structure - God Tier Lock-Free with DashMap
 pub struct Db {
-    data: DashMap<String, DataType>,
+    /// SELECT index; fixed for life since SWAPDB exchanges contents, not databases.
+    index: usize,
+    data: DashMap<Bytes, DataType>,
+    /// Deadlines of volatile keys. Lock order: a `data` shard, then `expires`
+    /// or `access`.
+    expires: Expires,
+    /// LRU/LFU statistics for maxmemory eviction.
+    access: AccessTable,
+    expired_keys: AtomicU64,
+    keyspace_hits: AtomicU64,
+    keyspace_misses: AtomicU64,
+}
+
+/// SET NX / XX
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetCondition {
+    Always,
+    IfMissing,
+    IfExists,
+}
+
+/// What SET does with the key's time to live.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetExpiry {
+    /// Plain SET: the key becomes persistent
+    Clear,
+    /// KEEPTTL
+    Keep,
+    /// EX / PX / EXAT / PXAT, as an absolute Unix ms deadline
+    At(u64),
 }
 
 impl Db {
-    pub fn new(_shard_count: usize) -> Self {
-        // DashMap handles sharding internally (default 64 shards)
-        // _shard_count parameter kept for API compatibility
+    /// Database `index` of the keyspace. Only db 0 gets the large
+    /// preallocation; the rest usually stay small or empty.
+    pub fn with_index(index: usize) -> Self {
+        Self::with_capacity(index, if index == 0 { 100_000 } else { 0 })
+    }
+
+    fn with_capacity(index: usize, capacity: usize) -> Self {
         Self {
-            data: DashMap::with_capacity(100_000),
+            index,
+            // One hasher for every database, so a key maps to the same shard
+            // everywhere and SWAPDB can exchange shards wholesale
+            data: DashMap::with_capacity_and_hasher(capacity, shard_hasher()),
+            expires: Expires::new(),
+            access: AccessTable::new(),
+            expired_keys: AtomicU64::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+
+    /// SWAPDB: exchange all keys and deadlines with `other`. Both databases
+    /// are write-locked for the duration, so no client sees a half swap.
+    pub fn swap_contents(&self, other: &Db) {
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        // Lock in address order so concurrent swaps cannot deadlock
+        let (first, second) = if (self as *const Db) < (other as *const Db) { (self, other) } else { (other, self) };
+        let mut a: Vec<_> = first.data.shards().iter().map(|s| s.write()).collect();
+        let mut b: Vec<_> = second.data.shards().iter().map(|s| s.write()).collect();
+        for (x, y) in a.iter_mut().zip(b.iter_mut()) {
+            std::mem::swap(&mut **x, &mut **y);
+        }
+        first.expires.swap_with(&second.expires);
+        first.access.swap_with(&second.access);
+    }
+
+    /// FLUSHDB. With `lazy` the old contents are released on the lazyfree thread.
+    pub fn flush(&self, lazy: bool) {
+        let mut shards: Vec<_> = self.data.shards().iter().map(|s| s.write()).collect();
+        for shard in shards.iter_mut() {
+            let old = std::mem::take(&mut **shard);
+            if lazy {
+                lazyfree::free_later(old);
+            }
+        }
+        self.expires.clear();
+        self.access.clear();
+    }
+
+    /// Rebuild from a snapshot's keys and deadlines, sized for what was
+    /// loaded. The index is set by `Keyspace::from_snapshot`.
+    pub fn from_parts(map: HashMap<Bytes, DataType>, expires: HashMap<Bytes, u64>) -> Self {
+        let db = Self::with_capacity(0, map.len());
+        for (k, v) in map {
+            db.data.insert(k, v);
+        }
+        for (k, at) in expires {
+            if db.data.contains_key(&k) {
+                db.expires.set(k, at);
+            }
         }
+        db
     }
 }
 
@@ -47,11 +174,15 @@ impl Serialize for Db {
     where
         S: Serializer,
     {
-        // Collect to HashMap for serialization
-        let snapshot: HashMap<String, DataType> = self.data.iter()
+        // Collect to HashMap for serialization; keys already past their
+        // deadline are left out
+        let now = now_ms();
+        let expires: HashMap<Bytes, u64> = self.expires.snapshot().into_iter().filter(|(_, at)| *at > now).collect();
+        let snapshot: HashMap<Bytes, DataType> = self.data.iter()
+            .filter(|entry| self.expires.get(entry.key()).is_none_or(|at| at > now))
             .map(|entry| (entry.key().clone(), entry.value().clone()))
             .collect();
-        snapshot.serialize(serializer)
+        (snapshot, expires).serialize(serializer)
     }
 }
 
@@ -60,12 +191,8 @@ impl<'de> Deserialize<'de> for Db {
     where
         D: Deserializer<'de>,
     {
-        let map: HashMap<String, DataType> = Deserialize::deserialize(deserializer)?;
-        let data = DashMap::with_capacity(map.len());
-        for (k, v) in map {
-            data.insert(k, v);
-        }
-        Ok(Db { data })
+        let (map, expires): (HashMap<Bytes, DataType>, HashMap<Bytes, u64>) = Deserialize::deserialize(deserializer)?;
+        Ok(Db::from_parts(map, expires))
     }
 }
 
@@ -77,85 +204,497 @@ impl Db {
          // DashMap handles this internally, no manual shard index
     }
 
+    /// Keyspace notification for `key` in this database.
+    fn notify(&self, class: u32, event: &str, key: &[u8]) {
+        notify::keyspace_event(class, event, self.index, key);
+    }
+
+    /// Lazy expiry: drop `key` if its deadline has passed. True if it was removed.
+    /// Every key lookup goes through here, so it also feeds LRU/LFU tracking.
+    fn expire_if_needed(&self, key: &[u8]) -> bool {
+        let now = now_ms();
+        match self.expires.get(key) {
+            Some(at) if at <= now => {}
+            _ => {
+                let mode = evict::tracking();
+                if mode != Tracking::Off {
+                    if let Some(entry) = self.data.get(key) {
+                        self.access.touch(entry.key(), mode);
+                    }
+                }
+                return false;
+            }
+        }
+        // Re-check under the data shard lock so a concurrent SET that just
+        // replaced the key (and its deadline) is not deleted
+        let removed = self.data.remove_if(key, |_, _| self.expires.remove_if_expired(key, now)).is_some();
+        if removed {
+            self.access.remove(key);
+            self.expired_keys.fetch_add(1, Ordering::Relaxed);
+            self.notify(notify::EXPIRED, "expired", key);
+        }
+        removed
+    }
+
+    /// Lookup for a read command: lazy expiry, then a keyspace hit or miss.
+    fn lookup_read(&self, key: &[u8]) -> Option<dashmap::mapref::one::Ref<'_, Bytes, DataType>> {
+        self.expire_if_needed(key);
+        let entry = self.data.get(key);
+        let counter = if entry.is_some() { &self.keyspace_hits } else { &self.keyspace_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+
+    /// Best of `samples` random keys to evict under `policy`, with its score.
+    pub fn eviction_candidate(&self, policy: EvictionPolicy, samples: usize) -> Option<(u64, Bytes)> {
+        let pool = if policy.volatile() { self.volatile_len() } else { self.data.len() };
+        if pool == 0 {
+            return None;
+        }
+        let sampled: Vec<(Bytes, Option<u64>)> = if policy.volatile() {
+            self.expires.sample(samples).into_iter().map(|(k, at)| (k, Some(at))).collect()
+        } else {
+            self.sample_keys(samples).into_iter().map(|k| (k, None)).collect()
+        };
+        sampled.into_iter()
+            .map(|(key, at)| (evict::score(policy, &self.access, &key, at), key))
+            .max_by_key(|(score, _)| *score)
+    }
+
+    #[cfg(test)]
+    pub fn access(&self) -> &AccessTable {
+        &self.access
+    }
+
+    /// Up to `n` random keys, possibly repeated.
+    fn sample_keys(&self, n: usize) -> Vec<Bytes> {
+        let shards = self.data.shards();
+        let mut rng = rand::thread_rng();
+        let mut keys = Vec::with_capacity(n);
+        for _ in 0..n * 4 {
+            if keys.len() == n {
+                break;
+            }
+            let guard = shards[rng.gen_range(0..shards.len())].read();
+            if guard.is_empty() {
+                continue;
+            }
+            // Walk from a random bucket to the next occupied one, like
+            // Redis's dictGetSomeKeys
+            let table = guard.raw_table();
+            let buckets = table.buckets();
+            let start = rng.gen_range(0..buckets);
+            // SAFETY: indexes are below `buckets()` and the shard is read-locked
+            let full = (0..buckets).map(|i| (start + i) % buckets).find(|&i| unsafe { table.is_bucket_full(i) });
+            if let Some(i) = full {
+                keys.push(unsafe { table.bucket(i).as_ref() }.0.clone());
+            }
+        }
+        // A few keys spread over many shards: random probes can all miss
+        if keys.is_empty() {
+            let start = rng.gen_range(0..shards.len());
+            keys.extend((0..shards.len())
+                .find_map(|i| shards[(start + i) % shards.len()].read().keys().next().cloned()));
+        }
+        keys
+    }
+
+    /// Remove a key chosen by maxmemory eviction. Freed inline so used
+    /// memory drops before the next check.
+    pub fn evict(&self, key: &[u8]) -> bool {
+        match self.data.remove(key) {
+            Some(_) => {
+                self.expires.remove(key);
+                self.access.remove(key);
+                self.notify(notify::EVICTED, "evicted", key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// One round of active expiry: walk deadline shards starting at `cursor`
+    /// and delete what has expired, until `budget` runs out or a shard turns
+    /// up mostly live keys. Returns the next cursor and the keys removed.
+    pub fn active_expire_cycle(&self, mut cursor: usize, budget: std::time::Duration) -> (usize, usize) {
+        let started = std::time::Instant::now();
+        let shards = self.expires.shard_count();
+        let mut removed = 0;
+        for _ in 0..shards {
+            let (expired, volatile) = self.expires.expired_in_shard(cursor, now_ms());
+            cursor = (cursor + 1) % shards;
+            for key in &expired {
+                if self.expire_if_needed(key) {
+                    removed += 1;
+                }
+            }
+            // Like Redis: keep going only while more than 10% of the
+            // volatile keys looked at were stale
+            if (volatile > 0 && expired.len() * 10 < volatile) || started.elapsed() >= budget {
+                break;
+            }
+        }
+        (cursor, removed)
+    }
+
+    /// Keys removed by lazy or active expiry since startup.
+    pub fn expired_keys(&self) -> u64 {
+        self.expired_keys.load(Ordering::Relaxed)
+    }
+
+    /// Keys with a time to live.
+    pub fn volatile_len(&self) -> usize {
+        self.expires.len()
+    }
+
+    /// Read lookups that found / did not find their key (INFO stats).
+    pub fn keyspace_hits(&self) -> u64 {
+        self.keyspace_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_misses(&self) -> u64 {
+        self.keyspace_misses.load(Ordering::Relaxed)
+    }
+
+    /// CONFIG RESETSTAT
+    pub fn reset_stats(&self) {
+        for counter in [&self.expired_keys, &self.keyspace_hits, &self.keyspace_misses] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Average remaining TTL in ms over a sample of volatile keys (INFO keyspace).
+    pub fn avg_ttl(&self) -> u64 {
+        let now = now_ms();
+        let sample = self.expires.sample(16);
+        if sample.is_empty() {
+            return 0;
+        }
+        sample.iter().map(|(_, at)| at.saturating_sub(now)).sum::<u64>() / sample.len() as u64
+    }
+
     /// Set a String key (lock-free)
-    pub fn set_string(&self, key: String, value: String) {
-        self.data.insert(key, DataType::String(ZedisString::new(&value)));
+    pub fn set_string(&self, key: Bytes, value: Bytes) {
+        self.expire_if_needed(&key);
+        self.expires.remove(&key);
+        self.data.insert(key.clone(), DataType::String(ZedisString::from_bytes(value)));
+        self.notify(notify::STRING, "set", &key);
     }
 
-    /// Get a String key (lock-free)
-    pub fn get_string(&self, key: &str) -> Option<String> {
-        self.data.get(key).and_then(|entry| {
-            match entry.value() {
-                DataType::String(s) => Some(s.to_string()),
-                _ => None,
+    /// SET with NX/XX, GET and expiry options. Returns whether the value was
+    /// written and, with `get_old`, the previous string value.
+    pub fn set_with(&self, key: Bytes, value: Bytes, cond: SetCondition, expiry: SetExpiry, get_old: bool) -> DbResult<(bool, Option<Bytes>)> {
+        self.expire_if_needed(&key);
+        let new_value = DataType::String(ZedisString::from_bytes(value));
+        let (guard, old) = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut e) => {
+                let old = match e.get() {
+                    DataType::String(s) => Some(s.to_bytes()),
+                    _ if get_old => return Err(ZedisError::WrongType),
+                    _ => None,
+                };
+                if cond == SetCondition::IfMissing {
+                    return Ok((false, old));
+                }
+                e.insert(new_value);
+                (e, old)
+            }
+            Entry::Vacant(e) => {
+                if cond == SetCondition::IfExists {
+                    return Ok((false, None));
+                }
+                (e.insert_entry(new_value), None)
             }
+        };
+        // Deadline changes while the entry is still locked
+        match expiry {
+            SetExpiry::Clear => { self.expires.remove(&key); }
+            SetExpiry::Keep => {}
+            SetExpiry::At(at) => self.expires.set(key.clone(), at),
+        }
+        drop(guard);
+        self.notify(notify::STRING, "set", &key);
+        if let SetExpiry::At(_) = expiry {
+            self.notify(notify::GENERIC, "expire", &key);
+        }
+        Ok((true, old))
+    }
+
+    /// Get a String key (lock-free)
+    pub fn get_string(&self, key: &[u8]) -> DbResult<Option<Bytes>> {
+        self.lookup_read(key).map_or(Ok(None), |entry| match entry.value() {
+            DataType::String(s) => Ok(Some(s.to_bytes())),
+            _ => Err(ZedisError::WrongType),
         })
     }
 
     /// Delete a key (lock-free)
-    pub fn del(&self, key: &str) -> bool {
-        self.data.remove(key).is_some()
+    pub fn del(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        match self.data.remove(key) {
+            Some(_) => {
+                self.expires.remove(key);
+                self.access.remove(key);
+                self.notify(notify::GENERIC, "del", key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// UNLINK: remove the key now, release its memory in the background.
+    pub fn unlink(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        match self.data.remove(key) {
+            Some((_, value)) => {
+                self.expires.remove(key);
+                self.access.remove(key);
+                lazyfree::free(value);
+                self.notify(notify::GENERIC, "del", key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// RENAME / RENAMENX. The TTL moves with the value. `Ok(false)` when
+    /// `nx` is set and `dst` already exists.
+    pub fn rename(&self, src: &[u8], dst: Bytes, nx: bool) -> DbResult<bool> {
+        self.expire_if_needed(src);
+        self.expire_if_needed(&dst);
+        if !self.data.contains_key(src) {
+            return Err(ZedisError::NoSuchKey);
+        }
+        if src == &dst[..] {
+            return Ok(!nx);
+        }
+        if nx && self.data.contains_key(&dst) {
+            return Ok(false);
+        }
+        let Some((_, value)) = self.data.remove(src) else {
+            return Err(ZedisError::NoSuchKey);
+        };
+        let ttl = self.expires.remove(src);
+        self.access.remove(src);
+        // Never hold two shard locks at once: src and dst may share a shard
+        let old = self.data.insert(dst.clone(), value);
+        self.restore_ttl(dst.clone(), ttl);
+        if let Some(old) = old {
+            lazyfree::free(old);
+        }
+        self.notify(notify::GENERIC, "rename_from", src);
+        self.notify(notify::GENERIC, "rename_to", &dst);
+        Ok(true)
+    }
+
+    /// COPY src dst [DB target] [REPLACE]. False if `src` is missing or
+    /// `dst` exists in `target` without `replace`.
+    pub fn copy(&self, src: &[u8], target: &Db, dst: Bytes, replace: bool) -> bool {
+        self.expire_if_needed(src);
+        target.expire_if_needed(&dst);
+        if std::ptr::eq(self, target) && src == &dst[..] {
+            return false;
+        }
+        let Some(value) = self.data.get(src).map(|v| v.value().clone()) else {
+            return false;
+        };
+        let ttl = self.expires.get(src);
+        match target.data.entry(dst.clone()) {
+            Entry::Occupied(mut e) => {
+                if !replace {
+                    return false;
+                }
+                let old = e.insert(value);
+                target.restore_ttl(dst.clone(), ttl);
+                drop(e);
+                lazyfree::free(old);
+            }
+            Entry::Vacant(e) => {
+                let _guard = e.insert(value);
+                target.restore_ttl(dst.clone(), ttl);
+            }
+        }
+        target.notify(notify::GENERIC, "copy_to", &dst);
+        true
+    }
+
+    /// MOVE key to `target`, TTL included. False if the key is missing here
+    /// or already exists there.
+    pub fn move_key(&self, key: &[u8], target: &Db) -> bool {
+        if std::ptr::eq(self, target) {
+            return false;
+        }
+        self.expire_if_needed(key);
+        target.expire_if_needed(key);
+        if !self.data.contains_key(key) || target.data.contains_key(key) {
+            return false;
+        }
+        let Some((key, value)) = self.data.remove(key) else { return false };
+        let ttl = self.expires.remove(&key);
+        self.access.remove(&key);
+        // Only one database is locked at a time; if the target key appeared
+        // in between, the value goes back where it came from
+        match target.data.entry(key.clone()) {
+            Entry::Vacant(e) => {
+                let _guard = e.insert(value);
+                target.restore_ttl(key.clone(), ttl);
+                self.notify(notify::GENERIC, "move_from", &key);
+                target.notify(notify::GENERIC, "move_to", &key);
+                true
+            }
+            Entry::Occupied(e) => {
+                drop(e);
+                let _guard = self.data.entry(key.clone()).or_insert(value);
+                self.restore_ttl(key, ttl);
+                false
+            }
+        }
+    }
+
+    /// Point the key's deadline at `ttl`, or make it persistent.
+    fn restore_ttl(&self, key: Bytes, ttl: Option<u64>) {
+        match ttl {
+            Some(at) => self.expires.set(key, at),
+            None => { self.expires.remove(&key); }
+        }
+    }
+
+    /// Serialized value for DUMP, None if the key is missing.
+    pub fn dump(&self, key: &[u8]) -> Option<Result<Vec<u8>, String>> {
+        self.lookup_read(key).map(|v| dump::dump_value(v.value()))
+    }
+
+    /// RESTORE a DUMP payload under `key` with an optional absolute deadline.
+    pub fn restore(&self, key: Bytes, payload: &[u8], at: Option<u64>, replace: bool) -> DbResult<()> {
+        let value = dump::restore_value(payload).map_err(ZedisError::err)?;
+        self.expire_if_needed(&key);
+        match self.data.entry(key.clone()) {
+            Entry::Occupied(mut e) => {
+                if !replace {
+                    return Err(ZedisError::BusyKey);
+                }
+                let old = e.insert(value);
+                self.restore_ttl(key.clone(), at);
+                drop(e);
+                lazyfree::free(old);
+            }
+            Entry::Vacant(e) => {
+                let _guard = e.insert(value);
+                self.restore_ttl(key.clone(), at);
+            }
+        }
+        self.notify(notify::GENERIC, "restore", &key);
+        Ok(())
     }
 
     /// Check existence (lock-free)
-    pub fn exists(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+    pub fn exists(&self, key: &[u8]) -> bool {
+        self.lookup_read(key).is_some()
     }
 
-    /// TTL Check (MVP: Always -1 for existing keys, -2 for missing)
-    pub fn get_ttl(&self, key: &str) -> i64 {
-        if self.data.contains_key(key) {
-            -1 // Persistent
-        } else {
-            -2 // Key does not exist
+    /// Absolute deadline in Unix ms (EXPIRETIME / PEXPIRETIME): -2 for a
+    /// missing key, -1 for a persistent one.
+    pub fn expire_time(&self, key: &[u8]) -> i64 {
+        self.expire_if_needed(key);
+        let Some(_entry) = self.data.get(key) else { return -2 };
+        self.expires.get(key).map_or(-1, |at| at as i64)
+    }
+
+    /// Remaining time to live in ms (PTTL): -2 missing, -1 persistent.
+    pub fn pttl(&self, key: &[u8]) -> i64 {
+        match self.expire_time(key) {
+            at if at < 0 => at,
+            at => (at - now_ms() as i64).max(0),
+        }
+    }
+
+    /// Give an existing key a deadline (EXPIRE family). A deadline in the
+    /// past deletes the key. False if there is no such key.
+    pub fn set_expire(&self, key: &[u8], at: u64) -> bool {
+        self.expire_if_needed(key);
+        if at <= now_ms() {
+            // Like Redis, a deadline already past is a DEL
+            return self.del(key);
+        }
+        let Some(entry) = self.data.get(key) else { return false };
+        self.expires.set(entry.key().clone(), at);
+        drop(entry);
+        self.notify(notify::GENERIC, "expire", key);
+        true
+    }
+
+    /// PERSIST: drop the deadline. False if the key is missing or had none.
+    pub fn persist(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        let Some(entry) = self.data.get(key) else { return false };
+        let persisted = self.expires.remove(key).is_some();
+        drop(entry);
+        if persisted {
+            self.notify(notify::GENERIC, "persist", key);
         }
+        persisted
     }
 
     /// Increment a key (INCR/INCRBY) - atomic via entry API
-    pub fn incr_by(&self, key: String, amount: i64) -> Result<i64, String> {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::String(ZedisString::new("0")));
+    pub fn incr_by(&self, key: Bytes, amount: i64) -> DbResult<i64> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::String(ZedisString::new(b"0")));
         
         if let DataType::String(s) = entry.value_mut() {
-            let mut int_val = s.to_string().parse::<i64>().map_err(|_| "ERR value is not an integer or out of range".to_string())?;
-            int_val += amount;
-            *s = ZedisString::new(&int_val.to_string());
+            let int_val = std::str::from_utf8(s.as_bytes()).ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .ok_or(ZedisError::NotInteger)?
+                .checked_add(amount)
+                .ok_or(ZedisError::Overflow)?;
+            *s = ZedisString::new(int_val.to_string().as_bytes());
+            drop(entry);
+            self.notify(notify::STRING, "incrby", &key);
             Ok(int_val)
         } else {
-            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+            Err(ZedisError::WrongType)
         }
     }
 
     /// Push to a List (RPUSH)
-    pub fn list_push(&self, key: String, value: String) -> usize {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::List(Vec::new()));
+    pub fn list_push(&self, key: Bytes, value: Bytes) -> DbResult<usize> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::List(Vec::new()));
         
-        match entry.value_mut() {
+        let len = match entry.value_mut() {
             DataType::List(list) => {
                 list.push(value);
                 list.len()
             }
-            _ => 0,
-        }
+            _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        self.notify(notify::LIST, "rpush", &key);
+        Ok(len)
     }
 
     /// Pop from a List (LPOP)
-    pub fn list_pop(&self, key: &str) -> Option<String> {
-        self.data.get_mut(key).and_then(|mut entry| {
-            if let DataType::List(list) = entry.value_mut() {
-                if list.is_empty() { return None; }
-                Some(list.remove(0))
-            } else {
-                None
-            }
-        })
+    pub fn list_pop(&self, key: &[u8]) -> DbResult<Option<Bytes>> {
+        self.expire_if_needed(key);
+        let popped = match self.data.get_mut(key) {
+            None => None,
+            Some(mut entry) => match entry.value_mut() {
+                DataType::List(list) if list.is_empty() => None,
+                DataType::List(list) => Some(list.remove(0)),
+                _ => return Err(ZedisError::WrongType),
+            },
+        };
+        if popped.is_some() {
+            self.notify(notify::LIST, "lpop", key);
+        }
+        Ok(popped)
     }
 
     /// Range of a List (LRANGE)
-    pub fn list_range(&self, key: &str, start: i64, stop: i64) -> Vec<String> {
-        self.data.get(key).map(|entry| {
+    pub fn list_range(&self, key: &[u8], start: i64, stop: i64) -> DbResult<Vec<Bytes>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             if let DataType::List(list) = entry.value() {
                 let len = list.len() as i64;
-                if len == 0 { return Vec::new(); }
+                if len == 0 { return Ok(Vec::new()); }
 
                 let mut start_idx = if start < 0 { len + start } else { start };
                 if start_idx < 0 { start_idx = 0; }
@@ -163,79 +702,109 @@ impl Db {
                 let mut stop_idx = if stop < 0 { len + stop } else { stop };
                 if stop_idx < 0 { stop_idx = 0; }
                 
-                if start_idx >= len { return Vec::new(); }
+                if start_idx >= len { return Ok(Vec::new()); }
                 if stop_idx >= len { stop_idx = len - 1; }
-                if start_idx > stop_idx { return Vec::new(); }
+                if start_idx > stop_idx { return Ok(Vec::new()); }
 
                 let start_u = start_idx as usize;
                 let stop_u = stop_idx as usize;
-                if start_u > stop_u { return Vec::new(); }
+                if start_u > stop_u { return Ok(Vec::new()); }
                 
-                list[start_u..=stop_u].to_vec()
+                Ok(list[start_u..=stop_u].to_vec())
             } else {
-                Vec::new()
+                Err(ZedisError::WrongType)
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// Set a Field in a Hash (HSET)
-    pub fn hash_set(&self, key: String, field: String, value: String) -> usize {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::Hash(HashMap::new()));
+    pub fn hash_set(&self, key: Bytes, field: Bytes, value: Bytes) -> DbResult<usize> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::Hash(HashMap::new()));
         
-        match entry.value_mut() {
+        let added = match entry.value_mut() {
             DataType::Hash(map) => {
                 if map.insert(field, value).is_some() { 0 } else { 1 }
             }
-            _ => 0,
-        }
+            _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        self.notify(notify::HASH, "hset", &key);
+        Ok(added)
     }
 
     /// Get a Field from a Hash (HGET)
-    pub fn hash_get(&self, key: &str, field: &str) -> Option<String> {
-        self.data.get(key).and_then(|entry| {
+    pub fn hash_get(&self, key: &[u8], field: &[u8]) -> DbResult<Option<Bytes>> {
+        self.lookup_read(key).map_or(Ok(None), |entry| {
+            match entry.value() {
+                 DataType::Hash(map) => Ok(map.get(field).cloned()),
+                 _ => Err(ZedisError::WrongType),
+            }
+        })
+    }
+
+    /// HGETALL key
+    pub fn hash_getall(&self, key: &[u8]) -> DbResult<Vec<(Bytes, Bytes)>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                 DataType::Hash(map) => map.get(field).cloned(),
-                 _ => None,
+                DataType::Hash(map) => Ok(map.iter().map(|(f, v)| (f.clone(), v.clone())).collect()),
+                _ => Err(ZedisError::WrongType),
             }
         })
     }
 
     /// ZADD key score member
-    pub fn zadd(&self, key: String, score: f64, member: String) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::ZSet(ZSet::new()));
+    pub fn zadd(&self, key: Bytes, score: f64, member: Bytes) -> DbResult<bool> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::ZSet(ZSet::new()));
         
-        match entry.value_mut() {
+        let added = match entry.value_mut() {
             DataType::ZSet(z) => z.add(score, member),
-            _ => false,
-        }
+            _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        self.notify(notify::ZSET, "zadd", &key);
+        Ok(added)
     }
 
     /// ZRANGE key start stop
-    pub fn zrange(&self, key: &str, start: usize, end: usize) -> Vec<String> {
-        self.data.get(key).map(|entry| {
+    pub fn zrange(&self, key: &[u8], start: usize, end: usize) -> DbResult<Vec<Bytes>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
+            match entry.value() {
+                DataType::ZSet(z) => Ok(z.range(start, end)),
+                _ => Err(ZedisError::WrongType),
+            }
+        })
+    }
+    
+    /// ZRANGE key start stop WITHSCORES
+    pub fn zrange_withscores(&self, key: &[u8], start: usize, end: usize) -> DbResult<Vec<(Bytes, f64)>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                DataType::ZSet(z) => z.range(start, end),
-                _ => Vec::new(),
+                DataType::ZSet(z) => Ok(z.range_with_scores(start, end)),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
     
     /// BITCOUNT key
-    pub fn bitcount(&self, key: &str) -> usize {
-        self.data.get(key).map(|entry| {
+    pub fn bitcount(&self, key: &[u8]) -> DbResult<usize> {
+        self.lookup_read(key).map_or(Ok(0), |entry| {
              match entry.value() {
-                DataType::String(s) => s.as_str().as_bytes().iter().map(|b| b.count_ones() as usize).sum(),
-                _ => 0,
+                DataType::String(s) => Ok(s.as_bytes().iter().map(|b| b.count_ones() as usize).sum()),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or(0)
+        })
     }
 
     /// BITFIELD key
-    pub fn bitfield(&self, key: String, ops: Vec<BitfieldOp>, overflow: Vec<BitOverflow>) -> Vec<Option<i64>> {
-         let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::String(ZedisString::new("")));
+    pub fn bitfield(&self, key: Bytes, ops: Vec<BitfieldOp>, overflow: Vec<BitOverflow>) -> DbResult<Vec<Option<i64>>> {
+        self.expire_if_needed(&key);
+         let writes = ops.iter().any(|op| !matches!(op, BitfieldOp::Get(..)));
+         let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::String(ZedisString::new(b"")));
          
          if let DataType::String(ref mut s) = entry.value_mut() {
-             let mut bytes = s.to_string().into_bytes();
+             let mut bytes = s.as_bytes().to_vec();
              
              let mut results = Vec::new();
              let mut current_overflow = BitOverflow::Wrap;
@@ -268,13 +837,15 @@ impl Db {
                  }
              }
              
-             unsafe {
-                  *s = ZedisString::new(&String::from_utf8_unchecked(bytes));
+             *s = ZedisString::from_bytes(Bytes::from(bytes));
+             drop(entry);
+             if writes {
+                 self.notify(notify::STRING, "setbit", &key);
              }
              
-             results
+             Ok(results)
          } else {
-             vec![]
+             Err(ZedisError::WrongType)
          }
     }
 
@@ -371,305 +942,479 @@ impl Db {
 
 
     /// GEOADD key longitude latitude member
-    pub fn geoadd(&self, key: String, lon: f64, lat: f64, member: String) -> bool {
+    pub fn geoadd(&self, key: Bytes, lon: f64, lat: f64, member: Bytes) -> DbResult<bool> {
          let score = lon + lat; 
          self.zadd(key, score, member)
     }
 
     /// SADD key member
-    pub fn sadd(&self, key: String, member: String) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::Set(hashbrown::HashSet::new()));
+    pub fn sadd(&self, key: Bytes, member: Bytes) -> DbResult<bool> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::Set(hashbrown::HashSet::new()));
         
-        match entry.value_mut() {
+        let added = match entry.value_mut() {
             DataType::Set(s) => s.insert(member),
-            _ => false,
+            _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        if added {
+            self.notify(notify::SET, "sadd", &key);
         }
+        Ok(added)
     }
 
     /// SMEMBERS key
-    pub fn smembers(&self, key: &str) -> Vec<String> {
-        self.data.get(key).map(|entry| {
+    pub fn smembers(&self, key: &[u8]) -> DbResult<Vec<Bytes>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                DataType::Set(s) => s.iter().cloned().collect(),
-                _ => Vec::new(),
+                DataType::Set(s) => Ok(s.iter().cloned().collect()),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// XADD key ID field value ...
-    pub fn xadd(&self, key: String, id: Option<&str>, fields: HashMap<String, String>) -> String {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::Stream(crate::core::structs::stream::Stream::new()));
+    pub fn xadd(&self, key: Bytes, id: Option<&str>, fields: HashMap<Bytes, Bytes>) -> DbResult<String> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::Stream(crate::core::structs::stream::Stream::new()));
         
-        match entry.value_mut() {
+        let id = match entry.value_mut() {
             DataType::Stream(s) => s.add(id, fields),
-            _ => "ERR".to_string(),
+            _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        let id = match id {
+            Ok(id) => id,
+            Err(e) => {
+                // Don't leave behind the stream created for a rejected entry
+                self.data.remove_if(&key, |_, v| matches!(v, DataType::Stream(s) if s.is_empty()));
+                return Err(e);
+            }
+        };
+        self.notify(notify::STREAM, "xadd", &key);
+        Ok(id)
+    }
+
+    /// Vector keys are grouped into one index per `prefix:` (whole key when there is no colon)
+    fn vector_index_name(key: &Bytes) -> Bytes {
+        match key.iter().position(|b| *b == b':') {
+            Some(pos) => key.slice(..pos),
+            None => key.clone(),
         }
     }
 
     /// VADD key vector
-    pub fn vadd(&self, key: String, vector: Vec<f32>) -> bool {
-        let index_name = if let Some(pos) = key.find(':') {
-            key[..pos].to_string()
-        } else {
-            key.clone()
-        };
+    pub fn vadd(&self, key: Bytes, vector: Vec<f32>) -> DbResult<()> {
+        let index_name = Self::vector_index_name(&key);
+        self.expire_if_needed(&index_name);
         
-        let mut entry = self.data.entry(index_name).or_insert_with(|| 
+        let mut entry = self.data.entry(index_name.clone()).or_insert_with(|| 
             DataType::Vector(crate::core::structs::vector::VectorIndex::new(vector.len()))
         );
         match entry.value_mut() {
             DataType::Vector(v) => {
                 let dense_f16: Vec<half::f16> = vector.iter().map(|x| half::f16::from_f32(*x)).collect();
-                v.add(key, dense_f16, None).is_ok()
+                v.add(String::from_utf8_lossy(&key).into_owned(), dense_f16, None).map_err(ZedisError::err)?
             },
-            _ => false,
+            _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        // The event names the index key, which is what holds the data
+        self.notify(notify::VECTOR, "vadd", &index_name);
+        Ok(())
     }
     
     /// BF.ADD
-    pub fn bf_add(&self, key: String, item: String) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| 
+    pub fn bf_add(&self, key: Bytes, item: &[u8]) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| 
             DataType::Bloom(crate::core::structs::bloom::BloomFilter::new(1024, 3))
         );
         match entry.value_mut() {
-             DataType::Bloom(b) => { b.insert(&item); true },
-             _ => false,
+             DataType::Bloom(b) => b.insert(item),
+             _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::SKETCH, "bf.add", &key);
+        Ok(())
     }
 
-    /// JSON.SET
-    pub fn json_set(&self, key: String, json: String) -> bool {
-        if let Some(doc) = crate::core::structs::json::JsonDoc::new(&json) {
-            self.data.insert(key, DataType::Json(doc));
-            true
-        } else {
-            false
+    /// JSON.SET: replaces a JSON document, but not a key of another type.
+    pub fn json_set(&self, key: Bytes, json: &str) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let doc = crate::core::structs::json::JsonDoc::new(json).ok_or_else(|| ZedisError::err("invalid JSON document"))?;
+        match self.data.entry(key.clone()) {
+            Entry::Occupied(mut e) => match e.get() {
+                DataType::Json(_) => { e.insert(DataType::Json(doc)); }
+                _ => return Err(ZedisError::WrongType),
+            },
+            Entry::Vacant(e) => { e.insert(DataType::Json(doc)); }
         }
+        self.notify(notify::JSON, "json.set", &key);
+        Ok(())
     }
 
     /// VSEARCH
-    pub fn vsearch(&self, index_name: &str, query: Vec<f32>, k: usize) -> Vec<(String, f32)> {
-        self.data.get(index_name).map(|entry| {
+    pub fn vsearch(&self, index_name: &[u8], query: Vec<f32>, k: usize) -> DbResult<Vec<(String, f32)>> {
+        self.lookup_read(index_name).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
                  DataType::Vector(v) => {
                      let q_half: Vec<half::f16> = query.iter().map(|f| half::f16::from_f32(*f)).collect();
-                     v.search_hybrid(&q_half, None, k, 1.0)
+                     Ok(v.search_hybrid(&q_half, None, k, 1.0))
                  },
-                _ => Vec::new(),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// VADD.M3 (Hybrid)
-    pub fn vadd_hybrid(&self, key: String, dense: Vec<half::f16>, sparse: Option<Vec<(u32, f32)>>) -> bool {
-        let index_name = if let Some(pos) = key.find(':') {
-            key[..pos].to_string()
-        } else {
-            key.clone()
-        };
+    pub fn vadd_hybrid(&self, key: Bytes, dense: Vec<half::f16>, sparse: Option<Vec<(u32, f32)>>) -> DbResult<()> {
+        let index_name = Self::vector_index_name(&key);
+        self.expire_if_needed(&index_name);
         
-        let mut entry = self.data.entry(index_name).or_insert_with(|| 
+        let mut entry = self.data.entry(index_name.clone()).or_insert_with(|| 
             DataType::Vector(crate::core::structs::vector::VectorIndex::new(dense.len()))
         );
         match entry.value_mut() {
-            DataType::Vector(v) => v.add(key, dense, sparse).is_ok(),
-            _ => false,
+            DataType::Vector(v) => v.add(String::from_utf8_lossy(&key).into_owned(), dense, sparse).map_err(ZedisError::err)?,
+            _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::VECTOR, "vadd", &index_name);
+        Ok(())
     }
 
     /// VSEARCH.HYBRID (Hybrid)
-    pub fn vsearch_hybrid(&self, key: &str, dense: Vec<half::f16>, sparse: Option<Vec<(u32, f32)>>, k: usize, alpha: f32) -> Vec<(String, f32)> {
-        self.data.get(key).map(|entry| {
+    pub fn vsearch_hybrid(&self, key: &[u8], dense: Vec<half::f16>, sparse: Option<Vec<(u32, f32)>>, k: usize, alpha: f32) -> DbResult<Vec<(String, f32)>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                DataType::Vector(v) => v.search_hybrid(&dense, sparse.as_deref(), k, alpha),
-                _ => Vec::new(),
+                DataType::Vector(v) => Ok(v.search_hybrid(&dense, sparse.as_deref(), k, alpha)),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// BF.EXISTS key item
-    pub fn bf_exists(&self, key: &str, item: &str) -> bool {
-        self.data.get(key).map(|entry| {
+    pub fn bf_exists(&self, key: &[u8], item: &[u8]) -> DbResult<bool> {
+        self.lookup_read(key).map_or(Ok(false), |entry| {
             match entry.value() {
-                DataType::Bloom(b) => b.contains(item),
-                _ => false,
+                DataType::Bloom(b) => Ok(b.contains(item)),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or(false)
+        })
     }
 
     /// JSON.GET key path
-    pub fn json_get(&self, key: &str, path: &str) -> Option<String> {
-        self.data.get(key).and_then(|entry| {
+    pub fn json_get(&self, key: &[u8], path: &str) -> DbResult<Option<String>> {
+        self.lookup_read(key).map_or(Ok(None), |entry| {
             match entry.value() {
-                 DataType::Json(doc) => doc.get(path),
-                 _ => None,
+                 DataType::Json(doc) => Ok(doc.get(path)),
+                 _ => Err(ZedisError::WrongType),
             }
         })
     }
     
     /// TS.ADD key ts value
-    pub fn ts_add(&self, key: String, ts: u64, val: f64) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::TimeSeries(crate::core::universe::TimeSeries::new()));
+    pub fn ts_add(&self, key: Bytes, ts: u64, val: f64) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::TimeSeries(crate::core::universe::TimeSeries::new()));
         match entry.value_mut() {
-            DataType::TimeSeries(t) => { t.add(ts, val); true },
-            _ => false,
+            DataType::TimeSeries(t) => t.add(ts, val),
+            _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::TIMESERIES, "ts.add", &key);
+        Ok(())
     }
 
     /// TS.RANGE key min max
-    pub fn ts_range(&self, key: &str, min: u64, max: u64) -> Vec<(u64, f64)> {
-        self.data.get(key).map(|entry| {
+    pub fn ts_range(&self, key: &[u8], min: u64, max: u64) -> DbResult<Vec<(u64, f64)>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                DataType::TimeSeries(t) => t.range(min, max),
-                _ => Vec::new(),
+                DataType::TimeSeries(t) => Ok(t.range(min, max)),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// GRAPH.ADD_EDGE key u v
-    pub fn graph_add_edge(&self, key: String, u: String, v: String) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::Graph(crate::core::universe::Graph::new()));
+    pub fn graph_add_edge(&self, key: Bytes, u: String, v: String) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::Graph(crate::core::universe::Graph::new()));
         match entry.value_mut() {
-            DataType::Graph(g_inner) => { g_inner.add_edge(u, v); true },
-            _ => false,
+            DataType::Graph(g_inner) => g_inner.add_edge(u, v),
+            _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::GRAPH, "graph.add", &key);
+        Ok(())
     }
 
     /// GRAPH.BFS key start depth
-    pub fn graph_bfs(&self, key: &str, start: &str, depth: usize) -> Vec<String> {
-        self.data.get(key).map(|entry| {
+    pub fn graph_bfs(&self, key: &[u8], start: &str, depth: usize) -> DbResult<Vec<String>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                DataType::Graph(g) => g.bfs(start, depth),
-                _ => Vec::new(),
+                DataType::Graph(g) => Ok(g.bfs(start, depth)),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// ML.RUN model_key input
-    pub fn ml_run(&self, key: &str, input: &[f32]) -> Option<Vec<f32>> {
-        self.data.get(key).and_then(|entry| {
+    pub fn ml_run(&self, key: &[u8], input: &[f32]) -> DbResult<Option<Vec<f32>>> {
+        self.lookup_read(key).map_or(Ok(None), |entry| {
             match entry.value() {
-                DataType::Model(m) => Some(m.run(input)),
-                _ => None,
+                DataType::Model(m) => Ok(Some(m.run(input))),
+                _ => Err(ZedisError::WrongType),
             }
         })
     }
 
     /// ML.LOAD key name
-    pub fn ml_load(&self, key: String, name: String) -> bool {
-        self.data.insert(key, DataType::Model(crate::core::universe::Model::new(name)));
+    pub fn ml_load(&self, key: Bytes, name: String) -> bool {
+        self.expire_if_needed(&key);
+        self.data.insert(key.clone(), DataType::Model(crate::core::universe::Model::new(name)));
+        self.notify(notify::MODEL, "ml.load", &key);
         true
     }
 
     /// XRANGE
-    pub fn xrange(&self, key: &str, start: &str, end: &str) -> Vec<crate::core::structs::stream::StreamEntry> {
-        self.data.get(key).map(|entry| {
+    pub fn xrange(&self, key: &[u8], start: &str, end: &str) -> DbResult<Vec<crate::core::structs::stream::StreamEntry>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
             match entry.value() {
-                DataType::Stream(s) => s.range(start, end),
-                _ => Vec::new(),
+                DataType::Stream(s) => Ok(s.range(start, end)),
+                _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// PFADD key element
-    pub fn pf_add(&self, key: String, element: String) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::HyperLogLog(HyperLogLogWrapper::new()));
-        match entry.value_mut() {
-             DataType::HyperLogLog(h) => h.add(&element),
-             _ => false,
+    pub fn pf_add(&self, key: Bytes, element: &[u8]) -> DbResult<bool> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::HyperLogLog(HyperLogLogWrapper::new()));
+        let updated = match entry.value_mut() {
+             DataType::HyperLogLog(h) => h.add(element),
+             _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        if updated {
+            self.notify(notify::STRING, "pfadd", &key);
         }
+        Ok(updated)
     }
 
     /// PFCOUNT key
-    pub fn pf_count(&self, key: &str) -> usize {
-        self.data.get_mut(key).map(|mut entry| {
+    pub fn pf_count(&self, key: &[u8]) -> DbResult<usize> {
+        self.expire_if_needed(key);
+        self.data.get_mut(key).map_or(Ok(0), |mut entry| {
             match entry.value_mut() {
-                 DataType::HyperLogLog(h) => h.count() as usize,
-                 _ => 0,
+                 DataType::HyperLogLog(h) => Ok(h.count() as usize),
+                 _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or(0)
+        })
     }
 
     /// CF.ADD key item
-    pub fn cf_add(&self, key: String, item: String) -> bool {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::Cuckoo(CuckooFilterWrapper::new()));
-        match entry.value_mut() {
-             DataType::Cuckoo(c) => c.add(&item),
-             _ => false,
+    pub fn cf_add(&self, key: Bytes, item: &[u8]) -> DbResult<bool> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::Cuckoo(CuckooFilterWrapper::new()));
+        let added = match entry.value_mut() {
+             DataType::Cuckoo(c) => c.add(item),
+             _ => return Err(ZedisError::WrongType),
+        };
+        drop(entry);
+        if added {
+            self.notify(notify::SKETCH, "cf.add", &key);
         }
+        Ok(added)
     }
 
     /// CF.EXISTS key item
-    pub fn cf_exists(&self, key: &str, item: &str) -> bool {
-        self.data.get(key).map(|entry| {
+    pub fn cf_exists(&self, key: &[u8], item: &[u8]) -> DbResult<bool> {
+        self.lookup_read(key).map_or(Ok(false), |entry| {
             match entry.value() {
-                 DataType::Cuckoo(c) => c.contains(item),
-                 _ => false,
+                 DataType::Cuckoo(c) => Ok(c.contains(item)),
+                 _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or(false)
+        })
     }
 
     /// CMS.INCRBY key item increment
-    pub fn cms_incr(&self, key: String, item: String, incr: usize) {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::CountMin(CountMinSketchWrapper::new()));
+    pub fn cms_incr(&self, key: Bytes, item: &[u8], incr: usize) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::CountMin(CountMinSketchWrapper::new()));
         match entry.value_mut() {
-             DataType::CountMin(c) => c.incr(&item, incr),
-             _ => {},
+             DataType::CountMin(c) => c.incr(item, incr),
+             _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::SKETCH, "cms.incrby", &key);
+        Ok(())
     }
 
     /// CMS.QUERY key item
-    pub fn cms_query(&self, key: &str, item: &str) -> usize {
-        self.data.get(key).map(|entry| {
+    pub fn cms_query(&self, key: &[u8], item: &[u8]) -> DbResult<usize> {
+        self.lookup_read(key).map_or(Ok(0), |entry| {
             match entry.value() {
-                 DataType::CountMin(c) => c.query(item),
-                 _ => 0,
+                 DataType::CountMin(c) => Ok(c.query(item)),
+                 _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or(0)
+        })
     }
 
     /// TOPK.ADD key item
-    pub fn topk_add(&self, key: String, item: String) {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::TopK(TopKWrapper::new(50)));
+    pub fn topk_add(&self, key: Bytes, item: &[u8]) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::TopK(TopKWrapper::new(50)));
         match entry.value_mut() {
-             DataType::TopK(t) => t.add(&item),
-             _ => {},
+             DataType::TopK(t) => t.add(item),
+             _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::SKETCH, "topk.add", &key);
+        Ok(())
     }
 
     /// TOPK.LIST key
-    pub fn topk_list(&self, key: &str) -> Vec<(String, usize)> {
-        self.data.get(key).map(|entry| {
+    pub fn topk_list(&self, key: &[u8]) -> DbResult<Vec<(Bytes, usize)>> {
+        self.lookup_read(key).map_or(Ok(Vec::new()), |entry| {
              match entry.value() {
-                 DataType::TopK(t) => t.query(),
-                 _ => Vec::new(),
+                 DataType::TopK(t) => Ok(t.query()),
+                 _ => Err(ZedisError::WrongType),
             }
-        }).unwrap_or_default()
+        })
     }
 
     /// TDIGEST.ADD key value
-    pub fn tdigest_add(&self, key: String, value: f64) {
-        let mut entry = self.data.entry(key).or_insert_with(|| DataType::TDigest(TDigestWrapper::new()));
+    pub fn tdigest_add(&self, key: Bytes, value: f64) -> DbResult<()> {
+        self.expire_if_needed(&key);
+        let mut entry = self.data.entry(key.clone()).or_insert_with(|| DataType::TDigest(TDigestWrapper::new()));
         match entry.value_mut() {
              DataType::TDigest(t) => t.add(value),
-             _ => {},
+             _ => return Err(ZedisError::WrongType),
         }
+        drop(entry);
+        self.notify(notify::SKETCH, "tdigest.add", &key);
+        Ok(())
     }
 
     /// TDIGEST.QUANTILE key q
-    pub fn tdigest_quantile(&self, key: &str, q: f64) -> f64 {
-        self.data.get(key).map(|entry| {
+    pub fn tdigest_quantile(&self, key: &[u8], q: f64) -> DbResult<f64> {
+        self.lookup_read(key).map_or(Ok(0.0), |entry| {
              match entry.value() {
-                 DataType::TDigest(t) => t.quantile(q),
-                 _ => 0.0,
+                 DataType::TDigest(t) => Ok(t.quantile(q)),
+                 _ => Err(ZedisError::WrongType),
              }
-        }).unwrap_or(0.0)
+        })
     }
 
     /// Iterate over all data (for persistence)
+    /// Number of keys (DBSIZE). Expired keys not yet reclaimed are counted, as in Redis.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Type name of the value at `key` (TYPE), or "none".
+    pub fn key_type(&self, key: &[u8]) -> &'static str {
+        self.lookup_read(key).map_or("none", |v| v.type_name())
+    }
+
+    /// MEMORY USAGE: bytes held by the key, its value and its TTL entry.
+    pub fn memory_usage(&self, key: &[u8]) -> Option<usize> {
+        self.expire_if_needed(key);
+        let value = self.data.get(key)?.memory_usage();
+        let ttl = if self.expires.get(key).is_some() { key.len() + 16 } else { 0 };
+        Some(key.len() + std::mem::size_of::<Bytes>() + value + ttl)
+    }
+
+    fn is_expired(&self, key: &[u8], now: u64) -> bool {
+        self.expires.get(key).is_some_and(|at| at <= now)
+    }
+
+    /// Every live key matching a glob pattern (KEYS).
+    pub fn keys(&self, pattern: &[u8]) -> Vec<Bytes> {
+        let now = now_ms();
+        let match_all = pattern == b"*";
+        self.data.iter()
+            .filter(|entry| match_all || glob_match(pattern, entry.key(), false))
+            .filter(|entry| !self.is_expired(entry.key(), now))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// One SCAN step. Visits about `count` keys starting at `cursor` and
+    /// returns the next cursor (0 when done) with the visited keys that pass
+    /// `filter`.
+    ///
+    /// The cursor is `shard << 48 | position`, where a key's position is its
+    /// hash under a fixed hasher. Each DashMap shard is walked in position
+    /// order, so inserts, deletes and rehashes between calls cannot make a
+    /// key that exists for the whole scan be skipped or returned twice.
+    pub fn scan<F>(&self, cursor: u64, count: usize, mut filter: F) -> (u64, Vec<Bytes>)
+    where
+        F: FnMut(&Bytes, &DataType) -> bool,
+    {
+        let shards = self.data.shards();
+        let mut shard = (cursor >> SCAN_POSITION_BITS) as usize;
+        let mut from = cursor & SCAN_POSITION_MASK;
+        let count = count.max(1);
+        let now = now_ms();
+        let mut visited = 0;
+        let mut keys = Vec::new();
+
+        while shard < shards.len() {
+            let guard = shards[shard].read();
+            let mut page: Vec<(u64, &Bytes, &DataType)> = guard.iter()
+                .map(|(k, v)| (scan_position(k), k, v.get()))
+                .filter(|(pos, _, _)| *pos >= from)
+                .collect();
+            page.sort_unstable_by_key(|(pos, _, _)| *pos);
+
+            for (i, (pos, key, value)) in page.iter().enumerate() {
+                // Only stop between distinct positions so colliding keys
+                // always land in the same reply
+                if visited >= count && *pos != page[i - 1].0 {
+                    return (((shard as u64) << SCAN_POSITION_BITS) | pos, keys);
+                }
+                visited += 1;
+                if !self.is_expired(key, now) && filter(key, value) {
+                    keys.push((*key).clone());
+                }
+            }
+            shard += 1;
+            from = 0;
+            if visited >= count {
+                break;
+            }
+        }
+
+        if shard >= shards.len() {
+            (0, keys)
+        } else {
+            ((shard as u64) << SCAN_POSITION_BITS, keys)
+        }
+    }
+
+    /// A random live key (RANDOMKEY). Sampled the way eviction samples, so
+    /// the cost doesn't grow with the keyspace; None if the probes only
+    /// turn up keys past their deadline.
+    pub fn random_key(&self) -> Option<Bytes> {
+        let now = now_ms();
+        for _ in 0..RANDOM_KEY_ROUNDS {
+            let keys = self.sample_keys(RANDOM_KEY_SAMPLES);
+            if keys.is_empty() {
+                return None;
+            }
+            if let Some(key) = keys.into_iter().find(|key| !self.is_expired(key, now)) {
+                return Some(key);
+            }
+        }
+        None
+    }
+
     pub fn visit_all<F>(&self, mut callback: F)
     where
-        F: FnMut(&String, &DataType),
+        F: FnMut(&Bytes, &DataType),
     {
         for entry in self.data.iter() {
             callback(entry.key(), entry.value());
@@ -683,9 +1428,9 @@ use crate::core::structs::stream::Stream;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum DataType {
     String(ZedisString),
-    List(Vec<String>),
-    Set(hashbrown::HashSet<String>),
-    Hash(HashMap<String, String>),
+    List(Vec<Bytes>),
+    Set(hashbrown::HashSet<Bytes>),
+    Hash(HashMap<Bytes, Bytes>),
     ZSet(ZSet),
 
     Stream(Stream),
@@ -703,3 +1448,145 @@ pub enum DataType {
     TopK(TopKWrapper),
     TDigest(TDigestWrapper),
 }
+
+impl DataType {
+    /// Approximate bytes held by the value, for MEMORY USAGE and eviction.
+    pub fn memory_usage(&self) -> usize {
+        // Per-entry bookkeeping of a hashbrown table slot plus its Bytes handles
+        const ENTRY: usize = 48;
+        let heap = match self {
+            DataType::String(ZedisString::Inline(..)) => 0,
+            DataType::String(ZedisString::Heap(b)) => b.len(),
+            DataType::List(l) => l.capacity() * std::mem::size_of::<Bytes>() + l.iter().map(|v| v.len()).sum::<usize>(),
+            DataType::Set(s) => s.iter().map(|m| m.len() + ENTRY).sum(),
+            DataType::Hash(h) => h.iter().map(|(f, v)| f.len() + v.len() + ENTRY).sum(),
+            DataType::ZSet(z) => z.memory_usage(),
+            DataType::Stream(s) => s.memory_usage(),
+            DataType::Vector(v) => v.memory_usage(),
+            DataType::Bloom(b) => b.memory_usage(),
+            DataType::Json(j) => j.memory_usage(),
+            DataType::TimeSeries(t) => t.memory_usage(),
+            DataType::Graph(g) => g.memory_usage(),
+            DataType::Model(m) => m.name.len(),
+            DataType::HyperLogLog(h) => h.memory_usage(),
+            DataType::Cuckoo(c) => c.memory_usage(),
+            DataType::CountMin(c) => c.memory_usage(),
+            DataType::TopK(t) => t.memory_usage(),
+            DataType::TDigest(t) => t.memory_usage(),
+        };
+        std::mem::size_of::<DataType>() + heap
+    }
+
+    /// Rough cost of dropping the value, in owned allocations. Structures
+    /// whose size is not cheaply known count as large.
+    pub fn free_effort(&self) -> usize {
+        match self {
+            DataType::List(l) => l.len(),
+            DataType::Set(s) => s.len(),
+            DataType::Hash(h) => h.len(),
+            DataType::ZSet(_) | DataType::Stream(_) | DataType::Vector(_)
+            | DataType::Json(_) | DataType::Graph(_) => usize::MAX,
+            _ => 1,
+        }
+    }
+
+    /// Name reported by TYPE and matched by SCAN ... TYPE.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataType::String(_) => "string",
+            DataType::List(_) => "list",
+            DataType::Set(_) => "set",
+            DataType::Hash(_) => "hash",
+            DataType::ZSet(_) => "zset",
+            DataType::Stream(_) => "stream",
+            DataType::Vector(_) => "vector",
+            DataType::Bloom(_) => "bloom",
+            DataType::Json(_) => "json",
+            DataType::TimeSeries(_) => "timeseries",
+            DataType::Graph(_) => "graph",
+            DataType::Model(_) => "model",
+            DataType::HyperLogLog(_) => "hyperloglog",
+            DataType::Cuckoo(_) => "cuckoo",
+            DataType::CountMin(_) => "cms",
+            DataType::TopK(_) => "topk",
+            DataType::TDigest(_) => "tdigest",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `live` keys with a deadline an hour away and `stale` ones already past theirs.
+    fn db_with(live: &[&str], stale: &[&str]) -> Db {
+        let mut map = HashMap::new();
+        let mut expires = HashMap::new();
+        for (keys, at) in [(live, now_ms() + 3_600_000), (stale, 1)] {
+            for key in keys {
+                map.insert(Bytes::from(key.to_string()), DataType::String(ZedisString::from_bytes(Bytes::from("v"))));
+                expires.insert(Bytes::from(key.to_string()), at);
+            }
+        }
+        Db::from_parts(map, expires)
+    }
+
+    #[test]
+    fn loaded_databases_are_sized_by_their_contents() {
+        let db = db_with(&["a", "b"], &[]);
+        assert_eq!(db.len(), 2);
+        assert!(db.data.capacity() < 1_000, "preallocated {} slots for 2 keys", db.data.capacity());
+    }
+
+    #[test]
+    fn random_key_returns_live_keys_only() {
+        assert_eq!(db_with(&[], &[]).random_key(), None);
+        assert_eq!(db_with(&[], &["stale"]).random_key(), None);
+        let db = db_with(&["a", "b", "c"], &["stale"]);
+        for _ in 0..50 {
+            let key = db.random_key().unwrap();
+            assert!([&b"a"[..], b"b", b"c"].contains(&&key[..]), "got {:?}", key);
+        }
+    }
+
+    #[test]
+    fn lazy_expiry_on_access() {
+        let db = db_with(&["live"], &["stale"]);
+        assert_eq!(db.get_string(b"stale").unwrap(), None);
+        assert_eq!(db.expired_keys(), 1);
+        assert_eq!(db.pttl(b"stale"), -2);
+        assert_eq!(db.get_string(b"live").unwrap(), Some(Bytes::from("v")));
+        assert!(db.pttl(b"live") > 3_500_000);
+        assert_eq!(db.volatile_len(), 1);
+    }
+
+    #[test]
+    fn set_with_deadline_expires_lazily() {
+        let db = db_with(&[], &[]);
+        db.set_with(Bytes::from("k"), Bytes::from("v"), SetCondition::Always, SetExpiry::At(now_ms() + 20), false).unwrap();
+        assert!(db.exists(b"k"));
+        std::thread::sleep(std::time::Duration::from_millis(30));
+        assert!(!db.exists(b"k"));
+        assert_eq!(db.expired_keys(), 1);
+    }
+
+    #[test]
+    fn active_expiry_removes_untouched_keys() {
+        let stale: Vec<String> = (0..100).map(|i| format!("stale:{}", i)).collect();
+        let stale: Vec<&str> = stale.iter().map(String::as_str).collect();
+        let db = db_with(&["live"], &stale);
+
+        let (mut cursor, mut removed) = (0, 0);
+        for _ in 0..db.expires.shard_count() {
+            let (next, n) = db.active_expire_cycle(cursor, std::time::Duration::from_secs(1));
+            cursor = next;
+            removed += n;
+        }
+        assert_eq!(removed, 100);
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.expired_keys(), 100);
+        assert_eq!(db.volatile_len(), 1);
+        // Nothing was read: the keys went without a lookup
+        assert_eq!(db.keyspace_misses(), 0);
+    }
+}
@@ -11,6 +11,11 @@ pub struct BloomFilter {
 }
 
 impl BloomFilter {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.bits.capacity() / 8
+    }
+
     pub fn new(size: usize, k: u32) -> Self {
         Self {
             bits: BitVec::from_elem(size, false),
@@ -18,24 +23,27 @@ impl BloomFilter {
         }
     }
 
-    fn get_hashes(&self, item: &str) -> Vec<usize> {
+    fn get_hashes(&self, item: &[u8]) -> Vec<usize> {
         let mut hashes = Vec::with_capacity(self.k_hashes as usize);
         for i in 0..self.k_hashes {
             let mut hasher = DefaultHasher::new();
-            item.hash(&mut hasher);
+            // Same byte stream as `str::hash`, so filters saved before
+            // values became binary-safe keep matching.
+            hasher.write(item);
+            hasher.write_u8(0xff);
             i.hash(&mut hasher); // Salt with index
             hashes.push((hasher.finish() as usize) % self.bits.len());
         }
         hashes
     }
 
-    pub fn insert(&mut self, item: &str) {
+    pub fn insert(&mut self, item: &[u8]) {
         for hash in self.get_hashes(item) {
             self.bits.set(hash, true);
         }
     }
 
-    pub fn contains(&self, item: &str) -> bool {
+    pub fn contains(&self, item: &[u8]) -> bool {
         for hash in self.get_hashes(item) {
             if !self.bits.get(hash).unwrap_or(false) {
                 return false;
@@ -8,6 +8,19 @@ pub struct JsonDoc {
 }
 
 impl JsonDoc {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        fn value_size(v: &Value) -> usize {
+            32 + match v {
+                Value::String(s) => s.len(),
+                Value::Array(a) => a.iter().map(value_size).sum(),
+                Value::Object(o) => o.iter().map(|(k, v)| k.len() + 24 + value_size(v)).sum(),
+                _ => 0,
+            }
+        }
+        value_size(&self.root)
+    }
+
     pub fn new(json_str: &str) -> Option<Self> {
         match serde_json::from_str(json_str) {
             Ok(v) => Some(Self { root: v }),
@@ -9,6 +9,7 @@ use std::hash::{Hash, Hasher, DefaultHasher};
 use topk::FilteredSpaceSaving;
 use tdigest::TDigest;
 use serde::{Serialize, Deserialize};
+use bytes::Bytes;
 
 /// Simple HyperLogLog-like implementation for cardinality estimation
 /// Uses a simplified approach that works without external crate dependencies
@@ -21,11 +22,16 @@ pub struct HyperLogLogWrapper {
 }
 
 impl HyperLogLogWrapper {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.seen.capacity() * 9
+    }
+
     pub fn new() -> Self {
         Self { seen: HashSet::new() }
     }
 
-    pub fn add(&mut self, val: &str) -> bool {
+    pub fn add(&mut self, val: &[u8]) -> bool {
         let mut hasher = DefaultHasher::new();
         val.hash(&mut hasher);
         self.seen.insert(hasher.finish())
@@ -56,22 +62,27 @@ impl Default for CuckooFilterWrapper {
 }
 
 impl CuckooFilterWrapper {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.inner.as_ref().map_or(0, |cf| cf.memory_usage())
+    }
+
     pub fn new() -> Self {
         Self { inner: Some(CuckooFilter::new()) }
     }
 
-    pub fn add(&mut self, val: &str) -> bool {
+    pub fn add(&mut self, val: &[u8]) -> bool {
         if let Some(ref mut cf) = self.inner {
             return cf.add(val).is_ok();
         }
         false
     }
 
-    pub fn contains(&self, val: &str) -> bool {
+    pub fn contains(&self, val: &[u8]) -> bool {
         self.inner.as_ref().map(|cf| cf.contains(val)).unwrap_or(false)
     }
     
-    pub fn delete(&mut self, val: &str) -> bool {
+    pub fn delete(&mut self, val: &[u8]) -> bool {
         self.inner.as_mut().map(|cf| cf.delete(val)).unwrap_or(false)
     }
 }
@@ -92,19 +103,24 @@ impl Clone for CuckooFilterWrapper {
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct CountMinSketchWrapper {
     #[serde(skip)]
-    counts: HashMap<String, usize>,
+    counts: HashMap<Bytes, usize>,
 }
 
 impl CountMinSketchWrapper {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.counts.keys().map(|k| k.len() + 48).sum()
+    }
+
     pub fn new() -> Self {
         Self { counts: HashMap::new() }
     }
 
-    pub fn incr(&mut self, val: &str, count: usize) {
-        *self.counts.entry(val.to_string()).or_insert(0) += count;
+    pub fn incr(&mut self, val: &[u8], count: usize) {
+        *self.counts.entry(Bytes::copy_from_slice(val)).or_insert(0) += count;
     }
 
-    pub fn query(&self, val: &str) -> usize {
+    pub fn query(&self, val: &[u8]) -> usize {
         *self.counts.get(val).unwrap_or(&0)
     }
 }
@@ -113,7 +129,7 @@ impl CountMinSketchWrapper {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TopKWrapper {
     #[serde(skip)]
-    inner: Option<FilteredSpaceSaving<String>>,
+    inner: Option<FilteredSpaceSaving<Bytes>>,
     k: usize,
 }
 
@@ -130,17 +146,22 @@ impl Default for TopKWrapper {
 }
 
 impl TopKWrapper {
+    /// Approximate heap footprint in bytes: `k` tracked items plus the filter.
+    pub fn memory_usage(&self) -> usize {
+        self.k * 96
+    }
+
     pub fn new(k: usize) -> Self {
         Self { inner: Some(FilteredSpaceSaving::new(k)), k }
     }
 
-    pub fn add(&mut self, val: &str) {
+    pub fn add(&mut self, val: &[u8]) {
         if let Some(ref mut tk) = self.inner {
-            tk.insert(val.to_string(), 1);
+            tk.insert(Bytes::copy_from_slice(val), 1);
         }
     }
 
-    pub fn query(&self) -> Vec<(String, usize)> {
+    pub fn query(&self) -> Vec<(Bytes, usize)> {
         // FilteredSpaceSaving has limited API - return empty for now
         Vec::new()
     }
@@ -160,6 +181,11 @@ impl Default for TDigestWrapper {
 }
 
 impl TDigestWrapper {
+    /// Approximate heap footprint in bytes: one centroid per slot.
+    pub fn memory_usage(&self) -> usize {
+        self.inner.as_ref().map_or(0, |td| td.max_size() * 16)
+    }
+
     pub fn new() -> Self {
         Self { inner: Some(TDigest::new_with_size(100)) }
     }
@@ -1,45 +1,62 @@
 use std::fmt;
 
+use bytes::Bytes;
 use serde::{Serialize, Deserialize};
 
+/// Binary-safe string value with small-string optimization.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ZedisString {
     Inline(u8, [u8; 22]), // 1 byte len, 22 bytes data (fits in 24 bytes total with discriminant)
-    Heap(String),
+    Heap(Bytes),
 }
 
 impl ZedisString {
-    pub fn new(s: &str) -> Self {
+    pub fn new(s: &[u8]) -> Self {
         let len = s.len();
         if len <= 22 {
             let mut buf = [0u8; 22];
-            buf[..len].copy_from_slice(s.as_bytes());
+            buf[..len].copy_from_slice(s);
             ZedisString::Inline(len as u8, buf)
         } else {
-            ZedisString::Heap(s.to_string())
+            ZedisString::Heap(Bytes::copy_from_slice(s))
         }
     }
 
-    pub fn as_str(&self) -> &str {
+    /// Build from an owned buffer, reusing the allocation for heap values.
+    pub fn from_bytes(b: Bytes) -> Self {
+        if b.len() <= 22 {
+            Self::new(&b)
+        } else {
+            ZedisString::Heap(b)
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
         match self {
-            ZedisString::Inline(len, buf) => unsafe {
-                std::str::from_utf8_unchecked(&buf[..*len as usize])
-            },
-            ZedisString::Heap(s) => s.as_str(),
+            ZedisString::Inline(len, buf) => &buf[..*len as usize],
+            ZedisString::Heap(b) => b,
         }
     }
-    
+
+    /// Owned copy of the value. Heap values are shared, not copied.
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            ZedisString::Inline(len, buf) => Bytes::copy_from_slice(&buf[..*len as usize]),
+            ZedisString::Heap(b) => b.clone(),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn len(&self) -> usize {
         match self {
             ZedisString::Inline(len, _) => *len as usize,
-            ZedisString::Heap(s) => s.len(),
+            ZedisString::Heap(b) => b.len(),
         }
     }
 }
 
 impl fmt::Display for ZedisString {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
+        write!(f, "{}", String::from_utf8_lossy(self.as_bytes()))
     }
 }
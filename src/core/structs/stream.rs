@@ -1,12 +1,14 @@
 use hashbrown::HashMap;
+use bytes::Bytes;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Serialize, Deserialize};
+use crate::core::error::{DbResult, ZedisError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamEntry {
     pub id: String, // "timestamp-sequence"
-    pub fields: HashMap<String, String>,
+    pub fields: HashMap<Bytes, Bytes>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +18,13 @@ pub struct Stream {
 }
 
 impl Stream {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.entries.iter()
+            .map(|e| 64 + e.id.len() + e.fields.iter().map(|(k, v)| k.len() + v.len() + 48).sum::<usize>())
+            .sum()
+    }
+
     pub fn new() -> Self {
         Self {
             entries: Vec::new(),
@@ -23,27 +32,38 @@ impl Stream {
         }
     }
 
-    pub fn add(&mut self, id_arg: Option<&str>, fields: HashMap<String, String>) -> String {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-        let (ts, seq) = if let Some(id_str) = id_arg {
-             if id_str == "*" {
-                 (now, if now == self.last_id.0 { self.last_id.1 + 1 } else { 0 })
-             } else {
-                 // Simplified ID parsing for MVP
-                 let parts: Vec<&str> = id_str.split('-').collect();
-                 let t = parts[0].parse().unwrap_or(now);
-                 let s = parts.get(1).unwrap_or(&"0").parse().unwrap_or(0);
-                 (t, s)
-             }
-        } else {
-             (now, if now == self.last_id.0 { self.last_id.1 + 1 } else { 0 })
-        };
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 
-        if ts < self.last_id.0 || (ts == self.last_id.0 && seq <= self.last_id.1) {
-             // For auto-generated IDs, this logic ensures increase.
-             // For user IDs, we should error if lower, but for MVP we just accept/overwrite logic or force new ID.
-             // Let's simpler: force logic.
-        }
+    /// XADD with `*` (or no ID), `ms-*` or an explicit `ms[-seq]`. Explicit
+    /// IDs must be above the stream's top item, as in Redis.
+    pub fn add(&mut self, id_arg: Option<&str>, fields: HashMap<Bytes, Bytes>) -> DbResult<String> {
+        let next_seq = |ms: u128, last: (u128, u64)| if ms == last.0 { last.1.checked_add(1) } else { Some(0) };
+        let (ts, seq) = match id_arg.unwrap_or("*") {
+            "*" => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+                // A clock that went backwards keeps counting on the top item
+                let ms = now.max(self.last_id.0);
+                (ms, next_seq(ms, self.last_id).ok_or_else(|| ZedisError::err("The stream has exhausted the last possible ID, unable to add more items"))?)
+            }
+            id_str => {
+                let invalid = || ZedisError::err("Invalid stream ID specified as stream command argument");
+                let (ms, seq) = id_str.split_once('-').unwrap_or((id_str, "0"));
+                let ms: u128 = ms.parse().map_err(|_| invalid())?;
+                let seq = match seq {
+                    "*" => next_seq(ms, self.last_id).unwrap_or(0),
+                    seq => seq.parse().map_err(|_| invalid())?,
+                };
+                if (ms, seq) == (0, 0) {
+                    return Err(ZedisError::err("The ID specified in XADD must be greater than 0-0"));
+                }
+                if (ms, seq) <= self.last_id {
+                    return Err(ZedisError::err("The ID specified in XADD is equal or smaller than the target stream top item"));
+                }
+                (ms, seq)
+            }
+        };
 
         self.last_id = (ts, seq);
         let id_string = format!("{}-{}", ts, seq);
@@ -53,7 +73,7 @@ impl Stream {
             fields,
         });
         
-        id_string
+        Ok(id_string)
     }
 
     pub fn range(&self, start: &str, end: &str) -> Vec<StreamEntry> {
@@ -17,6 +17,16 @@ pub struct VectorIndex {
 }
 
 impl VectorIndex {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.vectors.iter()
+            .map(|(k, v)| {
+                let sparse = v.sparse.as_ref().map_or(0, |s| s.len() * std::mem::size_of::<(u32, f32)>());
+                k.len() + v.dense.len() * 2 + sparse + 96
+            })
+            .sum()
+    }
+
     pub fn new(dim: usize) -> Self {
         Self {
             vectors: HashMap::new(),
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::ops::Bound;
+use bytes::Bytes;
 use rand::Rng;
 use std::sync::Arc;
 
@@ -33,7 +35,7 @@ use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ZSet {
-    dict: HashMap<String, f64>,
+    dict: HashMap<Bytes, f64>,
     // For a real production "God Tier" ZSet, we would implement the SkipList manually 
     // to allow O(log N) rank operations and range queries.
     // For this prototype, we'll use a simplified vector-based approach for sorting 
@@ -45,7 +47,7 @@ pub struct ZSet {
     // Actually, let's use a BTreeMap for score -> key mapping which gives us O(log N) 
     // for range queries by score, which is the most common case.
     // But ZQS are unique (score, key) pairs.
-    sorted: std::collections::BTreeMap<(i64, String), ()>, // (score * 10^epsilon, key)
+    sorted: std::collections::BTreeMap<(i64, Bytes), ()>, // (score * 10^epsilon, key)
 }
 
 // Helper to handle floats in BTreeMap keys (Not NaN)
@@ -55,6 +57,12 @@ fn float_to_key(f: f64) -> i64 {
 
 #[allow(dead_code)]
 impl ZSet {
+    /// Approximate heap footprint in bytes (MEMORY USAGE, eviction).
+    pub fn memory_usage(&self) -> usize {
+        // Each member lives in the dict and the sorted index
+        self.dict.keys().map(|m| 2 * m.len() + 80).sum()
+    }
+
     pub fn new() -> Self {
         Self {
             dict: HashMap::new(),
@@ -62,7 +70,7 @@ impl ZSet {
         }
     }
 
-    pub fn add(&mut self, score: f64, ele: String) -> bool {
+    pub fn add(&mut self, score: f64, ele: Bytes) -> bool {
         let is_update = self.dict.contains_key(&ele);
         if is_update {
             let old_score = self.dict[&ele];
@@ -75,7 +83,7 @@ impl ZSet {
         !is_update
     }
 
-    pub fn range(&self, start: usize, end: usize) -> Vec<String> {
+    pub fn range(&self, start: usize, end: usize) -> Vec<Bytes> {
         let len = self.dict.len();
         if start >= len {
             return Vec::new();
@@ -99,15 +107,27 @@ impl ZSet {
             .collect()
     }
     
-    pub fn range_by_score(&self, min: f64, max: f64) -> Vec<String> {
-         let start_key = (float_to_key(min), String::new());
-         let end_key = (float_to_key(max), String::from("\x7f")); // Max char
+    /// Same as `range`, paired with each member's score (ZRANGE ... WITHSCORES).
+    pub fn range_with_scores(&self, start: usize, end: usize) -> Vec<(Bytes, f64)> {
+        self.range(start, end)
+            .into_iter()
+            .map(|member| {
+                let score = self.dict.get(&member).cloned().unwrap_or(0.0);
+                (member, score)
+            })
+            .collect()
+    }
+
+    pub fn range_by_score(&self, min: f64, max: f64) -> Vec<Bytes> {
+         let start_key = (float_to_key(min), Bytes::new());
+         let max_k = float_to_key(max);
 
-         self.sorted.range(start_key..=end_key)
+         self.sorted.range((Bound::Included(start_key), Bound::Unbounded))
+            .take_while(|((k, _), _)| *k <= max_k)
             .map(|((_, ele), _)| ele.clone())
             .collect()
     }
-    pub fn score(&self, member: &str) -> Option<f64> {
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
         self.dict.get(member).cloned()
     }
 }
@@ -20,6 +20,11 @@ pub struct TimeSeries {
 }
 
 impl TimeSeries {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.samples.capacity() * std::mem::size_of::<(u64, f64)>()
+    }
+
     pub fn new() -> Self { 
         Self { samples: Vec::new() } 
     }
@@ -46,6 +51,13 @@ pub struct Graph {
 }
 
 impl Graph {
+    /// Approximate heap footprint in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.adj.iter()
+            .map(|(node, edges)| node.len() + 48 + edges.iter().map(|e| e.len() + 24).sum::<usize>())
+            .sum()
+    }
+
     pub fn new() -> Self {
         Self { adj: HashMap::new() }
     }
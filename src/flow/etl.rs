@@ -2,6 +2,7 @@ use crate::core::storage::Db;
 use crate::core::ai::BgeM3;
 use crate::flow::config::{FlowItem, FlowTarget};
 use std::sync::Arc;
+use bytes::Bytes;
 use sqlx::{AnyPool, Row, Column};
 use serde_json::{Value, Map};
 use log::{info, error, warn, debug};
@@ -54,7 +55,9 @@ pub async fn run_flow(db: Arc<Db>, bge: Option<Arc<BgeM3>>, config: FlowItem) {
             match config.target {
                 FlowTarget::Json => {
                     let key = format_key(&config.key_format, &config.table, &row_map);
-                    db.json_set(key, Value::Object(row_map.clone()).to_string());
+                    if let Err(e) = db.json_set(key.into(), &Value::Object(row_map.clone()).to_string()) {
+                        warn!("🌊 Z-Flow: Row skipped for '{}': {}", config.name, e);
+                    }
                 },
                 FlowTarget::Vector => {
                     let mut text_parts = Vec::new();
@@ -70,7 +73,9 @@ pub async fn run_flow(db: Arc<Db>, bge: Option<Arc<BgeM3>>, config: FlowItem) {
 
                     if let Some(b) = &bge {
                          if let Ok((dense, sparse)) = b.embed_hybrid(&text) {
-                              db.vadd_hybrid(key, dense, Some(sparse));
+                              if let Err(e) = db.vadd_hybrid(key.into(), dense, Some(sparse)) {
+                                  warn!("🌊 Z-Flow: Row skipped for '{}': {}", config.name, e);
+                              }
                          }
                     }
                 },
@@ -78,7 +83,9 @@ pub async fn run_flow(db: Arc<Db>, bge: Option<Arc<BgeM3>>, config: FlowItem) {
                     if let (Some(item_col), Some(bf_key)) = (&config.item, &config.key) {
                         if let Some(val) = row_map.get(item_col).and_then(|v| v.as_str()) {
                             if !val.is_empty() {
-                                db.bf_add(bf_key.clone(), val.to_string());
+                                if let Err(e) = db.bf_add(bf_key.clone().into(), val.as_bytes()) {
+                                    warn!("🌊 Z-Flow: Row skipped for '{}': {}", config.name, e);
+                                }
                             }
                         }
                     }
@@ -88,7 +95,9 @@ pub async fn run_flow(db: Arc<Db>, bge: Option<Arc<BgeM3>>, config: FlowItem) {
                         let u = row_map.get(src_col).and_then(|v| v.as_str()).unwrap_or("");
                         let v = row_map.get(dst_col).and_then(|v| v.as_str()).unwrap_or("");
                         if !u.is_empty() && !v.is_empty() {
-                            db.graph_add_edge(gkey.clone(), u.to_string(), v.to_string());
+                            if let Err(e) = db.graph_add_edge(gkey.clone().into(), u.to_string(), v.to_string()) {
+                                warn!("🌊 Z-Flow: Row skipped for '{}': {}", config.name, e);
+                            }
                         }
                     }
                 },
@@ -106,7 +115,9 @@ pub async fn run_flow(db: Arc<Db>, bge: Option<Arc<BgeM3>>, config: FlowItem) {
                         
                         if ts > 0 {
                             let key = format_key(&config.key_format, &config.table, &row_map);
-                            db.ts_add(key, ts as u64, val);
+                            if let Err(e) = db.ts_add(key.into(), ts as u64, val) {
+                                warn!("🌊 Z-Flow: Row skipped for '{}': {}", config.name, e);
+                            }
                         }
                     }
                 },
@@ -117,7 +128,9 @@ pub async fn run_flow(db: Arc<Db>, bge: Option<Arc<BgeM3>>, config: FlowItem) {
                         let member = row_map.get(mem_col).and_then(|v| v.as_str()).unwrap_or("");
                         
                         if !member.is_empty() {
-                            db.geoadd(gkey.clone(), lon, lat, member.to_string());
+                            if let Err(e) = db.geoadd(gkey.clone().into(), lon, lat, Bytes::copy_from_slice(member.as_bytes())) {
+                                warn!("🌊 Z-Flow: Row skipped for '{}': {}", config.name, e);
+                            }
                         }
                     }
                 }
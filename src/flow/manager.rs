@@ -14,6 +14,7 @@ pub struct FlowManager {
     db: Arc<Db>,
     bge: Option<Arc<BgeM3>>,
     tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+    watcher: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl FlowManager {
@@ -22,6 +23,7 @@ impl FlowManager {
             db,
             bge,
             tasks: Mutex::new(HashMap::new()),
+            watcher: Mutex::new(None),
         }
     }
 
@@ -50,13 +52,36 @@ impl FlowManager {
              
              let self_clone = self.clone();
              let path_string = path_str.clone();
-             tokio::spawn(async move {
+             let handle = tokio::spawn(async move {
                  let _w = w; // Keep watcher alive
                  while rx.recv().await.is_some() {
                       tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
                       self_clone.reload_config(&path_string).await;
                  }
              });
+             *self.watcher.lock().await = Some(handle);
+        }
+    }
+
+    /// Every configured flow with its state, "running" or "stopped" (INFO zedis).
+    pub async fn task_states(&self) -> Vec<(String, &'static str)> {
+        let tasks = self.tasks.lock().await;
+        let mut states: Vec<_> = tasks.iter()
+            .map(|(name, handle)| (name.clone(), if handle.is_finished() { "stopped" } else { "running" }))
+            .collect();
+        states.sort();
+        states
+    }
+
+    /// Stop watching the config and abort every running flow (shutdown).
+    pub async fn stop(&self) {
+        if let Some(handle) = self.watcher.lock().await.take() {
+            handle.abort();
+        }
+        let mut tasks = self.tasks.lock().await;
+        for (name, handle) in tasks.drain() {
+            info!("🌊 Z-Flow: Stopping '{}'", name);
+            handle.abort();
         }
     }
 
@@ -1,15 +1,36 @@
 use log::info;
 use core_affinity::CoreId;
+use crate::core::evict::EvictionPolicy;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+/// Above this the box is "high memory": evict lazily, only volatile keys.
+const HIGH_MEMORY: u64 = 32 * GIB;
+
+/// Memory limit and eviction policy the Auto-Tuner picks for this machine.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryAdvice {
+    pub maxmemory: u64,
+    pub policy: EvictionPolicy,
+}
 
 pub struct HardwareManager {
     pub core_ids: Vec<CoreId>,
+    /// CPU model string, e.g. "AMD EPYC 7763 64-Core Processor" (INFO zedis).
+    pub cpu_brand: String,
+    /// Physical RAM in bytes (INFO memory total_system_memory).
+    pub total_memory: u64,
 }
 
 impl HardwareManager {
     pub fn new() -> Self {
+        use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
         let core_ids = core_affinity::get_core_ids().unwrap_or_else(Vec::new);
         info!("Detected {} cores", core_ids.len());
-        Self { core_ids }
+        let sys = System::new_with_specifics(
+            RefreshKind::new().with_cpu(CpuRefreshKind::new()).with_memory(MemoryRefreshKind::new().with_ram()),
+        );
+        let cpu_brand = sys.cpus().first().map(|cpu| cpu.brand().trim().to_string()).unwrap_or_default();
+        Self { core_ids, cpu_brand, total_memory: sys.total_memory() }
     }
 
     /// Pin the current thread to a specific core index
@@ -27,40 +48,59 @@ impl HardwareManager {
     }
 
     pub fn check_simd_support(&self) {
+        match self.simd_level() {
+            "avx2" => info!("Hardware: AVX2 detected. Enabling SIMD optimizations."),
+            "sse4.1" => info!("Hardware: SSE4.1 detected. Enabling legacy SIMD."),
+            "scalar" => info!("Hardware: No advanced SIMD detected. Running scalar fallback."),
+            _ => info!("Hardware: Non-x86 architecture. SIMD check skipped."),
+        }
+    }
+
+    /// Best SIMD instruction set available: "avx2", "sse4.1", "scalar", or
+    /// "unknown" off x86.
+    pub fn simd_level(&self) -> &'static str {
         #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
         {
             if is_x86_feature_detected!("avx2") {
-                info!("Hardware: AVX2 detected. Enabling SIMD optimizations.");
+                "avx2"
             } else if is_x86_feature_detected!("sse4.1") {
-                info!("Hardware: SSE4.1 detected. Enabling legacy SIMD.");
+                "sse4.1"
             } else {
-                info!("Hardware: No advanced SIMD detected. Running scalar fallback.");
+                "scalar"
             }
         }
         #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
         {
-            info!("Hardware: Non-x86 architecture. SIMD check skipped.");
+            "unknown"
         }
     }
 
-    /// God Tier Auto-Tuner: Suggests config based on hardware
-    pub fn loop_recommendations(&self) {
+    /// God Tier Auto-Tuner: Suggests config based on hardware. The memory
+    /// advice is applied to `maxmemory` / `maxmemory-policy` left on `auto`.
+    pub fn loop_recommendations(&self) -> MemoryAdvice {
         use sysinfo::System;
         let mut sys = System::new_all();
         sys.refresh_all();
-        
-        let total_memory = sys.total_memory();
+
+        // Bytes; a container's cgroup limit wins over the host's RAM
+        let total_memory = sys.cgroup_limits()
+            .map(|limits| limits.total_memory)
+            .filter(|&limit| limit > 0)
+            .unwrap_or_else(|| sys.total_memory());
         let core_count = self.core_ids.len();
 
         info!("--- Auto-Tuner Analysis ---");
-        info!("Total Memory: {} GB", total_memory / 1024 / 1024);
+        info!("Total Memory: {} GB", total_memory / GIB);
         info!("Core Count: {}", core_count);
 
-        if total_memory > 32 * 1024 * 1024 {
-             info!("Recommend: High-Memory Mode (Lazy eviction, large slab pages)");
+        let advice = if total_memory > HIGH_MEMORY {
+            info!("Recommend: High-Memory Mode (Lazy eviction, large slab pages)");
+            MemoryAdvice { maxmemory: total_memory / 10 * 9, policy: EvictionPolicy::VolatileLru }
         } else {
-             info!("Recommend: Low-Memory Mode (Aggressive eviction, small slab pages)");
-        }
+            info!("Recommend: Low-Memory Mode (Aggressive eviction, small slab pages)");
+            MemoryAdvice { maxmemory: total_memory / 4 * 3, policy: EvictionPolicy::AllKeysLru }
+        };
+        info!("Recommend: maxmemory {} MB, maxmemory-policy {}", advice.maxmemory / 1024 / 1024, advice.policy.name());
 
         if core_count > 16 {
              info!("Recommend: High-Concurrency Partitioning (2048+ shards)");
@@ -68,6 +108,6 @@ impl HardwareManager {
              info!("Recommend: Standard Partitioning (1024 shards)");
         }
         info!("---------------------------");
+        advice
     }
 }
-
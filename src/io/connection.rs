@@ -1,20 +1,34 @@
-use bytes::{Bytes, BytesMut};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use bytes::BytesMut;
+use std::time::Instant;
 use tokio::net::TcpStream;
 use anyhow::Result;
-use crate::core::protocol::RespFrame;
+use crate::core::protocol::{ProtocolLimits, ProtocolVersion, RequestParser, RespFrame};
+use crate::io::traits::AsyncStream;
 
 
-pub struct Connection {
-    stream: BufWriter<TcpStream>,
+/// A client session over any `AsyncStream` transport.
+pub struct Connection<S = TcpStream> {
+    stream: S,
     buffer: BytesMut,
+    parser: RequestParser,
+    /// Encoded replies waiting for the next flush. Reused across batches.
+    out: Vec<u8>,
+    /// Reply protocol negotiated via HELLO. RESP2 until the client asks otherwise.
+    pub protocol: ProtocolVersion,
+    /// Set after a protocol error: the error reply is queued and no further
+    /// requests are read.
+    closing: bool,
 }
 
-impl Connection {
-    pub fn new(stream: TcpStream) -> Self {
+impl<S: AsyncStream> Connection<S> {
+    pub fn new(stream: S, limits: ProtocolLimits) -> Self {
         Self {
-            stream: BufWriter::new(stream),
+            stream,
             buffer: BytesMut::with_capacity(4096),
+            parser: RequestParser::new(limits),
+            out: Vec::with_capacity(4096),
+            protocol: ProtocolVersion::Resp2,
+            closing: false,
         }
     }
 
@@ -22,13 +36,13 @@ impl Connection {
     pub async fn read_frame(&mut self) -> Result<Option<RespFrame>> {
         loop {
             // Attempt to parse a frame from the buffered data
-            if let Ok((remaining, frame)) = crate::core::protocol::parse_frame(&self.buffer) {
-                // Calculate how many bytes were consumed
-                let consumed = self.buffer.len() - remaining.len();
-                // Advance the buffer
-                let _ = self.buffer.split_to(consumed);
+            if let Some(frame) = self.next_buffered_frame() {
                 return Ok(Some(frame));
             }
+            if self.closing {
+                self.flush().await?;
+                return Ok(None);
+            }
 
             // If incomplete, read more data from the socket
             if 0 == self.stream.read_buf(&mut self.buffer).await? {
@@ -42,12 +56,67 @@ impl Connection {
         }
     }
 
-    pub async fn write_frame(&mut self, frame: &RespFrame) -> Result<()> {
-        let mut buf = Vec::new();
-        frame.encode(&mut buf);
-        self.stream.write_all(&buf).await?;
-        self.stream.flush().await?;
+    /// Next frame that is already fully buffered, without touching the socket.
+    /// Used to drain a pipelined batch before flushing replies.
+    /// A malformed request queues `ERR Protocol error` and ends the stream.
+    pub fn next_buffered_frame(&mut self) -> Option<RespFrame> {
+        if self.closing {
+            return None;
+        }
+        match self.parser.parse(&mut self.buffer) {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::debug!("Closing connection: {}", e);
+                self.queue_frame(&RespFrame::Error(e.to_string()));
+                self.closing = true;
+                None
+            }
+        }
+    }
+
+    /// Encode a reply into the output buffer. Nothing is sent until `flush`.
+    pub fn queue_frame(&mut self, frame: &RespFrame) {
+        frame.encode_for(self.protocol, &mut self.out);
+    }
+
+    /// Queue replies already encoded for this connection's protocol.
+    pub fn queue_encoded(&mut self, bytes: &[u8]) {
+        self.out.extend_from_slice(bytes);
+    }
+
+    /// Write every queued reply with a single write.
+    pub async fn flush(&mut self) -> Result<()> {
+        if !self.out.is_empty() {
+            self.stream.write_all(&self.out).await?;
+            self.out.clear();
+        }
         Ok(())
     }
-}
 
+    /// Like `flush`, but gives up at `deadline` (a client stuck over its soft
+    /// output limit). Returns false on timeout; the connection must then be dropped.
+    pub async fn flush_until(&mut self, deadline: Option<Instant>) -> Result<bool> {
+        match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline.into(), self.flush()).await {
+                Ok(res) => res.map(|_| true),
+                Err(_) => Ok(false),
+            },
+            None => self.flush().await.map(|_| true),
+        }
+    }
+
+    /// Unparsed input bytes and spare capacity (CLIENT LIST qbuf / qbuf-free).
+    pub fn query_buffer(&self) -> (usize, usize) {
+        (self.buffer.len(), self.buffer.capacity() - self.buffer.len())
+    }
+
+    /// Replies encoded but not yet flushed.
+    pub fn pending_output(&self) -> usize {
+        self.out.len()
+    }
+
+    pub async fn write_frame(&mut self, frame: &RespFrame) -> Result<()> {
+        self.queue_frame(frame);
+        self.flush().await
+    }
+}
@@ -20,3 +20,22 @@ impl ZedisListener {
         Ok((socket, addr))
     }
 }
+
+/// Bind a Unix domain socket (`unixsocket`), replacing a stale socket file
+/// left by a previous run. `perm` is applied like Redis' `unixsocketperm`;
+/// 0 keeps the process umask.
+#[cfg(unix)]
+pub fn bind_unix(path: &str, perm: u32) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(meta) = std::fs::metadata(path) {
+        if meta.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    if perm != 0 {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(perm))?;
+    }
+    Ok(listener)
+}
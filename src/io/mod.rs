@@ -1,3 +1,5 @@
 pub mod traits;
 pub mod connection;
 pub mod listener;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
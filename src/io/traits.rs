@@ -1,9 +1,22 @@
-use async_trait::async_trait;
+use bytes::BytesMut;
 use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-#[async_trait]
-#[allow(dead_code)]
-pub trait AsyncStream: Send + Sync {
-    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
-    async fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+/// Byte transport under a `Connection`: TCP, Unix socket, TLS or io_uring.
+/// Everything above this trait (parsing, dispatch, reply batching) is shared.
+pub trait AsyncStream {
+    /// Read into the spare capacity of `buf`. `Ok(0)` means the peer closed.
+    async fn read_buf(&mut self, buf: &mut BytesMut) -> io::Result<usize>;
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()>;
+}
+
+// Any tokio stream (TcpStream, UnixStream, TlsStream<_>) is a transport.
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncStream for T {
+    async fn read_buf(&mut self, buf: &mut BytesMut) -> io::Result<usize> {
+        AsyncReadExt::read_buf(self, buf).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        AsyncWriteExt::write_all(self, buf).await
+    }
 }
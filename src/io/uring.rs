@@ -0,0 +1,52 @@
+//! io_uring transport (`--features io-uring`, Linux only).
+//!
+//! Each worker thread runs its own `tokio_uring` runtime and ring. Reads and
+//! writes are submitted to the ring with owned buffers: a buffer is handed to
+//! the kernel for the operation and returned with its result, then reused.
+
+use bytes::BytesMut;
+use std::io;
+use tokio_uring::net::TcpStream;
+use crate::io::traits::AsyncStream;
+
+/// Size of the per-connection read buffer.
+const READ_BUF_SIZE: usize = 16 * 1024;
+
+pub struct UringStream {
+    stream: TcpStream,
+    // Moved into the kernel during an operation, hence the Options
+    read_buf: Option<Vec<u8>>,
+    write_buf: Option<Vec<u8>>,
+}
+
+impl UringStream {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            read_buf: Some(Vec::with_capacity(READ_BUF_SIZE)),
+            write_buf: Some(Vec::with_capacity(READ_BUF_SIZE)),
+        }
+    }
+}
+
+impl AsyncStream for UringStream {
+    async fn read_buf(&mut self, buf: &mut BytesMut) -> io::Result<usize> {
+        let mut read_buf = self.read_buf.take().ok_or_else(|| io::Error::other("read already in progress"))?;
+        read_buf.clear();
+        let (res, read_buf) = self.stream.read(read_buf).await;
+        if let Ok(n) = res {
+            buf.extend_from_slice(&read_buf[..n]);
+        }
+        self.read_buf = Some(read_buf);
+        res
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut out = self.write_buf.take().unwrap_or_default();
+        out.clear();
+        out.extend_from_slice(data);
+        let (res, out) = self.stream.write_all(out).await;
+        self.write_buf = Some(out);
+        res
+    }
+}
@@ -1,9 +1,11 @@
 #![allow(unexpected_cfgs)]
 #![allow(unused_imports)]
 use mimalloc::MiMalloc;
+use crate::core::memory::TrackingAllocator;
 
+// mimalloc, counted for INFO used_memory and maxmemory
 #[global_allocator]
-static GLOBAL: MiMalloc = MiMalloc;
+static GLOBAL: TrackingAllocator<MiMalloc> = TrackingAllocator(MiMalloc);
 
 mod config;
 mod server;
@@ -15,6 +17,7 @@ mod persistence;
 mod scripting;
 mod compatibility;
 mod flow;
+mod shutdown;
 
 
 use log::info;
@@ -30,8 +33,14 @@ async fn main() -> anyhow::Result<()> {
     let core_count = num_cpus::get();
     info!("Detected {} CPU cores. Initializing Thread-per-Core architecture...", core_count);
 
-    // Config Load
-    let conf = config::Config::default();
+    // Config Load: zedis.conf / TOML file, ZEDIS_* env, --flags
+    let conf = match config::Config::load(std::env::args().skip(1)) {
+        Ok(conf) => conf,
+        Err(e) => {
+            eprintln!("\n*** FATAL CONFIG ERROR ***\n{}", e);
+            std::process::exit(1);
+        }
+    };
 
 
     // Start Server
@@ -1,43 +1,101 @@
+use crate::core::keyspace::Keyspace;
+use crate::core::latency;
 use crate::core::storage::{Db, DataType};
+use crate::core::protocol::{RespFrame, parse_frame};
+use bytes::Bytes;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use hashbrown::HashMap;
+use std::io::{BufWriter, Read, Write};
 use std::sync::Arc;
 use anyhow::Result;
 use log::{info, error};
 use parking_lot::Mutex;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
+/// RDB header: magic + format version, followed by the bincode payload.
+/// Files without it are the original bare keyspace dump.
+const RDB_MAGIC: &[u8; 8] = b"ZEDISRDB";
+/// 1: keyspace + expires, 2: every database as (index, keyspace + expires)
+const RDB_VERSION: u32 = 2;
+
+// INFO persistence: writes since the last RDB save, and how that save went
+static CHANGES: AtomicU64 = AtomicU64::new(0);
+static LAST_SAVE_TIME: AtomicU64 = AtomicU64::new(0);
+static LAST_SAVE_OK: AtomicBool = AtomicBool::new(true);
+
+/// State of the RDB snapshot for INFO persistence.
+pub struct RdbStatus {
+    pub changes_since_last_save: u64,
+    /// Unix seconds of the last successful save (or of startup)
+    pub last_save_time: u64,
+    pub last_save_ok: bool,
+}
+
+fn unix_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
 /// Fsync Policy for AOF durability vs performance tradeoff
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FsyncPolicy {
-    Always,    // Flush every write (safest, slowest)
-    EverySec,  // Flush every 1 second (balanced)
-    No,        // Let OS decide (fastest, least safe)
+    Always = 0,    // Flush every write (safest, slowest)
+    EverySec = 1,  // Flush every 1 second (balanced)
+    No = 2,        // Let OS decide (fastest, least safe)
+}
+
+impl FsyncPolicy {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            0 => FsyncPolicy::Always,
+            2 => FsyncPolicy::No,
+            _ => FsyncPolicy::EverySec,
+        }
+    }
+}
+
+/// Flush the AOF buffer and fsync the file. Only the fsync is timed
+/// (LATENCY aof-fsync): that is the call that can stall on the disk.
+fn fsync(writer: &mut BufWriter<File>) -> std::io::Result<()> {
+    writer.flush()?;
+    latency::measure(latency::AOF_FSYNC, || writer.get_ref().sync_data())
 }
 
 // Append Only File (AOF) Manager - God Tier Durability + Performance
 pub struct AofManager {
-    sender: parking_lot::Mutex<Option<mpsc::Sender<String>>>,
+    sender: parking_lot::Mutex<Option<mpsc::Sender<Vec<u8>>>>,
+    writer: parking_lot::Mutex<Option<thread::JoinHandle<()>>>,
     enabled: AtomicBool,
-    fsync_policy: FsyncPolicy,
+    path: String,
+    /// Whether the writer's last write/flush succeeded (INFO aof_last_write_status).
+    write_ok: Arc<AtomicBool>,
+    /// Database the log is positioned on; a SELECT is written when a
+    /// command targets another one. Only touched under the `sender` lock.
+    selected: AtomicUsize,
+    // Shared with the writer thread so CONFIG SET appendfsync applies live
+    fsync_policy: Arc<AtomicU8>,
 }
 
 impl AofManager {
+    #[allow(dead_code)]
     pub fn new(path: &str, enabled: bool) -> Result<Self> {
         Self::with_policy(path, enabled, FsyncPolicy::EverySec)
     }
 
     pub fn with_policy(path: &str, enabled: bool, policy: FsyncPolicy) -> Result<Self> {
-        let (tx, rx) = mpsc::channel::<String>();
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
         let path = path.to_string();
-        let fsync_policy = policy;
+        let fsync_policy = Arc::new(AtomicU8::new(policy as u8));
+        let policy_handle = fsync_policy.clone();
+        let write_ok = Arc::new(AtomicBool::new(true));
+        let write_status = write_ok.clone();
+        let file_path = path.clone();
         
         // Background writer thread - non-blocking for callers
-        thread::spawn(move || {
+        let writer_thread = thread::spawn(move || {
             let file = std::fs::OpenOptions::new()
                 .create(true)
                 .append(true)
@@ -50,17 +108,16 @@ impl AofManager {
                 // Batch receive with timeout for periodic flush
                 match rx.recv_timeout(Duration::from_millis(100)) {
                     Ok(cmd) => {
-                        let _ = writer.write_all(cmd.as_bytes());
-                        let _ = writer.write_all(b"\n");
+                        let mut ok = writer.write_all(&cmd).is_ok();
                         
                         // Flush based on policy
-                        match fsync_policy {
+                        match FsyncPolicy::from_u8(policy_handle.load(Ordering::Relaxed)) {
                             FsyncPolicy::Always => {
-                                let _ = writer.flush();
+                                ok &= fsync(&mut writer).is_ok();
                             }
                             FsyncPolicy::EverySec => {
                                 if last_flush.elapsed() >= Duration::from_secs(1) {
-                                    let _ = writer.flush();
+                                    ok &= fsync(&mut writer).is_ok();
                                     last_flush = std::time::Instant::now();
                                 }
                             }
@@ -68,17 +125,27 @@ impl AofManager {
                                 // Let OS handle it
                             }
                         }
+                        write_status.store(ok, Ordering::Relaxed);
                     }
                     Err(mpsc::RecvTimeoutError::Timeout) => {
-                        // Periodic flush on timeout (for EverySec policy)
-                        if fsync_policy == FsyncPolicy::EverySec {
-                            let _ = writer.flush();
-                            last_flush = std::time::Instant::now();
+                        // Periodic flush on timeout (for EverySec policy), with
+                        // the fsync once the second is up
+                        if FsyncPolicy::from_u8(policy_handle.load(Ordering::Relaxed)) == FsyncPolicy::EverySec {
+                            let ok = if last_flush.elapsed() >= Duration::from_secs(1) {
+                                last_flush = std::time::Instant::now();
+                                fsync(&mut writer)
+                            } else {
+                                writer.flush()
+                            };
+                            write_status.store(ok.is_ok(), Ordering::Relaxed);
                         }
                     }
                     Err(mpsc::RecvTimeoutError::Disconnected) => {
-                        // Channel closed, final flush and exit
-                        let _ = writer.flush();
+                        // Channel closed (shutdown): everything queued has been
+                        // written, make it durable and exit
+                        if let Err(e) = writer.flush().and_then(|_| writer.get_ref().sync_all()) {
+                            error!("AOF: final fsync failed: {}", e);
+                        }
                         break;
                     }
                 }
@@ -87,43 +154,125 @@ impl AofManager {
 
         Ok(Self {
             sender: parking_lot::Mutex::new(Some(tx)),
+            writer: parking_lot::Mutex::new(Some(writer_thread)),
             enabled: AtomicBool::new(enabled),
+            path: file_path,
+            write_ok,
+            selected: AtomicUsize::new(usize::MAX),
             fsync_policy,
         })
     }
 
-    pub fn append(&self, command: &str) -> Result<()> {
+    /// Log one command run against database `db`. Arguments are stored as
+    /// a RESP multi-bulk request, so binary keys and values replay byte for byte.
+    pub fn append(&self, db: usize, args: &[&[u8]]) -> Result<()> {
         if !self.enabled.load(Ordering::Relaxed) {
             return Ok(());
         }
+        let mut buf = Vec::with_capacity(48 + args.iter().map(|a| a.len() + 16).sum::<usize>());
+        encode_command(&mut buf, args);
         // Non-blocking send to background writer
-        if let Some(ref tx) = *self.sender.lock() {
-            let _ = tx.send(command.to_string());
+        let sender = self.sender.lock();
+        if let Some(ref tx) = *sender {
+            if self.selected.swap(db, Ordering::Relaxed) != db {
+                let mut select = Vec::with_capacity(32);
+                encode_command(&mut select, &[b"SELECT", db.to_string().as_bytes()]);
+                let _ = tx.send(select);
+            }
+            let _ = tx.send(buf);
         }
         Ok(())
     }
 
+    /// Stop accepting writes, drain the queue to disk and fsync.
+    /// Blocks until the writer thread has exited.
+    pub fn shutdown(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+        drop(self.sender.lock().take());
+        if let Some(handle) = self.writer.lock().take() {
+            if handle.join().is_err() {
+                error!("AOF: writer thread panicked");
+            }
+        }
+        info!("AOF: flushed and closed");
+    }
+
     pub fn enable(&self) {
+        // Replay may have left the file on any database
+        self.selected.store(usize::MAX, Ordering::Relaxed);
         self.enabled.store(true, Ordering::Relaxed);
     }
 
     pub fn disable(&self) {
         self.enabled.store(false, Ordering::Relaxed);
     }
+
+    pub fn set_policy(&self, policy: FsyncPolicy) {
+        self.fsync_policy.store(policy as u8, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn last_write_ok(&self) -> bool {
+        self.write_ok.load(Ordering::Relaxed)
+    }
+
+    /// Current size of the log file in bytes.
+    pub fn file_size(&self) -> u64 {
+        std::fs::metadata(&self.path).map_or(0, |m| m.len())
+    }
+}
+
+fn encode_command(buf: &mut Vec<u8>, args: &[&[u8]]) {
+    buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
 }
 
 pub struct Persistence;
 
 impl Persistence {
-    pub fn save_rdb(db: &Arc<Db>, path: &str) -> Result<()> {
+    /// Count a write for rdb_changes_since_last_save.
+    pub fn record_change() {
+        CHANGES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rdb_status() -> RdbStatus {
+        RdbStatus {
+            changes_since_last_save: CHANGES.load(Ordering::Relaxed),
+            last_save_time: LAST_SAVE_TIME.load(Ordering::Relaxed),
+            last_save_ok: LAST_SAVE_OK.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn save_rdb(keyspace: &Keyspace, path: &str) -> Result<()> {
+        // Writes landing during the save count towards the next one
+        let changes = CHANGES.load(Ordering::Relaxed);
+        let result = latency::measure(latency::SAVE, || Self::write_rdb(keyspace, path));
+        LAST_SAVE_OK.store(result.is_ok(), Ordering::Relaxed);
+        if result.is_ok() {
+            CHANGES.fetch_sub(changes, Ordering::Relaxed);
+            LAST_SAVE_TIME.store(unix_secs(), Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn write_rdb(keyspace: &Keyspace, path: &str) -> Result<()> {
         let tmp_path = format!("{}.tmp", path);
         let file = File::create(&tmp_path)?;
         let mut writer = BufWriter::new(file);
 
         info!("Starting Bincode RDB save to {}", path);
         
+        writer.write_all(RDB_MAGIC)?;
+        writer.write_all(&RDB_VERSION.to_le_bytes())?;
         // God Tier: Bincode Serialize directly to disk stream
-        bincode::serialize_into(&mut writer, &**db)?;
+        bincode::serialize_into(&mut writer, keyspace)?;
         
         writer.flush()?;
         // Ensure file is closed/flushed before rename
@@ -136,14 +285,151 @@ impl Persistence {
         Ok(())
     }
 
-    pub fn load_rdb(path: &str) -> Result<Arc<Db>> {
+    /// Read every command logged in an AOF file.
+    /// Files written before the RESP format (one space-separated command per line) are still accepted.
+    pub fn read_aof(path: &str) -> Result<Vec<RespFrame>> {
+        let data = std::fs::read(path)?;
+        let mut commands = Vec::new();
+
+        if data.first() == Some(&b'*') {
+            let mut input = &data[..];
+            while !input.is_empty() {
+                match parse_frame(input) {
+                    Ok((rest, frame)) => {
+                        commands.push(frame);
+                        input = rest;
+                    }
+                    Err(_) => {
+                        // Truncated tail (crash mid-write): keep what was complete
+                        error!("AOF: Ignoring {} trailing bytes that do not form a complete command", input.len());
+                        break;
+                    }
+                }
+            }
+        } else {
+            // Legacy text format
+            for line in data.split(|b| *b == b'\n') {
+                let parts: Vec<RespFrame> = line
+                    .split(|b| b.is_ascii_whitespace())
+                    .filter(|p| !p.is_empty())
+                    .map(|p| RespFrame::bulk(Bytes::copy_from_slice(p)))
+                    .collect();
+                if !parts.is_empty() {
+                    commands.push(RespFrame::Array(Some(parts)));
+                }
+            }
+        }
+        Ok(commands)
+    }
+
+    /// Write the whole dataset to the AOF as one RESTORE per live key.
+    /// With appendonly the next start loads the AOF alone, so an AOF begun
+    /// over an RDB-loaded dataset has to carry that dataset too.
+    pub fn seed_aof(keyspace: &Keyspace, aof: &AofManager) -> usize {
+        let mut seeded = 0;
+        for db in keyspace.iter() {
+            for key in db.keys(b"*") {
+                let Some(Ok(payload)) = db.dump(&key) else { continue };
+                // -1 (no deadline) becomes RESTORE's 0
+                let at = db.expire_time(&key).max(0).to_string();
+                if let Err(e) = aof.append(db.index(), &[b"RESTORE", &key, at.as_bytes(), &payload, b"ABSTTL", b"REPLACE"]) {
+                    error!("AOF error: {}", e);
+                }
+                seeded += 1;
+            }
+        }
+        seeded
+    }
+
+    /// Like Redis, rdb_last_save_time starts at startup.
+    pub fn reset_save_time() {
+        LAST_SAVE_TIME.store(unix_secs(), Ordering::Relaxed);
+    }
+
+    /// Load a snapshot into a keyspace of `databases` databases.
+    pub fn load_rdb(path: &str, databases: usize) -> Result<Keyspace> {
+        Self::reset_save_time();
         info!("Loading RDB from {}", path);
         let file = File::open(path)?;
-        let reader = std::io::BufReader::new(file);
-        
+        let mut reader = std::io::BufReader::new(file);
+
+        let mut header = [0u8; 12];
+        let versioned = reader.read_exact(&mut header).is_ok() && &header[..8] == RDB_MAGIC;
+        if !versioned {
+            // Pre-expiry format: the keyspace map with no header
+            let reader = std::io::BufReader::new(File::open(path)?);
+            let map: HashMap<Bytes, DataType> = bincode::deserialize_from(reader)?;
+            return Self::keyspace(databases, vec![(0, Db::from_parts(map, HashMap::new()))]);
+        }
+        let version = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        if version > RDB_VERSION {
+            anyhow::bail!("RDB format version {} is newer than supported ({})", version, RDB_VERSION);
+        }
+
         // God Tier: Streaming Deserialize
-        let db: Db = bincode::deserialize_from(reader)?;
-        
-        Ok(Arc::new(db))
+        let dbs: Vec<(usize, Db)> = if version == 1 {
+            vec![(0, bincode::deserialize_from(reader)?)]
+        } else {
+            let dbs: Vec<(u32, Db)> = bincode::deserialize_from(reader)?;
+            dbs.into_iter().map(|(i, db)| (i as usize, db)).collect()
+        };
+        Self::keyspace(databases, dbs)
+    }
+
+    fn keyspace(databases: usize, dbs: Vec<(usize, Db)>) -> Result<Keyspace> {
+        Keyspace::from_snapshot(databases, dbs).map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::expire::now_ms;
+
+    fn rdb_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("zedis-rdb-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn rdb_round_trips_every_database() {
+        let keyspace = Keyspace::new(4);
+        let deadline = now_ms() + 3_600_000;
+        keyspace.db(0).set_string(Bytes::from("k"), Bytes::from("zero"));
+        keyspace.db(2).set_string(Bytes::from("k"), Bytes::from("two"));
+        assert!(keyspace.db(2).set_expire(b"k", deadline));
+        keyspace.db(3).list_push(Bytes::from("l"), Bytes::from("a")).unwrap();
+        keyspace.db(3).list_push(Bytes::from("l"), Bytes::from("b")).unwrap();
+
+        let path = rdb_path("multi.rdb");
+        Persistence::save_rdb(&keyspace, &path).unwrap();
+        let loaded = Persistence::load_rdb(&path, 4).unwrap();
+
+        assert_eq!(loaded.len(), 4);
+        for (index, db) in loaded.iter().enumerate() {
+            assert_eq!(db.index(), index);
+        }
+        assert_eq!(loaded.db(0).get_string(b"k").unwrap(), Some(Bytes::from("zero")));
+        assert_eq!(loaded.db(0).expire_time(b"k"), -1);
+        assert_eq!(loaded.db(1).len(), 0);
+        assert_eq!(loaded.db(2).get_string(b"k").unwrap(), Some(Bytes::from("two")));
+        assert_eq!(loaded.db(2).expire_time(b"k"), deadline as i64);
+        assert_eq!(loaded.db(3).list_range(b"l", 0, -1).unwrap(), vec![Bytes::from("a"), Bytes::from("b")]);
+
+        // More databases than saved: the extra ones start empty
+        let wider = Persistence::load_rdb(&path, 16).unwrap();
+        assert_eq!(wider.len(), 16);
+        assert_eq!(wider.db(2).get_string(b"k").unwrap(), Some(Bytes::from("two")));
+    }
+
+    #[test]
+    fn rdb_with_a_database_past_the_configured_count_is_refused() {
+        let keyspace = Keyspace::new(4);
+        keyspace.db(3).set_string(Bytes::from("k"), Bytes::from("v"));
+        let path = rdb_path("narrow.rdb");
+        Persistence::save_rdb(&keyspace, &path).unwrap();
+        let err = Persistence::load_rdb(&path, 2).err().expect("db 3 does not fit in 2 databases");
+        assert_eq!(err.to_string(), "snapshot has DB 3 but databases is 2");
     }
 }
@@ -1,7 +1,19 @@
 use mlua::{Lua, Result, IntoLua, Value, FromLua};
+use bytes::Bytes;
 use std::sync::Arc;
 use crate::core::storage::Db;
 use crate::persistence::AofManager;
+use crate::core::monitor;
+use crate::core::error::ZedisError;
+
+/// The error reply raised by the `redis.call` that ended a script, if any.
+pub fn call_error(e: &mlua::Error) -> Option<ZedisError> {
+    match e {
+        mlua::Error::CallbackError { cause, .. } => call_error(cause),
+        mlua::Error::ExternalError(e) => e.downcast_ref::<ZedisError>().cloned(),
+        _ => None,
+    }
+}
 
 thread_local! {
     static LUA: Lua = Lua::new();
@@ -15,13 +27,21 @@ impl ScriptEngine {
         Self {}
     }
 
-    pub fn eval(&self, script: &str, keys: Vec<String>, args: Vec<String>, db: Arc<Db>, aof: Arc<AofManager>) -> Result<String> {
+    pub fn eval(&self, script: &str, keys: Vec<Bytes>, args: Vec<Bytes>, db: Arc<Db>, aof: Arc<AofManager>) -> Result<Bytes> {
         LUA.with(|lua| {
             let globals = lua.globals();
             
-            // Inject KEYS and ARGV
-            globals.set("KEYS", keys)?;
-            globals.set("ARGV", args)?;
+            // Inject KEYS and ARGV (Lua strings are binary-safe)
+            let keys_tbl = lua.create_table()?;
+            for (i, k) in keys.iter().enumerate() {
+                keys_tbl.set(i + 1, lua.create_string(k)?)?;
+            }
+            let args_tbl = lua.create_table()?;
+            for (i, a) in args.iter().enumerate() {
+                args_tbl.set(i + 1, lua.create_string(a)?)?;
+            }
+            globals.set("KEYS", keys_tbl)?;
+            globals.set("ARGV", args_tbl)?;
 
             // Register 'redis' module
             let redis = lua.create_table()?;
@@ -32,31 +52,38 @@ impl ScriptEngine {
             // redis.call implementation
             // Note: In MVP, support basic GET/SET/INCR. Full dispatcher duplication is nice but verbosed.
             let call = lua.create_function(move |lua_ctx, args: mlua::MultiValue| -> Result<Value> {
-                let vec: Vec<String> = args.iter().filter_map(|v| String::from_lua(v.clone(), lua_ctx).ok()).collect();
+                let vec: Vec<Bytes> = args.iter().filter_map(|v| {
+                    mlua::String::from_lua(v.clone(), lua_ctx).ok().map(|s| Bytes::copy_from_slice(s.as_bytes()))
+                }).collect();
                 if vec.is_empty() { return Ok(Value::Nil); }
-                let cmd = vec[0].to_uppercase();
+                let cmd = String::from_utf8_lossy(&vec[0]).to_uppercase();
+                if monitor::is_active() {
+                    let args: Vec<&[u8]> = vec.iter().map(|a| a.as_ref()).collect();
+                    monitor::feed(db_clone.index(), "lua", &args);
+                }
                 
                 // Sync dispatch
                 match cmd.as_str() {
                     "GET" => {
                         if vec.len() < 2 { return Ok(Value::Nil); }
+                        // redis.call raises the error reply, as in Redis
                         match db_clone.get_string(&vec[1]) {
-                            Some(s) => return Ok(Value::String(lua_ctx.create_string(&s)?)),
-                            None => return Ok(Value::Nil),
+                            Ok(Some(s)) => return Ok(Value::String(lua_ctx.create_string(&s)?)),
+                            Ok(None) => return Ok(Value::Nil),
+                            Err(e) => return Err(mlua::Error::external(e)),
                         }
                     },
                     "SET" => {
                         if vec.len() < 3 { return Ok(Value::Nil); }
                         db_clone.set_string(vec[1].clone(), vec[2].clone());
-                        let _ = aof_clone.append(&format!("SET {} {}", vec[1], vec[2]));
+                        let _ = aof_clone.append(db_clone.index(), &[b"SET", &vec[1], &vec[2]]);
                         return Ok(Value::String(lua_ctx.create_string("OK")?));
                     },
                     "INCR" => {
                         if vec.len() < 2 { return Ok(Value::Nil); }
-                        if let Ok(v) = db_clone.incr_by(vec[1].clone(), 1) {
-                             let _ = aof_clone.append(&format!("INCR {}", vec[1]));
-                             return Ok(Value::Integer(v));
-                        }
+                        let v = db_clone.incr_by(vec[1].clone(), 1).map_err(mlua::Error::external)?;
+                        let _ = aof_clone.append(db_clone.index(), &[b"INCR", &vec[1]]);
+                        return Ok(Value::Integer(v));
                     },
                     // Add more mappings as needed (God Tier would map all)
                     _ => {
@@ -71,10 +98,10 @@ impl ScriptEngine {
             globals.set("redis", redis)?;
 
             match lua.load(script).eval::<Value>()? {
-                 Value::String(s) => Ok(s.to_str()?.to_string()),
-                 Value::Integer(i) => Ok(i.to_string()),
-                 Value::Boolean(b) => Ok(b.to_string()),
-                 _ => Ok("OK".to_string()), // Default/Nil
+                 Value::String(s) => Ok(Bytes::copy_from_slice(s.as_bytes())),
+                 Value::Integer(i) => Ok(Bytes::from(i.to_string())),
+                 Value::Boolean(b) => Ok(Bytes::from(b.to_string())),
+                 _ => Ok(Bytes::from_static(b"OK")), // Default/Nil
             }
         })
     }
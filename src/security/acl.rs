@@ -1,10 +1,15 @@
 use hashbrown::HashMap;
 use parking_lot::RwLock;
+use crate::core::executor::command::CommandSpec;
+use crate::core::glob::glob_match;
+use crate::core::protocol::RespFrame;
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct User {
     pub name: String,
+    /// Hex SHA-256 of the password (see `hash_password`); empty for `nopass`.
     pub password_hash: String,
     pub allowed_commands: Vec<String>, // Simplification for now, optimal would be a Bitmap
     pub allowed_keys: Vec<String>,     // Glob patterns
@@ -32,20 +37,48 @@ impl AclEngine {
         }
     }
 
-    pub fn check_permission(&self, username: &str, command: &str) -> bool {
+    /// May `username` run this command with these arguments? Both the
+    /// command rules and the key patterns must allow it.
+    pub fn check_permission(&self, username: &str, spec: &CommandSpec, args: &[RespFrame]) -> bool {
         // God Tier: Use parking_lot RwLock (no poison, faster)
         let users = self.users.read();
-        if let Some(user) = users.get(username) {
-            // Check Command Permission (Simple Glob-like check)
-            for pattern in &user.allowed_commands {
-                if pattern == "*" || pattern.eq_ignore_ascii_case(command) {
-                    return true;
-                }
+        let Some(user) = users.get(username) else { return false };
+        if !Self::command_allowed(user, spec) {
+            return false;
+        }
+        if user.allowed_keys.iter().any(|p| p == "*") {
+            return true;
+        }
+        spec.keys_of(args)
+            .iter()
+            .all(|key| user.allowed_keys.iter().any(|p| glob_match(p.as_bytes(), key, false)))
+    }
+
+    /// Command rules only, for places with no arguments to check (MONITOR).
+    pub fn can_run(&self, username: &str, spec: &CommandSpec) -> bool {
+        self.users.read().get(username).is_some_and(|user| Self::command_allowed(user, spec))
+    }
+
+    /// `*`, a command name or an `@category`.
+    fn command_allowed(user: &User, spec: &CommandSpec) -> bool {
+        user.allowed_commands.iter().any(|pattern| match pattern.strip_prefix('@') {
+            Some(category) => spec.in_category(category),
+            None => pattern == "*" || pattern.eq_ignore_ascii_case(spec.name),
+        })
+    }
+
+    /// Verify credentials (HELLO AUTH / AUTH). An empty password hash means `nopass`.
+    pub fn authenticate(&self, username: &str, password: &str) -> bool {
+        let users = self.users.read();
+        match users.get(username) {
+            Some(user) => {
+                user.password_hash.is_empty()
+                    || constant_time_eq(user.password_hash.as_bytes(), hash_password(password).as_bytes())
             }
+            None => false,
         }
-        false
     }
-    
+
     // God Tier: Add user management methods for production
     #[allow(dead_code)]
     pub fn add_user(&self, name: String, password_hash: String, commands: Vec<String>, keys: Vec<String>) {
@@ -64,3 +97,51 @@ impl AclEngine {
         users.remove(name).is_some()
     }
 }
+
+/// Hex SHA-256 of a password, the form Redis ACL keeps (`#<hash>` rules).
+pub fn hash_password(password: &str) -> String {
+    Sha256::digest(password.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare without an early exit, so timing doesn't reveal how many
+/// leading bytes of a guess were right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authenticate_checks_the_password_digest() {
+        let acl = AclEngine::new();
+        acl.add_user("alice".to_string(), hash_password("s3cret"), vec!["*".to_string()], vec!["*".to_string()]);
+
+        assert!(acl.authenticate("alice", "s3cret"));
+        assert!(!acl.authenticate("alice", "s3cre"));
+        // The stored digest is not itself a valid password
+        assert!(!acl.authenticate("alice", &hash_password("s3cret")));
+        assert!(acl.authenticate("default", "anything"));
+        assert!(!acl.authenticate("nobody", "s3cret"));
+    }
+
+    #[test]
+    fn password_digest_is_hex_sha256() {
+        assert_eq!(hash_password("foobar"), "c3ab8ff13720e8ad9047dd39466b3c8974e592c2fa383d4a3960714caef0c4f2");
+    }
+
+    #[test]
+    fn can_run_checks_command_rules_only() {
+        use crate::core::executor::command::lookup;
+        let acl = AclEngine::new();
+        acl.add_user("reader".to_string(), String::new(), vec!["@read".to_string(), "monitor".to_string()], vec!["app:*".to_string()]);
+
+        let (get, set, monitor) = (lookup("GET").unwrap(), lookup("SET").unwrap(), lookup("MONITOR").unwrap());
+        assert!(acl.can_run("reader", get));
+        assert!(acl.can_run("reader", monitor));
+        assert!(!acl.can_run("reader", set));
+        assert!(acl.can_run("default", set));
+        assert!(!acl.can_run("nobody", get));
+    }
+}
@@ -1,5 +1,5 @@
 use std::time::Instant;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 
@@ -41,8 +41,8 @@ impl TokenBucket {
 pub struct DdosGuard {
     // Map IP address to TokenBucket (using parking_lot for God Tier performance)
     limiters: Mutex<HashMap<std::net::IpAddr, (TokenBucket, Instant)>>,
-    capacity: u32,
-    rate: f64,
+    // Bucket burst size and refill rate, adjustable via CONFIG SET
+    limits: RwLock<(u32, f64)>,
     cleanup_threshold: usize, // Max entries before cleanup
 }
 
@@ -50,8 +50,7 @@ impl DdosGuard {
     pub fn new(capacity: u32, rate: f64) -> Self {
         Self {
             limiters: Mutex::new(HashMap::new()),
-            capacity,
-            rate,
+            limits: RwLock::new((capacity, rate)),
             cleanup_threshold: 10000, // Cleanup when > 10k IPs tracked
         }
     }
@@ -67,7 +66,8 @@ impl DdosGuard {
         }
 
         let entry = limiters.entry(ip).or_insert_with(|| {
-            (TokenBucket::new(self.capacity, self.rate), now)
+            let (capacity, rate) = *self.limits.read();
+            (TokenBucket::new(capacity, rate), now)
         });
         
         // Update last access time
@@ -75,6 +75,12 @@ impl DdosGuard {
         entry.0.allow()
     }
     
+    /// New limits apply to every client: existing buckets are dropped.
+    pub fn set_limits(&self, capacity: u32, rate: f64) {
+        *self.limits.write() = (capacity, rate);
+        self.limiters.lock().clear();
+    }
+
     // Remove entries not accessed in the last 5 minutes
     fn cleanup_stale_entries(&self, limiters: &mut HashMap<std::net::IpAddr, (TokenBucket, Instant)>, now: Instant) {
         let stale_threshold = std::time::Duration::from_secs(300); // 5 minutes
@@ -1,30 +1,142 @@
 use std::sync::Arc;
-use tokio_rustls::rustls::{ServerConfig, pki_types::{CertificateDer, PrivateKeyDer}};
+use std::path::{Path, PathBuf};
+use tokio_rustls::rustls::{RootCertStore, ServerConfig, pki_types::{CertificateDer, PrivateKeyDer}};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
 use tokio_rustls::TlsAcceptor;
+use notify::{Watcher, RecursiveMode, RecommendedWatcher, Config};
+use parking_lot::RwLock;
+use log::{info, error};
 use std::fs::File;
 use std::io::BufReader;
 use anyhow::Result;
 
-#[allow(dead_code)]
+/// Where the TLS material lives (`tls-cert-file`, `tls-key-file`, `tls-ca-cert-file`).
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    pub cert_path: String,
+    pub key_path: String,
+    /// CA bundle used to verify client certificates (mutual TLS).
+    pub ca_path: Option<String>,
+    /// With a CA configured: reject clients that present no certificate.
+    pub require_client_cert: bool,
+}
+
+/// Hot-swappable TLS acceptor. Reloading only affects new handshakes;
+/// established sessions keep the config they were accepted with.
 pub struct TlsConfig {
-    pub acceptor: TlsAcceptor,
+    settings: RwLock<TlsSettings>,
+    acceptor: RwLock<TlsAcceptor>,
 }
 
 impl TlsConfig {
-    pub fn load(cert_path: &str, key_path: &str) -> Result<Self> {
-        let certs = load_certs(cert_path)?;
-        let key = load_keys(key_path)?;
+    pub fn load(settings: TlsSettings) -> Result<Self> {
+        let acceptor = build_acceptor(&settings)?;
+        Ok(Self {
+            settings: RwLock::new(settings),
+            acceptor: RwLock::new(acceptor),
+        })
+    }
+
+    /// Acceptor for the next handshake.
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().clone()
+    }
+
+    /// Re-read cert/key/CA from disk. On error the previous config stays active.
+    pub fn reload(&self) -> Result<()> {
+        let acceptor = build_acceptor(&self.settings.read())?;
+        *self.acceptor.write() = acceptor;
+        Ok(())
+    }
+
+    /// Switch to other files (CONFIG SET tls-*). Nothing changes unless the
+    /// new material loads. The watcher keeps watching the startup directories.
+    pub fn update(&self, settings: TlsSettings) -> Result<()> {
+        let acceptor = build_acceptor(&settings)?;
+        *self.settings.write() = settings;
+        *self.acceptor.write() = acceptor;
+        Ok(())
+    }
+
+    /// Reload whenever one of the configured files changes on disk.
+    pub fn watch(self: Arc<Self>) {
+        let settings = self.settings.read().clone();
+        let mut files: Vec<PathBuf> = vec![
+            PathBuf::from(&settings.cert_path),
+            PathBuf::from(&settings.key_path),
+        ];
+        if let Some(ca) = &settings.ca_path {
+            files.push(PathBuf::from(ca));
+        }
 
-        let config = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, key)
-            .map_err(|e| anyhow::anyhow!("TLS Config Error: {}", e))?;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let watched = files.clone();
+        let watcher = RecommendedWatcher::new(move |res: notify::Result<notify::Event>| {
+             if let Ok(event) = res {
+                 if event.paths.iter().any(|p| watched.iter().any(|f| p.ends_with(f) || f.ends_with(p))) {
+                     let _ = tx.try_send(());
+                 }
+             }
+        }, Config::default());
 
-        let acceptor = TlsAcceptor::from(Arc::new(config));
-        Ok(Self { acceptor })
+        let mut w = match watcher {
+            Ok(w) => w,
+            Err(e) => {
+                error!("🔐 TLS: Watch failed: {}", e);
+                return;
+            }
+        };
+        // Watch the directories: renewals usually replace the files via rename
+        for file in &files {
+            let dir = file.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            if let Err(e) = w.watch(dir, RecursiveMode::NonRecursive) {
+                error!("🔐 TLS: Watch of '{}' failed: {}", dir.display(), e);
+            }
+        }
+
+        tokio::spawn(async move {
+            let _w = w; // Keep watcher alive
+            while rx.recv().await.is_some() {
+                // Let the writer finish replacing cert and key
+                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+                match self.reload() {
+                    Ok(()) => info!("🔐 TLS: Certificates reloaded"),
+                    Err(e) => error!("🔐 TLS: Reload failed, keeping previous certificates: {}", e),
+                }
+            }
+        });
     }
 }
 
+fn build_acceptor(settings: &TlsSettings) -> Result<TlsAcceptor> {
+    let certs = load_certs(&settings.cert_path)?;
+    let key = load_keys(&settings.key_path)?;
+
+    let builder = match &settings.ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots.add(cert).map_err(|e| anyhow::anyhow!("TLS CA Error: {}", e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if settings.require_client_cert {
+                verifier.build()
+            } else {
+                verifier.allow_unauthenticated().build()
+            }
+            .map_err(|e| anyhow::anyhow!("TLS Client Verifier Error: {}", e))?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+
+    let config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| anyhow::anyhow!("TLS Config Error: {}", e))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
 fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
     let file = File::open(path)?;
     let mut reader = BufReader::new(file);
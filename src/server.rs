@@ -1,149 +1,496 @@
-use crate::config::Config;
-use crate::core::storage::Db;
-use crate::core::executor::Dispatcher;
+use crate::config::{Config, ConfigStore};
+use crate::core::keyspace::Keyspace;
+use crate::core::executor::{Dispatcher, Subsystems};
+use crate::core::client::Client;
+use crate::core::error::ZedisError;
+use crate::core::protocol::ProtocolLimits;
+use crate::core::stats::ServerStats;
 use crate::io::connection::Connection;
+use crate::io::traits::AsyncStream;
 use crate::security::ddos_guard::DdosGuard;
-use crate::hardware::HardwareManager;
-use crate::security::tls::TlsConfig;  // TlsConfig logic present
+use crate::hardware::{HardwareManager, MemoryAdvice};
+use crate::core::evict::{EvictionPolicy, Evictor};
+use crate::core::notify;
+use crate::core::latency;
+use crate::security::tls::TlsConfig;
+use crate::shutdown::{SaveMode, Shutdown};
+use tokio_util::task::TaskTracker;
 use tokio::net::TcpListener;
 use log::{info, error, warn};
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::core::ai::BgeM3;
-use crate::compatibility::elastic::ElasticMask;
+use crate::compatibility::elastic::{ElasticMask, MaskStatus};
 use crate::flow::manager::FlowManager;
 use crate::persistence::AofManager;
 
-pub async fn run(config: Config) -> anyhow::Result<()> {
+pub async fn run(mut config: Config) -> anyhow::Result<()> {
+    // Like Redis, `dir` becomes the working directory for every relative path
+    std::env::set_current_dir(&config.dir)
+        .map_err(|e| anyhow::anyhow!("Can't chdir to '{}': {}", config.dir, e))?;
+    config.dir = std::env::current_dir()?.to_string_lossy().into_owned();
+    // Runtime view for CONFIG GET/SET; `config` stays the startup snapshot
+    let store = Arc::new(ConfigStore::new(config.clone()));
+
     // Hardware Setup
     let hw_manager = Arc::new(HardwareManager::new());
     
     // Check SIMD support
     hw_manager.check_simd_support();
     
-    // Run Auto-Tuner
-    hw_manager.loop_recommendations();
+    // Run Auto-Tuner; its memory advice fills in maxmemory settings left on `auto`
+    let advice = hw_manager.loop_recommendations();
+    let evictor = {
+        let (maxmemory, policy) = memory_limits(&config, advice);
+        info!("🧹 Eviction: maxmemory {} bytes, policy {}", maxmemory, policy.name());
+        Arc::new(Evictor::new(maxmemory, policy, config.maxmemory_samples))
+    };
 
     // Pin the main server thread to Core 0 (Optional, but good for acceptor)
     hw_manager.pin_thread(0);
 
-    let addr = format!("{}:{}", config.host, config.port);
-    let listener = TcpListener::bind(&addr).await?;
+    // Bind listeners up front so a bad address or socket path fails fast.
+    // `port 0` disables TCP, like Redis, for unix-socket-only deployments.
+    let tcp_listener = if config.port != 0 {
+        let addr = format!("{}:{}", config.host, config.port);
+        Some(TcpListener::bind(&addr).await?)
+    } else {
+        None
+    };
+    #[cfg(unix)]
+    let unix_listener = match &config.unixsocket {
+        Some(path) => Some(crate::io::listener::bind_unix(path, config.unixsocketperm)?),
+        None => None,
+    };
+    let tls_listener = if config.tls_port != 0 {
+        let tls = Arc::new(TlsConfig::load(config.tls_settings())?);
+        tls.clone().watch();
+        let addr = format!("{}:{}", config.host, config.tls_port);
+        Some((TcpListener::bind(&addr).await?, tls))
+    } else {
+        None
+    };
     
     // Initialize Shared Storage Engine
-    let db = match crate::persistence::Persistence::load_rdb("dump.rdb") {
-        Ok(d) => {
-            info!("📦 RDB: Loaded snapshot successfully.");
-            d
-        },
-        Err(e) => {
-            info!("📦 RDB: No snapshot found or load failed ({}), starting fresh.", e);
-            Arc::new(Db::new(1024))
-        }
-    };
+    let from_aof = aof_is_authoritative(&config);
+    let keyspace = Arc::new(open_keyspace(&config, from_aof));
+    // Z-Flow and the Elastic mask work on database 0
+    let db = keyspace.db(0).clone();
     
     // Initialize AOF Manager (God Tier Persistence)
     // Start disabled to prevent AOF amplification during replay
-    let aof = Arc::new(AofManager::new("appendonly.aof", false)?);
+    let aof = Arc::new(AofManager::with_policy(&config.appendfilename, false, config.appendfsync)?);
 
     // Initialize BGE-M3 (Universe Tier)
-    let bge_model = match BgeM3::new("bge-m3") {
+    let bge_model = match BgeM3::new(&config.bge_model_dir) {
         Ok(m) => {
             log::info!("🧠 Universe Tier: BGE-M3 Loaded Successfully");
             Some(Arc::new(m))
         },
         Err(e) => {
-            log::warn!("⚠️ Universe Tier: BGE-M3 Failed to Load (Check 'bge-m3' dir): {}", e);
+            log::warn!("⚠️ Universe Tier: BGE-M3 Failed to Load (Check '{}' dir): {}", config.bge_model_dir, e);
             None
         }
     };
 
+    // Z-Flow is started once the AOF is replayed; the Elastic mask reports
+    // its listener state through `mask_status`
+    let flow_mgr = Arc::new(FlowManager::new(db.clone(), bge_model.clone()));
+    let mask_status = Arc::new(MaskStatus::default());
+
     let dispatcher = Arc::new(Dispatcher::new(
-        db.clone(), 
+        keyspace.clone(), 
         aof.clone(), 
-        config.shadow_addr.clone(), 
-        bge_model.clone()
+        store.clone(), 
+        bge_model.clone(),
+        evictor.clone(),
+        Subsystems { hw: hw_manager.clone(), flow: flow_mgr.clone(), mask: mask_status.clone() },
     ));
+    dispatcher.clients().set_output_limits(config.client_output_buffer_limits);
+    notify::set_flags(config.notify_keyspace_events);
+    latency::set_threshold(config.latency_monitor_threshold);
 
     // 📜 AOF Replay (God Tier Recovery)
-    use std::io::BufRead;
-    use crate::core::protocol::RespFrame;
-    
-    if let Ok(file) = std::fs::File::open("appendonly.aof") {
-        info!("🔄 AOF: Replaying commands...");
-        let reader = std::io::BufReader::new(file);
-        let mut count = 0;
-        for line in reader.lines() {
-             if let Ok(l) = line {
-                 if l.trim().is_empty() { continue; }
-                 // Basic Text Protocol Parser for Legacy AOF
-                 let parts: Vec<String> = l.split_whitespace().map(|s| s.to_string()).collect();
-                 if parts.is_empty() { continue; }
-                 
-                 let frame_parts: Vec<RespFrame> = parts.into_iter().map(|s| RespFrame::BulkString(Some(s))).collect();
-                 let frame = RespFrame::Array(Some(frame_parts));
-                 
-                 // Execute synchronously in main loop (await)
-                 let _ = dispatcher.execute(frame).await;
-                 count += 1;
-             }
-        }
-        info!("✅ AOF: Replayed {} commands.", count);
+    if from_aof {
+        replay_aof(&dispatcher, &config.appendfilename).await;
     }
     
     // Enable AOF for new writes
-    aof.enable();
+    if config.appendonly {
+        aof.enable();
+        if !from_aof {
+            let seeded = crate::persistence::Persistence::seed_aof(&keyspace, &aof);
+            info!("📜 AOF: Started from the loaded dataset ({} keys)", seeded);
+        }
+    }
 
     // Initialize Security Logic
-    let ddos_guard = Arc::new(DdosGuard::new(1000, 100.0)); // 1000 burst, 100 req/s
+    let ddos_guard = Arc::new(DdosGuard::new(config.ddos_burst, config.ddos_rate));
 
-    // 🎭 Z-Mask: Protocol Emulation (Spawned separate task)
+    // 🛑 Graceful shutdown on SIGTERM / SIGINT / SHUTDOWN
+    let shutdown = dispatcher.shutdown();
     {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown.listen_for_signals().await;
+        });
+    }
+
+    // ⏳ Active expiry: volatile keys nobody reads again are reclaimed here
+    {
+        let keyspace = keyspace.clone();
+        let token = shutdown.token();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_millis(100));
+            let mut cursors = vec![0; keyspace.len()];
+            let mut first = 0;
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {}
+                    _ = token.cancelled() => break,
+                }
+                // 25ms per tick shared by all databases; the starting one
+                // rotates so none is starved
+                let started = std::time::Instant::now();
+                let mut removed = 0;
+                for i in 0..keyspace.len() {
+                    let index = (first + i) % keyspace.len();
+                    let db = keyspace.db(index);
+                    let budget = Duration::from_millis(25).saturating_sub(started.elapsed());
+                    if budget.is_zero() {
+                        break;
+                    }
+                    if db.volatile_len() == 0 {
+                        continue;
+                    }
+                    let (next, n) = db.active_expire_cycle(cursors[index], budget);
+                    cursors[index] = next;
+                    removed += n;
+                }
+                first = (first + 1) % keyspace.len();
+                latency::add_sample(latency::EXPIRE_CYCLE, started.elapsed());
+                if removed > 0 {
+                    log::debug!("Active expiry removed {} key(s)", removed);
+                }
+            }
+        });
+    }
+
+    // 📈 INFO sampling: ops/sec and peak memory, every 100ms like Redis's cron
+    {
+        let stats = dispatcher.stats();
+        let token = shutdown.token();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_millis(100));
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {}
+                    _ = token.cancelled() => break,
+                }
+                stats.sample_ops();
+                crate::core::memory::peak_memory();
+            }
+        });
+    }
+
+    // 🎭 Z-Mask: Protocol Emulation (Spawned separate task)
+    let mask_task = (config.elastic_port != 0).then(|| {
         let mask = ElasticMask {
             db: db.clone(),
             bge: bge_model.clone(),
+            status: mask_status.clone(),
         };
+        let token = shutdown.token();
+        let port = config.elastic_port;
+        tokio::spawn(async move {
+            mask.run(port, token).await;
+        })
+    });
+
+    // 🌊 Z-Flow: Zero-ETL Sync (Spawned separate task)
+    {
+        let flow_mgr = flow_mgr.clone();
+        let path = config.zflow_config.clone();
         tokio::spawn(async move {
-            mask.run(9200).await;
+            flow_mgr.run(path).await;
         });
     }
 
-    // 🌊 Z-Flow: Zero-ETL Sync (Spawned separate task)
+    // ⚙️ CONFIG SET apply hooks. Connection settings (maxclients, timeout,
+    // tcp-keepalive, protocol limits) are read from the store per accept
     {
-        let flow_mgr = Arc::new(FlowManager::new(db.clone(), bge_model.clone()));
-        // In "Universe Tier", we don't hardcode paths. Ideally config based.
-        // But per strategy: defaults to zflow.toml locally.
+        let aof = aof.clone();
+        store.on_change(&["appendfsync"], move |c| {
+            aof.set_policy(c.appendfsync);
+            Ok(())
+        });
+    }
+    {
+        let aof = aof.clone();
+        store.on_change(&["appendonly"], move |c| {
+            if c.appendonly { aof.enable() } else { aof.disable() }
+            Ok(())
+        });
+    }
+    {
+        let evictor = evictor.clone();
+        store.on_change(&["maxmemory", "maxmemory-policy", "maxmemory-samples"], move |c| {
+            let (maxmemory, policy) = memory_limits(c, advice);
+            evictor.configure(maxmemory, policy, c.maxmemory_samples);
+            Ok(())
+        });
+    }
+    store.on_change(&["notify-keyspace-events"], |c| {
+        notify::set_flags(c.notify_keyspace_events);
+        Ok(())
+    });
+    store.on_change(&["latency-monitor-threshold"], |c| {
+        latency::set_threshold(c.latency_monitor_threshold);
+        Ok(())
+    });
+    {
+        let slowlog = dispatcher.slowlog();
+        store.on_change(&["slowlog-log-slower-than", "slowlog-max-len"], move |c| {
+            slowlog.configure(c.slowlog_log_slower_than, c.slowlog_max_len);
+            Ok(())
+        });
+    }
+    {
+        let ddos_guard = ddos_guard.clone();
+        store.on_change(&["ddos-burst", "ddos-rate"], move |c| {
+            ddos_guard.set_limits(c.ddos_burst, c.ddos_rate);
+            Ok(())
+        });
+    }
+    {
+        let clients = dispatcher.clients();
+        store.on_change(&["client-output-buffer-limit"], move |c| {
+            clients.set_output_limits(c.client_output_buffer_limits);
+            Ok(())
+        });
+    }
+    {
+        let flow_mgr = flow_mgr.clone();
+        store.on_change(&["zflow-config"], move |c| {
+            let flow_mgr = flow_mgr.clone();
+            let path = c.zflow_config.clone();
+            tokio::spawn(async move {
+                flow_mgr.stop().await;
+                flow_mgr.run(path).await;
+            });
+            Ok(())
+        });
+    }
+    if let Some((_, tls)) = &tls_listener {
+        let tls = tls.clone();
+        store.on_change(&["tls-cert-file", "tls-key-file", "tls-ca-cert-file", "tls-auth-clients"], move |c| {
+            tls.update(c.tls_settings()).map_err(|e| e.to_string())
+        });
+    }
+    store.on_change(&["dir"], |c| std::env::set_current_dir(&c.dir).map_err(|e| e.to_string()));
+
+    let shared = Listener {
+        dispatcher,
+        hw: hw_manager,
+        ddos_guard,
+        config: store.clone(),
+        shutdown: shutdown.clone(),
+        tracker: TaskTracker::new(),
+    };
+
+    let mut servers = tokio::task::JoinSet::new();
+    if let Some(listener) = tcp_listener {
+        info!("Zedis listening on {}", listener.local_addr()?);
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        {
+            let workers = spawn_uring_workers(listener, shared.clone(), config.worker_threads)?;
+            servers.spawn_blocking(move || {
+                for worker in workers {
+                    let _ = worker.join();
+                }
+            });
+        }
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        servers.spawn(serve_tcp(listener, shared.clone()));
+    }
+    if let Some((listener, tls)) = tls_listener {
+        info!("Zedis listening on {} (TLS)", listener.local_addr()?);
+        servers.spawn(serve_tls(listener, tls, shared.clone()));
+    }
+    #[cfg(unix)]
+    if let Some(listener) = unix_listener {
+        info!("Zedis listening on unix socket {}", config.unixsocket.as_deref().unwrap_or_default());
+        servers.spawn(serve_unix(listener, config.unixsocket.clone().unwrap_or_default(), shared.clone()));
+    }
+
+    // Accept loops run until shutdown is requested
+    {
+        let tracker = shared.tracker.clone();
+        let shutdown = shutdown.clone();
         tokio::spawn(async move {
-            flow_mgr.run("zflow.toml".to_string()).await;
+            shutdown.requested().await;
+            tracker.close();
         });
     }
+    while let Some(res) = servers.join_next().await {
+        if let Err(e) = res {
+            error!("Listener task failed: {}", e);
+        }
+    }
 
-    info!("Zedis listening on {}", addr);
+    let config = store.read().clone();
+
+    // 1. No new connections are accepted; let in-flight commands finish
+    info!("🛑 Shutdown: waiting up to {:?} for {} connection(s)", config.shutdown_timeout, shared.tracker.len());
+    if tokio::time::timeout(config.shutdown_timeout, shared.tracker.wait()).await.is_err() {
+        warn!("🛑 Shutdown: timed out, dropping {} connection(s)", shared.tracker.len());
+    }
+
+    // 2. Stop background producers of writes
+    flow_mgr.stop().await;
+    if let Some(mask_task) = mask_task {
+        if tokio::time::timeout(config.shutdown_timeout, mask_task).await.is_err() {
+            warn!("🛑 Shutdown: Z-Mask did not stop in time");
+        }
+    }
+
+    // 3. Make the AOF durable, then the optional final snapshot
+    aof.shutdown();
+    let save = match shutdown.save_mode() {
+        SaveMode::Save => true,
+        SaveMode::NoSave => false,
+        SaveMode::Default => config.save_on_shutdown,
+    };
+    if save {
+        match crate::persistence::Persistence::save_rdb(&keyspace, &config.dbfilename) {
+            Ok(()) => info!("🛑 Shutdown: RDB saved"),
+            Err(e) => error!("🛑 Shutdown: RDB save failed: {}", e),
+        }
+    }
+
+    #[cfg(unix)]
+    if let Some(path) = &config.unixsocket {
+        let _ = std::fs::remove_file(path);
+    }
+
+    info!("Zedis is now ready to exit, bye bye...");
+    Ok(())
+}
+
+/// With appendonly, a non-empty AOF holds every write (the RDB-loaded
+/// dataset it started from included), so it is loaded alone, as Redis
+/// does. Loading the RDB under it would apply those writes twice.
+fn aof_is_authoritative(config: &Config) -> bool {
+    config.appendonly && std::fs::metadata(&config.appendfilename).is_ok_and(|m| m.len() > 0)
+}
+
+/// Startup dataset: empty when the AOF is about to be replayed, the RDB
+/// snapshot otherwise.
+fn open_keyspace(config: &Config, from_aof: bool) -> Keyspace {
+    if from_aof {
+        info!("📦 RDB: Skipped, the AOF is loaded instead");
+        crate::persistence::Persistence::reset_save_time();
+        return Keyspace::new(config.databases);
+    }
+    match crate::persistence::Persistence::load_rdb(&config.dbfilename, config.databases) {
+        Ok(k) => {
+            info!("📦 RDB: Loaded snapshot successfully.");
+            k
+        },
+        Err(e) => {
+            info!("📦 RDB: No snapshot found or load failed ({}), starting fresh.", e);
+            Keyspace::new(config.databases)
+        }
+    }
+}
+
+async fn replay_aof(dispatcher: &Dispatcher, path: &str) {
+    info!("🔄 AOF: Replaying commands...");
+    match crate::persistence::Persistence::read_aof(path) {
+        Ok(commands) => {
+            let count = commands.len();
+            let replay_client = Client::internal("aof");
+            for frame in commands {
+                // Execute synchronously in main loop (await)
+                let _ = dispatcher.execute(&replay_client, frame).await;
+            }
+            info!("✅ AOF: Replayed {} commands.", count);
+        }
+        Err(e) => error!("AOF: Replay failed: {}", e),
+    }
+}
+
+/// Per-connection settings applied by `handle_connection`.
+#[derive(Clone, Copy)]
+struct SessionConfig {
+    limits: ProtocolLimits,
+    maxclients: usize,
+}
 
+/// State shared by every accept loop.
+#[derive(Clone)]
+struct Listener {
+    dispatcher: Arc<Dispatcher>,
+    hw: Arc<HardwareManager>,
+    ddos_guard: Arc<DdosGuard>,
+    config: Arc<ConfigStore>,
+    shutdown: Arc<Shutdown>,
+    /// Live connection tasks, awaited on shutdown.
+    tracker: TaskTracker,
+}
+
+impl Listener {
+    /// Settings for a connection accepted now.
+    fn session(&self) -> SessionConfig {
+        let config = self.config.read();
+        SessionConfig {
+            limits: config.protocol_limits(),
+            maxclients: config.maxclients,
+        }
+    }
+
+    fn tcp_keepalive(&self) -> Option<Duration> {
+        let keepalive = self.config.read().tcp_keepalive;
+        (!keepalive.is_zero()).then_some(keepalive)
+    }
+}
+
+#[cfg_attr(all(target_os = "linux", feature = "io-uring"), allow(dead_code))]
+async fn serve_tcp(listener: TcpListener, shared: Listener) {
     let mut validator_counter: usize = 0; // Simple round-robin for pinning workers
 
     loop {
-        match listener.accept().await {
+        let accepted = tokio::select! {
+            _ = shared.shutdown.requested() => break,
+            res = listener.accept() => res,
+        };
+        match accepted {
             Ok((socket, addr)) => {
                 // DDoS Check
-                if !ddos_guard.check_connection(&addr) {
+                if !shared.ddos_guard.check_connection(&addr) {
                     warn!("Connection rejected from {} (Rate Limit Exceeded)", addr);
                     continue;
                 }
 
                 info!("Accepted connection from {}", addr);
-                let dispatcher = dispatcher.clone();
-                let hw = hw_manager.clone();
+
+                tune_tcp(&socket, shared.tcp_keepalive());
+
+                let laddr = socket.local_addr().map(|a| a.to_string()).unwrap_or_default();
+                let dispatcher = shared.dispatcher.clone();
+                let hw = shared.hw.clone();
+                let session = shared.session();
                 validator_counter = validator_counter.wrapping_add(1); // God Tier: Prevent overflow panic
                 let core_idx = validator_counter;
 
-                tokio::spawn(async move {
+                shared.tracker.spawn(async move {
                     // Best effort pinning for worker task
                     // Note: In a pure Tokio runtime, pinning tasks is non-deterministic 
                     // without a custom runtime builder, but this sets affinity for the OS thread execution context temporarily.
                     hw.pin_thread(core_idx); 
 
-                    if let Err(e) = handle_connection(socket, dispatcher).await {
+                    if let Err(e) = handle_connection(socket, dispatcher, session, addr.to_string(), laddr).await {
                         error!("Connection error: {}", e);
                     }
                 });
@@ -155,89 +502,661 @@ pub async fn run(config: Config) -> anyhow::Result<()> {
     }
 }
 
-async fn handle_connection(socket: tokio::net::TcpStream, dispatcher: Arc<Dispatcher>) -> anyhow::Result<()> {
-    // God Tier Security: Auto-detect if TLS is configured
-    let _tls_enabled = TlsConfig::load("cert.pem", "key.pem").is_ok();
-    
-    // Performance Optimization: Disable Nagle's algorithm
+/// io_uring backend: one ring per pinned worker thread, all accepting on the
+/// same listening socket. Sessions use the same `Connection`/`handle_connection`
+/// path as epoll, only the transport differs.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+fn spawn_uring_workers(listener: TcpListener, shared: Listener, workers: usize) -> anyhow::Result<Vec<std::thread::JoinHandle<()>>> {
+    let std_listener = listener.into_std()?;
+    // Accepted sockets inherit TCP_NODELAY and keepalive from the listener
+    socket2::SockRef::from(&std_listener).set_nodelay(true)?;
+    if let Some(keepalive) = shared.tcp_keepalive() {
+        socket2::SockRef::from(&std_listener).set_tcp_keepalive(&keepalive_params(keepalive))?;
+    }
+    info!("⚡ io_uring: starting {} ring workers", workers.max(1));
+
+    let mut handles = Vec::new();
+    for worker in 0..workers.max(1) {
+        let std_listener = std_listener.try_clone()?;
+        let shared = shared.clone();
+        let handle = std::thread::Builder::new()
+            .name(format!("zedis-uring-{}", worker))
+            .spawn(move || {
+                shared.hw.pin_thread(worker);
+                tokio_uring::start(serve_uring(std_listener, shared));
+            })?;
+        handles.push(handle);
+    }
+    Ok(handles)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+async fn serve_uring(std_listener: std::net::TcpListener, shared: Listener) {
+    use crate::io::uring::UringStream;
+
+    // tokio-uring 0.4 can't adopt a listening socket, so accepts go through
+    // the reactor that `tokio_uring::start` runs; each accepted socket is then
+    // handed to the ring
+    let listener = match TcpListener::from_std(std_listener) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("⚡ io_uring: listener setup failed: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        let accepted = tokio::select! {
+            _ = shared.shutdown.requested() => break,
+            res = listener.accept() => res,
+        };
+        match accepted {
+            Ok((socket, addr)) => {
+                if !shared.ddos_guard.check_connection(&addr) {
+                    warn!("Connection rejected from {} (Rate Limit Exceeded)", addr);
+                    continue;
+                }
+
+                info!("Accepted connection from {} (io_uring)", addr);
+                let laddr = socket.local_addr().map(|a| a.to_string()).unwrap_or_default();
+                // The ring completes reads and writes itself: the socket goes back to blocking mode
+                let socket = match socket.into_std().and_then(|s| s.set_nonblocking(false).map(|_| s)) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("io_uring socket handoff failed: {}", e);
+                        continue;
+                    }
+                };
+                let stream = UringStream::new(tokio_uring::net::TcpStream::from_std(socket));
+                let dispatcher = shared.dispatcher.clone();
+                let session = shared.session();
+                tokio_uring::spawn(shared.tracker.track_future(async move {
+                    if let Err(e) = handle_connection(stream, dispatcher, session, addr.to_string(), laddr).await {
+                        error!("Connection error: {}", e);
+                    }
+                }));
+            }
+            Err(e) => {
+                error!("io_uring accept error: {}", e);
+            }
+        }
+    }
+
+    // Connection tasks live on this ring: keep it running until they finish
+    let timeout = shared.config.read().shutdown_timeout;
+    let _ = tokio::time::timeout(timeout, shared.tracker.wait()).await;
+}
+
+/// Same admission rules as `serve_tcp`; the handshake runs in the
+/// connection task so a slow client can't stall the accept loop.
+async fn serve_tls(listener: TcpListener, tls: Arc<TlsConfig>, shared: Listener) {
+    loop {
+        let accepted = tokio::select! {
+            _ = shared.shutdown.requested() => break,
+            res = listener.accept() => res,
+        };
+        match accepted {
+            Ok((socket, addr)) => {
+                if !shared.ddos_guard.check_connection(&addr) {
+                    warn!("TLS connection rejected from {} (Rate Limit Exceeded)", addr);
+                    continue;
+                }
+                tune_tcp(&socket, shared.tcp_keepalive());
+
+                let acceptor = tls.acceptor();
+                let laddr = socket.local_addr().map(|a| a.to_string()).unwrap_or_default();
+                let dispatcher = shared.dispatcher.clone();
+                let session = shared.session();
+                shared.tracker.spawn(async move {
+                    let handshake = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await;
+                    let stream = match handshake {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            warn!("TLS handshake with {} failed: {}", addr, e);
+                            return;
+                        }
+                        Err(_) => {
+                            warn!("TLS handshake with {} timed out", addr);
+                            return;
+                        }
+                    };
+                    info!("Accepted TLS connection from {}", addr);
+                    if let Err(e) = handle_connection(stream, dispatcher, session, addr.to_string(), laddr).await {
+                        error!("Connection error: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!("TLS accept error: {}", e);
+            }
+        }
+    }
+}
+
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Local clients are trusted by filesystem permissions, so no DDoS accounting.
+#[cfg(unix)]
+async fn serve_unix(listener: tokio::net::UnixListener, path: String, shared: Listener) {
+    loop {
+        let accepted = tokio::select! {
+            _ = shared.shutdown.requested() => break,
+            res = listener.accept() => res,
+        };
+        match accepted {
+            Ok((socket, _)) => {
+                let addr = format!("{}:0", path);
+                let laddr = path.clone();
+                let dispatcher = shared.dispatcher.clone();
+                let session = shared.session();
+                shared.tracker.spawn(async move {
+                    if let Err(e) = handle_connection(socket, dispatcher, session, addr, laddr).await {
+                        error!("Connection error: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!("Unix accept error: {}", e);
+            }
+        }
+    }
+}
+
+/// Nagle off for latency; keepalive to detect dead peers (`tcp-keepalive`).
+fn tune_tcp(socket: &tokio::net::TcpStream, keepalive: Option<Duration>) {
     if let Err(e) = socket.set_nodelay(true) {
         log::warn!("Failed to set TCP_NODELAY: {}", e);
     }
+    if let Some(keepalive) = keepalive {
+        if let Err(e) = socket2::SockRef::from(socket).set_tcp_keepalive(&keepalive_params(keepalive)) {
+            log::warn!("Failed to set TCP keepalive: {}", e);
+        }
+    }
+}
+
+/// Like Redis: first probe after `time`, then every `time / 3`.
+fn keepalive_params(time: Duration) -> socket2::TcpKeepalive {
+    let params = socket2::TcpKeepalive::new().with_time(time);
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd", target_os = "windows"))]
+    let params = params.with_interval((time / 3).max(Duration::from_secs(1)));
+    params
+}
 
-    let mut connection = Connection::new(socket);
+async fn handle_connection<S: AsyncStream>(mut socket: S, dispatcher: Arc<Dispatcher>, session: SessionConfig, addr: String, laddr: String) -> anyhow::Result<()> {
+    let clients = dispatcher.clients();
+    let stats = dispatcher.stats();
+    ServerStats::incr(&stats.total_connections_received);
+    let Some(client) = clients.try_register(addr.clone(), laddr, session.maxclients) else {
+        ServerStats::incr(&stats.rejected_connections);
+        warn!("Connection from {} rejected: maxclients ({}) reached", addr, session.maxclients);
+        socket.write_all(b"-ERR max number of clients reached\r\n").await?;
+        return Ok(());
+    };
+    let result = run_session(socket, &dispatcher, session, &client).await;
+    clients.unregister(client.id);
+    result
+}
+
+async fn run_session<S: AsyncStream>(socket: S, dispatcher: &Arc<Dispatcher>, session: SessionConfig, client: &Arc<Client>) -> anyhow::Result<()> {
+    let mut connection = Connection::new(socket, session.limits);
 
     let mut txn_queue: Option<Vec<crate::core::protocol::RespFrame>> = None;
+    // Set when a command was rejected while queuing
+    let mut txn_dirty = false;
 
-    while let Some(frame) = connection.read_frame().await? {
-        use crate::core::protocol::RespFrame;
-        
-        // Helper to check command name
-        let cmd_name = if let RespFrame::Array(Some(ref frames)) = frame {
-            if !frames.is_empty() {
-                match &frames[0] {
-                    RespFrame::BulkString(Some(s)) => Some(s.to_uppercase()),
-                    RespFrame::SimpleString(s) => Some(s.to_uppercase()),
-                    _ => None,
-                }
+    let shutdown = dispatcher.shutdown();
+
+    loop {
+        // Stop reading new commands once shutdown begins; a command already
+        // executing runs to completion and its reply is flushed
+        // Subscribers never reach this read (they wait in handle_subscribe), so
+        // like Redis they are exempt from the idle timeout
+        // Read every round so CONFIG SET timeout reaches existing clients
+        let idle_timeout = dispatcher.config().read().timeout;
+        let idle = async {
+            if idle_timeout.is_zero() {
+                std::future::pending::<()>().await
             } else {
+                tokio::time::sleep(idle_timeout).await
+            }
+        };
+        let first = tokio::select! {
+            frame = connection.read_frame() => frame?,
+            _ = shutdown.requested() => None,
+            _ = client.killed() => None,
+            _ = idle => {
+                log::debug!("Closing idle client id={} addr={}", client.id, client.addr);
                 None
             }
-        } else {
-            None
         };
+        let Some(first) = first else { break };
+
+        // Drain every complete frame already buffered, then flush all replies at once
+        let mut pending = Some(first);
+        while let Some(frame) = pending.take().or_else(|| connection.next_buffered_frame()) {
+            use crate::core::protocol::RespFrame;
 
-        match cmd_name.as_deref() {
-            Some("MULTI") => {
-                if txn_queue.is_some() {
-                    connection.write_frame(&RespFrame::Error("ERR MULTI calls can not be nested".to_string())).await?;
+            // A pipeline whose replies pile up past the hard limit is cut off
+            if dispatcher.output_buffer_exceeded(client, connection.pending_output()) {
+                return Ok(());
+            }
+        
+            // Helper to check command name
+            let cmd_name = if let RespFrame::Array(Some(ref frames)) = frame {
+                if !frames.is_empty() {
+                    frames[0].as_bytes().map(|s| String::from_utf8_lossy(s).to_uppercase())
                 } else {
-                    txn_queue = Some(Vec::new());
-                    connection.write_frame(&RespFrame::SimpleString("OK".to_string())).await?;
+                    None
                 }
-                continue;
+            } else {
+                None
+            };
+
+            if let Some(name) = &cmd_name {
+                client.touch(name);
             }
-            Some("EXEC") => {
-                if let Some(queue) = txn_queue.take() {
-                    let res = dispatcher.execute_transaction(queue).await?;
-                    connection.write_frame(&res).await?;
-                } else {
-                    connection.write_frame(&RespFrame::Error("ERR EXEC without MULTI".to_string())).await?;
+            let reply_mode = client.reply_mode();
+
+            match cmd_name.as_deref() {
+                Some("MULTI") => {
+                    if txn_queue.is_some() {
+                        connection.queue_frame(&ZedisError::err("MULTI calls can not be nested").into());
+                    } else {
+                        txn_queue = Some(Vec::new());
+                        txn_dirty = false;
+                        client.set_multi(true);
+                        connection.queue_frame(&RespFrame::SimpleString("OK".to_string()));
+                    }
+                    continue;
+                }
+                Some("EXEC") => {
+                    if let Some(queue) = txn_queue.take() {
+                        client.set_multi(false);
+                        if txn_dirty {
+                            connection.queue_frame(&ZedisError::ExecAbort.into());
+                            continue;
+                        }
+                        let res = dispatcher.execute_transaction(client, queue).await?;
+                        connection.queue_frame(&res);
+                    } else {
+                        connection.queue_frame(&ZedisError::err("EXEC without MULTI").into());
+                    }
+                    continue;
+                }
+                Some("DISCARD") => {
+                    if txn_queue.is_some() {
+                        txn_queue = None;
+                        client.set_multi(false);
+                        connection.queue_frame(&RespFrame::SimpleString("OK".to_string()));
+                    } else {
+                        connection.queue_frame(&ZedisError::err("DISCARD without MULTI").into());
+                    }
+                    continue;
+                }
+                Some("HELLO") => {
+                    if let RespFrame::Array(Some(ref frames)) = frame {
+                        let reply = handle_hello(frames, dispatcher, &mut connection, client);
+                        connection.queue_frame(&reply);
+                    }
+                    continue;
+                }
+                Some("MONITOR") => {
+                    // Whatever the client sends next ends MONITOR mode and runs as usual
+                    pending = dispatcher.handle_monitor(client, &mut connection).await?;
+                    continue;
+                }
+                Some("SUBSCRIBE" | "PSUBSCRIBE" | "UNSUBSCRIBE" | "PUNSUBSCRIBE") => {
+                    // Pub/sub mode until the client holds no subscriptions
+                    if let RespFrame::Array(Some(ref frames)) = frame {
+                         dispatcher.handle_subscribe(client, frames, &mut connection).await?;
+                    }
+                    continue;
+                }
+                _ => {
+                    // Logic fall-through
                 }
-                continue;
             }
-            Some("DISCARD") => {
-                if txn_queue.is_some() {
-                    txn_queue = None;
-                    connection.write_frame(&RespFrame::SimpleString("OK".to_string())).await?;
-                } else {
-                    connection.write_frame(&RespFrame::Error("ERR DISCARD without MULTI".to_string())).await?;
+
+            // Processing
+            if let Some(queue) = &mut txn_queue {
+                // Buffer
+                if let Err(e) = dispatcher.check_queued(&frame) {
+                    txn_dirty = true;
+                    connection.queue_frame(&e.into());
+                    continue;
+                }
+                queue.push(frame);
+                connection.queue_frame(&RespFrame::SimpleString("QUEUED".to_string()));
+            } else {
+                // Normal Execute
+                let response = dispatcher.execute(client, frame).await?;
+                if client.take_reply_permit(reply_mode) {
+                    connection.queue_frame(&response);
+                }
+            }
+        }
+        let (qbuf, qbuf_free) = connection.query_buffer();
+        client.set_buffers(qbuf, qbuf_free, connection.pending_output());
+        if dispatcher.output_buffer_exceeded(client, connection.pending_output()) {
+            return Ok(());
+        }
+        // A reader slow enough to keep output over the soft limit past its
+        // window is disconnected mid-write
+        if !connection.flush_until(dispatcher.clients().output_flush_deadline(client)).await? {
+            dispatcher.output_buffer_disconnect(client);
+            return Ok(());
+        }
+        dispatcher.clients().output_limit_reached(client, 0);
+        if shutdown.is_requested() || client.is_killed() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+fn handle_hello(
+    frames: &[crate::core::protocol::RespFrame],
+    dispatcher: &Dispatcher,
+    connection: &mut Connection<impl AsyncStream>,
+    client: &Client,
+) -> crate::core::protocol::RespFrame {
+    use crate::core::protocol::{ProtocolVersion, RespFrame};
+
+    let mut protocol = connection.protocol;
+    if frames.len() > 1 {
+        protocol = match frames[1].as_str().and_then(|s| s.parse::<i64>().ok()) {
+            Some(2) => ProtocolVersion::Resp2,
+            Some(3) => ProtocolVersion::Resp3,
+            Some(_) => return ZedisError::NoProto.into(),
+            None => return ZedisError::err("Protocol version is not an integer or out of range").into(),
+        };
+    }
+
+    let mut user = None;
+    let mut name = None;
+    let mut i = 2;
+    while i < frames.len() {
+        let opt = frames[i].as_str().map(|s| s.to_uppercase());
+        match opt.as_deref() {
+            Some("AUTH") if i + 2 < frames.len() => {
+                let (u, p) = match (frames[i + 1].as_str(), frames[i + 2].as_str()) {
+                    (Some(u), Some(p)) => (u, p),
+                    _ => return ZedisError::err("syntax error in HELLO option 'auth'").into(),
+                };
+                if !dispatcher.authenticate(u, p) {
+                    return ZedisError::WrongPass.into();
                 }
-                continue;
+                user = Some(u.to_string());
+                i += 3;
             }
-            Some("SUBSCRIBE") => {
-                // Hand off control to dispatcher's subscribe loop
-                if let RespFrame::Array(Some(ref frames)) = frame {
-                     dispatcher.handle_subscribe(frames, &mut connection).await?;
+            Some("SETNAME") if i + 1 < frames.len() => {
+                match frames[i + 1].as_str() {
+                    Some(n) if !n.contains([' ', '\n']) => name = Some(n.to_string()),
+                    _ => return ZedisError::err("Client names cannot contain spaces, newlines or special characters.").into(),
                 }
-                continue;
+                i += 2;
             }
             _ => {
-                // Logic fall-through
+                let opt = frames[i].as_bytes().map(|b| String::from_utf8_lossy(b).into_owned()).unwrap_or_default();
+                return ZedisError::err(format!("Syntax error in HELLO option '{}'", opt)).into();
             }
         }
+    }
 
-        // Processing
-        if let Some(queue) = &mut txn_queue {
-            // Buffer
-            queue.push(frame);
-            connection.write_frame(&RespFrame::SimpleString("QUEUED".to_string())).await?;
-        } else {
-            // Normal Execute
-            let response = dispatcher.execute(frame).await?;
-            connection.write_frame(&response).await?;
-        }
+    // Only commit the negotiation once every option has been validated
+    connection.protocol = protocol;
+    client.set_resp(protocol.as_i64());
+    if let Some(u) = user {
+        client.set_user(u);
+    }
+    if name.is_some() {
+        client.set_name(name);
     }
 
-    Ok(())
+    RespFrame::Map(vec![
+        (RespFrame::bulk("server"), RespFrame::bulk("zedis")),
+        (RespFrame::bulk("version"), RespFrame::bulk(env!("CARGO_PKG_VERSION"))),
+        (RespFrame::bulk("proto"), RespFrame::Integer(protocol.as_i64())),
+        (RespFrame::bulk("id"), RespFrame::Integer(client.id as i64)),
+        (RespFrame::bulk("mode"), RespFrame::bulk("standalone")),
+        (RespFrame::bulk("role"), RespFrame::bulk("master")),
+        (RespFrame::bulk("modules"), RespFrame::Array(Some(Vec::new()))),
+    ])
 }
 
+/// Effective maxmemory and policy: explicit settings, or the Auto-Tuner's
+/// advice for whichever is `auto`.
+fn memory_limits(config: &Config, advice: MemoryAdvice) -> (u64, EvictionPolicy) {
+    let maxmemory = config.maxmemory.map_or(advice.maxmemory, |m| m as u64);
+    (maxmemory, config.maxmemory_policy.unwrap_or(advice.policy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::protocol::RespFrame;
+    use crate::persistence::Persistence;
+    use bytes::Bytes;
+
+    fn test_config(name: &str) -> Config {
+        let dir = std::env::temp_dir().join(format!("zedis-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Config {
+            appendonly: true,
+            appendfilename: dir.join("appendonly.aof").to_string_lossy().into_owned(),
+            dbfilename: dir.join("dump.rdb").to_string_lossy().into_owned(),
+            ..Config::default()
+        }
+    }
+
+    /// The startup sequence of `run`, without listeners.
+    async fn start(config: &Config) -> (Arc<Keyspace>, Arc<AofManager>, Dispatcher) {
+        let from_aof = aof_is_authoritative(config);
+        let keyspace = Arc::new(open_keyspace(config, from_aof));
+        let aof = Arc::new(AofManager::with_policy(&config.appendfilename, false, config.appendfsync).unwrap());
+        let db = keyspace.db(0).clone();
+        let dispatcher = Dispatcher::new(
+            keyspace.clone(),
+            aof.clone(),
+            Arc::new(ConfigStore::new(config.clone())),
+            None,
+            Arc::new(Evictor::new(0, EvictionPolicy::NoEviction, 5)),
+            Subsystems {
+                hw: Arc::new(HardwareManager::new()),
+                flow: Arc::new(FlowManager::new(db, None)),
+                mask: Arc::new(MaskStatus::default()),
+            },
+        );
+        if from_aof {
+            replay_aof(&dispatcher, &config.appendfilename).await;
+        }
+        if config.appendonly {
+            aof.enable();
+            if !from_aof {
+                Persistence::seed_aof(&keyspace, &aof);
+            }
+        }
+        (keyspace, aof, dispatcher)
+    }
+
+    /// Final AOF flush and shutdown snapshot, as after SIGTERM.
+    fn stop(config: &Config, keyspace: &Keyspace, aof: &AofManager) {
+        aof.shutdown();
+        Persistence::save_rdb(keyspace, &config.dbfilename).unwrap();
+    }
+
+    async fn exec(dispatcher: &Dispatcher, args: &[&str]) -> RespFrame {
+        let frames = args.iter().map(|a| RespFrame::bulk(a.to_string())).collect();
+        dispatcher.execute(&Client::internal("test"), RespFrame::Array(Some(frames))).await.unwrap()
+    }
+
+    /// Serve one in-memory client connection; returns the client's end.
+    fn connect(dispatcher: &Arc<Dispatcher>, maxclients: usize) -> tokio::io::DuplexStream {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let session = SessionConfig { limits: ProtocolLimits::default(), maxclients };
+        tokio::spawn(handle_connection(server, dispatcher.clone(), session, "127.0.0.1:50000".to_string(), "127.0.0.1:6379".to_string()));
+        client
+    }
+
+    /// Send `request` and read until the replies end with `suffix`.
+    async fn roundtrip(stream: &mut tokio::io::DuplexStream, request: &[u8], suffix: &[u8]) -> Vec<u8> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        AsyncWriteExt::write_all(stream, request).await.unwrap();
+        let mut replies = Vec::new();
+        while !replies.ends_with(suffix) {
+            let mut chunk = [0; 1024];
+            let n = tokio::time::timeout(Duration::from_secs(5), AsyncReadExt::read(stream, &mut chunk)).await.unwrap().unwrap();
+            assert!(n > 0, "closed after {:?}", String::from_utf8_lossy(&replies));
+            replies.extend_from_slice(&chunk[..n]);
+        }
+        replies
+    }
+
+    fn assert_dataset(keyspace: &Keyspace) {
+        let db = keyspace.db(0);
+        assert_eq!(db.get_string(b"c").unwrap(), Some(Bytes::from("1")));
+        assert_eq!(db.list_range(b"l", 0, -1).unwrap(), vec![Bytes::from("a")]);
+    }
+
+    #[tokio::test]
+    async fn clean_restart_applies_writes_once() {
+        let config = test_config("restart");
+        let (keyspace, aof, dispatcher) = start(&config).await;
+        exec(&dispatcher, &["INCR", "c"]).await;
+        exec(&dispatcher, &["RPUSH", "l", "a"]).await;
+        stop(&config, &keyspace, &aof);
+
+        // Twice, so a restart on top of a restarted dataset is covered too
+        for _ in 0..2 {
+            let (keyspace, aof, _dispatcher) = start(&config).await;
+            assert_dataset(&keyspace);
+            stop(&config, &keyspace, &aof);
+        }
+    }
+
+    #[tokio::test]
+    async fn aof_replay_keeps_absolute_deadlines() {
+        let config = test_config("pxat");
+        let (keyspace, aof, dispatcher) = start(&config).await;
+        exec(&dispatcher, &["SET", "session", "v", "EX", "100"]).await;
+        exec(&dispatcher, &["SET", "k", "v"]).await;
+        exec(&dispatcher, &["EXPIRE", "k", "200"]).await;
+        exec(&dispatcher, &["SET", "gone", "v", "PX", "20"]).await;
+        let session_at = keyspace.db(0).expire_time(b"session");
+        let k_at = keyspace.db(0).expire_time(b"k");
+        stop(&config, &keyspace, &aof);
+
+        let log = std::fs::read_to_string(&config.appendfilename).unwrap();
+        assert!(log.contains("PXAT") && log.contains("PEXPIREAT"), "relative TTLs must be logged as deadlines");
+
+        // Replayed later, the deadlines are the ones set, not restarted from
+        // the replay, and what expired in between stays gone
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let (keyspace, aof, _dispatcher) = start(&config).await;
+        let db = keyspace.db(0);
+        assert_eq!(db.expire_time(b"session"), session_at);
+        assert_eq!(db.expire_time(b"k"), k_at);
+        assert!(!db.exists(b"gone"));
+        stop(&config, &keyspace, &aof);
+    }
+
+    #[tokio::test]
+    async fn aof_enabled_over_rdb_keeps_the_snapshot() {
+        let mut config = test_config("seed");
+        config.appendonly = false;
+        let (keyspace, aof, dispatcher) = start(&config).await;
+        exec(&dispatcher, &["INCR", "c"]).await;
+        exec(&dispatcher, &["RPUSH", "l", "a"]).await;
+        stop(&config, &keyspace, &aof);
+
+        // First start with appendonly loads the RDB and seeds the AOF from it;
+        // the next one loads that AOF alone
+        config.appendonly = true;
+        for _ in 0..2 {
+            let (keyspace, aof, _dispatcher) = start(&config).await;
+            assert_dataset(&keyspace);
+            stop(&config, &keyspace, &aof);
+        }
+    }
+
+    #[tokio::test]
+    async fn hello_3_switches_the_connection_to_resp3() {
+        let mut config = test_config("hello");
+        config.appendonly = false;
+        let (_keyspace, _aof, dispatcher) = start(&config).await;
+        let mut stream = connect(&Arc::new(dispatcher), 10);
+
+        roundtrip(&mut stream, b"HSET h f v\r\n", b":1\r\n").await;
+        let flat = b"*2\r\n$1\r\nf\r\n$1\r\nv\r\n";
+        assert_eq!(roundtrip(&mut stream, b"HGETALL h\r\n", flat).await, flat);
+        // A rejected version leaves the protocol alone
+        roundtrip(&mut stream, b"HELLO 4\r\nHGETALL h\r\n", b"-NOPROTO unsupported protocol version\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n").await;
+
+        let hello = roundtrip(&mut stream, b"HELLO 3\r\n", b"$7\r\nmodules\r\n*0\r\n").await;
+        let hello = String::from_utf8_lossy(&hello);
+        assert!(hello.starts_with("%7\r\n$6\r\nserver\r\n"), "{:?}", hello);
+        assert!(hello.contains("$5\r\nproto\r\n:3\r\n"), "{:?}", hello);
+        let map = b"%1\r\n$1\r\nf\r\n$1\r\nv\r\n";
+        assert_eq!(roundtrip(&mut stream, b"HGETALL h\r\n", map).await, map);
+    }
+
+    #[tokio::test]
+    async fn binary_keys_and_values_survive_aof_and_rdb() {
+        let mut config = test_config("binary");
+        let (keyspace, aof, dispatcher) = start(&config).await;
+        let mut parser = crate::core::protocol::RequestParser::default();
+        let mut input = bytes::BytesMut::from(&b"*3\r\n$3\r\nSET\r\n$3\r\n\xff\x00\xfe\r\n$3\r\n\xfe\x00\xff\r\n\
+            *4\r\n$4\r\nHSET\r\n$2\r\nh\xff\r\n$2\r\n\x00f\r\n$2\r\nv\x80\r\n"[..]);
+        while let Some(frame) = parser.parse(&mut input).unwrap() {
+            dispatcher.execute(&Client::internal("test"), frame).await.unwrap();
+        }
+        let check = |keyspace: &Keyspace| {
+            let db = keyspace.db(0);
+            assert_eq!(db.get_string(b"\xff\x00\xfe").unwrap(), Some(Bytes::from_static(b"\xfe\x00\xff")));
+            assert_eq!(db.hash_get(b"h\xff", b"\x00f").unwrap(), Some(Bytes::from_static(b"v\x80")));
+        };
+        check(&keyspace);
+        stop(&config, &keyspace, &aof);
+
+        // Replayed from the AOF, then loaded from the RDB alone
+        let (keyspace, aof, _dispatcher) = start(&config).await;
+        check(&keyspace);
+        stop(&config, &keyspace, &aof);
+        std::fs::remove_file(&config.appendfilename).unwrap();
+        config.appendonly = false;
+        let (keyspace, aof, _dispatcher) = start(&config).await;
+        check(&keyspace);
+        stop(&config, &keyspace, &aof);
+    }
+
+    #[tokio::test]
+    async fn connections_over_maxclients_are_refused() {
+        use tokio::io::AsyncReadExt;
+        let mut config = test_config("maxclients");
+        config.appendonly = false;
+        let (_keyspace, _aof, dispatcher) = start(&config).await;
+        let dispatcher = Arc::new(dispatcher);
+
+        let mut first = connect(&dispatcher, 1);
+        roundtrip(&mut first, b"PING\r\n", b"+PONG\r\n").await;
+        let mut second = connect(&dispatcher, 1);
+        let mut refused = String::new();
+        second.read_to_string(&mut refused).await.unwrap();
+        assert_eq!(refused, "-ERR max number of clients reached\r\n");
+        assert_eq!(dispatcher.stats().rejected_connections.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        // The slot is free again once the first client disconnects
+        drop(first);
+        for _ in 0..100 {
+            if dispatcher.clients().all().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut third = connect(&dispatcher, 1);
+        roundtrip(&mut third, b"PING\r\n", b"+PONG\r\n").await;
+    }
+}
@@ -0,0 +1,84 @@
+use parking_lot::Mutex;
+use tokio_util::sync::CancellationToken;
+use log::{info, error};
+
+/// What to do with the dataset on the way out (`SHUTDOWN [NOSAVE|SAVE]`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveMode {
+    /// Follow the `save_on_shutdown` setting.
+    Default,
+    Save,
+    NoSave,
+}
+
+/// Process-wide shutdown signal shared by listeners, connections and the
+/// SHUTDOWN command. Requesting it twice keeps the first save mode.
+pub struct Shutdown {
+    token: CancellationToken,
+    mode: Mutex<Option<SaveMode>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            mode: Mutex::new(None),
+        }
+    }
+
+    pub fn request(&self, mode: SaveMode) {
+        let mut current = self.mode.lock();
+        if current.is_none() {
+            *current = Some(mode);
+        }
+        self.token.cancel();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once shutdown has been requested.
+    pub async fn requested(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn save_mode(&self) -> SaveMode {
+        self.mode.lock().unwrap_or(SaveMode::Default)
+    }
+
+    /// Turn SIGTERM / SIGINT into a shutdown request.
+    pub async fn listen_for_signals(&self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut sigterm = match signal(SignalKind::terminate()) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("🛑 Shutdown: cannot install SIGTERM handler: {}", e);
+                    let _ = tokio::signal::ctrl_c().await;
+                    info!("🛑 Shutdown: received SIGINT");
+                    self.request(SaveMode::Default);
+                    return;
+                }
+            };
+            tokio::select! {
+                _ = sigterm.recv() => info!("🛑 Shutdown: received SIGTERM"),
+                _ = tokio::signal::ctrl_c() => info!("🛑 Shutdown: received SIGINT"),
+                _ = self.requested() => return,
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => info!("🛑 Shutdown: received Ctrl-C"),
+                _ = self.requested() => return,
+            }
+        }
+        self.request(SaveMode::Default);
+    }
+}